@@ -1,83 +1,129 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use anyhow::Result;
-use serde::{Serialize, Deserialize};
+use anyhow::{Context, Result};
+
+use crate::models::profile::default_profile;
+use crate::utils::session_file::SESSION_FILE_SUFFIX;
+pub use crate::models::Profile;
 
 pub const GOOSE_GLOBAL_PATH: &str = "~/.config/goose";
 pub const PROFILES_CONFIG_PATH: &str = "~/.config/goose/profiles.yaml";
 pub const SESSIONS_PATH: &str = "~/.config/goose/sessions";
-pub const SESSION_FILE_SUFFIX: &str = ".jsonl";
+pub const STATS_PATH: &str = "~/.config/goose/stats.jsonl";
 pub const LOG_PATH: &str = "~/.config/goose/logs";
 pub const RECOMMENDED_DEFAULT_PROVIDER: &str = "openai";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Profile {
-    // TODO: Define profile structure
+pub fn sessions_dir() -> PathBuf {
+    let path: PathBuf = shellexpand::tilde(SESSIONS_PATH).into_owned().into();
+    std::fs::create_dir_all(&path).unwrap();
+    path
 }
 
 pub fn session_path(name: &str) -> PathBuf {
-    let mut path: PathBuf = shellexpand::tilde(SESSIONS_PATH).into_owned().into();
-    std::fs::create_dir_all(&path).unwrap();
-    path.push(format!("{}{}", name, SESSION_FILE_SUFFIX));
-    path
+    sessions_dir().join(format!("{}{}", name, SESSION_FILE_SUFFIX))
 }
 
-pub fn write_config(profiles: &std::collections::HashMap<String, Profile>) -> Result<()> {
-    let config_path = shellexpand::tilde(PROFILES_CONFIG_PATH).into_owned();
-    let config_dir = Path::new(&config_path).parent().unwrap();
-    std::fs::create_dir_all(config_dir)?;
-    
+pub fn write_config(profiles: &HashMap<String, Profile>) -> Result<()> {
+    write_profiles(Path::new(shellexpand::tilde(PROFILES_CONFIG_PATH).as_ref()), profiles)
+}
+
+pub fn write_profiles(path: &Path, profiles: &HashMap<String, Profile>) -> Result<()> {
+    if let Some(config_dir) = path.parent() {
+        std::fs::create_dir_all(config_dir)?;
+    }
+
     let yaml = serde_yaml::to_string(profiles)?;
-    std::fs::write(config_path, yaml)?;
+    std::fs::write(path, yaml)?;
     Ok(())
 }
 
+/// Load the named profile from the profiles config, adding a default profile
+/// if the config or the profile does not exist yet
 pub fn ensure_config(name: Option<&str>) -> Result<(String, Profile)> {
-    let default_profile_name = "default".to_string();
-    let name = name.map(|s| s.to_string()).unwrap_or(default_profile_name.clone());
-    
-    // TODO: Load plugins and get default model configuration
-    let provider = RECOMMENDED_DEFAULT_PROVIDER;
-    let processor = "gpt-4";  // TODO: Get from provider
-    let accelerator = "none";  // TODO: Get from provider
-    
-    let default_profile = Profile {
-        // TODO: Create default profile
-    };
-    
-    let config_path = shellexpand::tilde(PROFILES_CONFIG_PATH).into_owned();
-    if !Path::new(&config_path).exists() {
-        println!("No configuration present, we will create a profile '{}' at: {}\n\
+    ensure_profile(Path::new(shellexpand::tilde(PROFILES_CONFIG_PATH).as_ref()), name)
+}
+
+pub fn ensure_profile(path: &Path, name: Option<&str>) -> Result<(String, Profile)> {
+    let name = name.unwrap_or("default").to_string();
+
+    let (provider, processor, accelerator) = default_model_configuration();
+    let default_profile = default_profile(provider, processor, accelerator);
+
+    if !path.exists() {
+        // Notices go to stderr so they never mix with JSON output
+        eprintln!("No configuration present, we will create a profile '{}' at: {}\n\
                  You can add your own profile in this file to further configure goose!", 
-                name, config_path);
-        let mut profiles = std::collections::HashMap::new();
+                name, path.display());
+        let mut profiles = HashMap::new();
         profiles.insert(name.clone(), default_profile.clone());
-        write_config(&profiles)?;
+        write_profiles(path, &profiles)?;
         return Ok((name, default_profile));
     }
     
-    let mut profiles = read_config()?;
+    let mut profiles = read_profiles(path)?;
     if let Some(profile) = profiles.get(&name) {
         Ok((name, profile.clone()))
     } else {
-        println!("Your configuration doesn't have a profile named '{}', adding one now", name);
+        eprintln!("Your configuration doesn't have a profile named '{}', adding one now", name);
         profiles.insert(name.clone(), default_profile.clone());
-        write_config(&profiles)?;
+        write_profiles(path, &profiles)?;
         Ok((name, default_profile))
     }
 }
 
-pub fn read_config() -> Result<std::collections::HashMap<String, Profile>> {
-    let config_path = shellexpand::tilde(PROFILES_CONFIG_PATH).into_owned();
-    let content = std::fs::read_to_string(config_path)?;
-    let profiles: std::collections::HashMap<String, Profile> = serde_yaml::from_str(&content)?;
+pub fn read_config() -> Result<HashMap<String, Profile>> {
+    read_profiles(Path::new(shellexpand::tilde(PROFILES_CONFIG_PATH).as_ref()))
+}
+
+pub fn read_profiles(path: &Path) -> Result<HashMap<String, Profile>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read profiles from {}", path.display()))?;
+    let profiles: HashMap<String, Profile> = serde_yaml::from_str(&content)
+        .with_context(|| format!("Failed to parse profiles in {}", path.display()))?;
     Ok(profiles)
 }
 
+/// Pick the provider and models for a new profile, preferring the first
+/// provider whose API key is available
 pub fn default_model_configuration() -> (String, String, String) {
-    // TODO: Load and check providers
-    (
-        RECOMMENDED_DEFAULT_PROVIDER.to_string(),
-        "gpt-4".to_string(),  // TODO: Get from provider
-        "none".to_string(),   // TODO: Get from provider
-    )
-}
\ No newline at end of file
+    let provider = ["openai", "anthropic"].into_iter()
+        .find(|provider| std::env::var(format!("{}_API_KEY", provider.to_uppercase())).is_ok())
+        .unwrap_or(RECOMMENDED_DEFAULT_PROVIDER);
+
+    let (processor, accelerator) = match provider {
+        "anthropic" => ("claude-3-5-sonnet-latest", "claude-3-5-haiku-latest"),
+        _ => ("gpt-4o", "gpt-4o-mini"),
+    };
+    (provider.to_string(), processor.to_string(), accelerator.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_config_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("goose-config-{}", uuid::Uuid::new_v4()))
+            .join("profiles.yaml")
+    }
+
+    #[test]
+    fn test_ensure_profile_creates_and_reads_config() {
+        let path = temp_config_path();
+
+        let (name, created) = ensure_profile(&path, None).unwrap();
+        assert_eq!(name, "default");
+        assert!(path.exists());
+
+        // The second call reads the profile back rather than creating it
+        let (_, read) = ensure_profile(&path, Some("default")).unwrap();
+        assert_eq!(read.processor, created.processor);
+
+        // Unknown profiles are added alongside the existing ones
+        ensure_profile(&path, Some("work")).unwrap();
+        let profiles = read_profiles(&path).unwrap();
+        assert!(profiles.contains_key("default") && profiles.contains_key("work"));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
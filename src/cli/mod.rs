@@ -1,2 +1,3 @@
 pub mod config;
+pub mod output;
 pub mod session;
\ No newline at end of file
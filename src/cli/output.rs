@@ -0,0 +1,394 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, Result};
+use colored::*;
+use serde::Serialize;
+
+use crate::cli::session::RunOutcome;
+use crate::exchange::{Message, MessageObserver, StreamEvent};
+use crate::models::message::Content;
+use crate::models::Usage;
+
+/// How a session reports what happens in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Colored output for people, streamed as it arrives
+    Text,
+    /// A JSON array of every event, written when the session ends
+    Json,
+    /// One JSON event per line, written as it happens
+    StreamJson,
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "stream-json" => Ok(OutputFormat::StreamJson),
+            _ => Err(anyhow!("Unknown output format: {}, expected text, json or stream-json", s)),
+        }
+    }
+}
+
+/// Something that happened in a session
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputEvent {
+    SessionStart {
+        session: String,
+        profile: String,
+        session_file: String,
+        resumed: bool,
+    },
+    /// A new session was named after its first prompt
+    SessionRenamed {
+        session: String,
+        session_file: String,
+    },
+    /// Where a resumed conversation left off
+    Recap {
+        message_count: usize,
+        last_prompt: Option<String>,
+        last_reply: Option<String>,
+        /// How far the session's plan has come, if it has one
+        plan: Option<String>,
+    },
+    UserMessage {
+        text: String,
+    },
+    AssistantText {
+        text: String,
+    },
+    ToolCall {
+        id: String,
+        name: String,
+        parameters: serde_json::Value,
+    },
+    ToolResult {
+        tool_use_id: String,
+        output: String,
+        is_error: bool,
+    },
+    /// Tokens a model used during the last turn
+    Usage {
+        model: String,
+        #[serde(flatten)]
+        usage: Usage,
+    },
+    Error {
+        message: String,
+        hint: String,
+    },
+    /// How a single-pass run ended, always the last event of one
+    Result {
+        /// `None` when the run failed before its session started
+        session: Option<String>,
+        outcome: RunOutcome,
+        exit_code: i32,
+        /// The final answer, when the run completed
+        text: Option<String>,
+        tool_calls: u32,
+        #[serde(flatten)]
+        usage: Usage,
+        cost: f64,
+    },
+}
+
+impl OutputEvent {
+    /// The events for a message added to the history
+    pub fn from_message(message: &Message) -> Vec<OutputEvent> {
+        if message.is_user() && message.tool_result().is_empty() {
+            return vec![OutputEvent::UserMessage { text: message.text() }];
+        }
+
+        let mut events = Vec::new();
+        let text = message.text();
+        if !text.is_empty() {
+            events.push(OutputEvent::AssistantText { text });
+        }
+        for content in &message.content {
+            match content {
+                Content::ToolUse { id, name, parameters } => events.push(OutputEvent::ToolCall {
+                    id: id.clone(),
+                    name: name.clone(),
+                    parameters: parameters.clone(),
+                }),
+                Content::ToolResult { tool_use_id, output, is_error } => events.push(OutputEvent::ToolResult {
+                    tool_use_id: tool_use_id.clone(),
+                    output: output.clone(),
+                    is_error: *is_error,
+                }),
+                _ => {}
+            }
+        }
+        events
+    }
+}
+
+/// Shows a session's events to whoever runs it
+pub trait Renderer: Send {
+    fn render(&mut self, event: &OutputEvent);
+
+    /// A piece of a response as it streams in, before the complete message
+    /// is rendered as events
+    fn render_stream(&mut self, _event: &StreamEvent) {}
+
+    /// Write out anything held back until the session ends
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A renderer shared between a session and the observer on its exchange
+pub type SharedRenderer = Arc<Mutex<Box<dyn Renderer>>>;
+
+pub fn create_renderer(format: OutputFormat) -> Box<dyn Renderer> {
+    match format {
+        OutputFormat::Text => Box::new(TextRenderer::default()),
+        OutputFormat::Json => Box::new(JsonRenderer::new(Box::new(std::io::stdout()), false)),
+        OutputFormat::StreamJson => Box::new(JsonRenderer::new(Box::new(std::io::stdout()), true)),
+    }
+}
+
+/// Renders every message added to the exchange's history
+pub struct RendererObserver(pub SharedRenderer);
+
+impl MessageObserver for RendererObserver {
+    fn on_message(&self, message: &Message) -> Result<()> {
+        let mut renderer = self.0.lock().unwrap();
+        for event in OutputEvent::from_message(message) {
+            renderer.render(&event);
+        }
+        Ok(())
+    }
+}
+
+/// Colored terminal output, streaming responses as they arrive
+///
+/// Assistant messages are shown from the stream, so their events only end
+/// the streamed line.
+pub struct TextRenderer {
+    profile: String,
+    /// The thinking indicator is showing
+    thinking: bool,
+    at_line_start: bool,
+}
+
+impl Default for TextRenderer {
+    fn default() -> Self {
+        Self { profile: "default".to_string(), thinking: false, at_line_start: true }
+    }
+}
+
+impl TextRenderer {
+    fn end_line(&mut self) {
+        if self.thinking {
+            print!("\r\x1B[K"); // Nothing arrived, still clear the thinking indicator
+            self.thinking = false;
+        } else if !self.at_line_start {
+            println!();
+        }
+        self.at_line_start = true;
+    }
+}
+
+impl Renderer for TextRenderer {
+    fn render(&mut self, event: &OutputEvent) {
+        match event {
+            OutputEvent::SessionStart { session, profile, session_file, resumed } => {
+                self.profile = profile.clone();
+                let action = if *resumed { "resuming" } else { "starting" };
+                println!("{}", format!("{} session | name: {} profile: {}",
+                    action, session.cyan(), profile.cyan()).dimmed());
+                println!("{}", format!("saving to {}", session_file).dimmed());
+            }
+            OutputEvent::SessionRenamed { session, session_file } => {
+                self.end_line();
+                println!("{}", format!("named session {} | saving to {}", session.cyan(), session_file).dimmed());
+            }
+            OutputEvent::Recap { last_prompt: None, .. } => {
+                println!("{}", "The session has no messages yet.".dimmed());
+            }
+            OutputEvent::Recap { message_count, last_prompt: Some(last_prompt), last_reply, plan } => {
+                println!("{}", format!("{} messages so far, the last exchange was:", message_count).dimmed());
+                println!("{} {}", "you:".cyan(), last_prompt);
+                if let Some(reply) = last_reply {
+                    println!("{} {}", "goose:".green(), reply);
+                }
+                if let Some(plan) = plan {
+                    println!("{}", plan.dimmed());
+                }
+                println!();
+            }
+            OutputEvent::UserMessage { .. } => {
+                print!("Thinking... ");
+                self.thinking = true;
+            }
+            OutputEvent::AssistantText { .. } | OutputEvent::ToolCall { .. } => self.end_line(),
+            OutputEvent::ToolResult { output, is_error, .. } => {
+                if *is_error {
+                    println!("{}", output.red());
+                }
+            }
+            OutputEvent::Usage { .. } => {}
+            OutputEvent::Error { message, hint } => {
+                self.end_line();
+                println!("{}", message.red());
+                println!("{}", hint.yellow());
+            }
+            OutputEvent::Result { session: None, .. } => self.end_line(),
+            OutputEvent::Result { session: Some(session), .. } => {
+                self.end_line();
+                println!("{}", format!("ended run | name: {} profile: {}", session.cyan(), self.profile.cyan()).dimmed());
+                println!("{}", format!("to resume: goose session resume {} --profile {}", session, self.profile).dimmed());
+            }
+        }
+        let _ = std::io::stdout().flush();
+    }
+
+    fn render_stream(&mut self, event: &StreamEvent) {
+        if self.thinking {
+            print!("\r\x1B[K"); // Clear the thinking indicator
+            self.thinking = false;
+            self.at_line_start = true;
+        }
+
+        match event {
+            StreamEvent::Text(text) => {
+                print!("{}", text);
+                self.at_line_start = text.ends_with('\n');
+            }
+            StreamEvent::ToolCall { name, arguments, .. } => {
+                if let Some(name) = name {
+                    if !self.at_line_start {
+                        println!();
+                    }
+                    print!("{} ", format!("─── {} ───", name).dimmed());
+                }
+                print!("{}", arguments.dimmed());
+                self.at_line_start = false;
+            }
+            StreamEvent::Usage(_) => {}
+        }
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Events as JSON, for scripts and CI jobs rather than people
+pub struct JsonRenderer {
+    writer: Box<dyn Write + Send>,
+    /// Write each event as a line when it happens, instead of all of them as
+    /// one array at the end
+    stream: bool,
+    events: Vec<OutputEvent>,
+}
+
+impl JsonRenderer {
+    pub fn new(writer: Box<dyn Write + Send>, stream: bool) -> Self {
+        Self { writer, stream, events: Vec::new() }
+    }
+}
+
+impl Renderer for JsonRenderer {
+    fn render(&mut self, event: &OutputEvent) {
+        if self.stream {
+            let line = serde_json::to_string(event).expect("output events serialize to JSON");
+            let _ = writeln!(self.writer, "{}", line);
+            let _ = self.writer.flush();
+        } else {
+            self.events.push(event.clone());
+        }
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if !self.stream {
+            writeln!(self.writer, "{}", serde_json::to_string_pretty(&self.events)?)?;
+            self.events.clear();
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::Role;
+
+    /// Collects what a renderer writes
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    fn tool_call() -> Message {
+        Message::new(Role::Assistant, vec![
+            Content::Text { text: "Let me look".to_string() },
+            Content::ToolUse {
+                id: "call_1".to_string(),
+                name: "bash".to_string(),
+                parameters: serde_json::json!({"command": "ls"}),
+            },
+        ])
+    }
+
+    #[test]
+    fn test_events_from_messages() {
+        let events = OutputEvent::from_message(&tool_call());
+        assert!(matches!(&events[..], [OutputEvent::AssistantText { .. }, OutputEvent::ToolCall { .. }]));
+
+        let result = Message::new(Role::User, vec![Content::ToolResult {
+            tool_use_id: "call_1".to_string(),
+            output: "src".to_string(),
+            is_error: false,
+        }]);
+        assert!(matches!(&OutputEvent::from_message(&result)[..], [OutputEvent::ToolResult { .. }]));
+        assert!(matches!(&OutputEvent::from_message(&Message::user("Hi"))[..], [OutputEvent::UserMessage { .. }]));
+    }
+
+    #[test]
+    fn test_stream_json_writes_a_line_per_event() {
+        let buffer = Buffer::default();
+        let renderer: SharedRenderer = Arc::new(Mutex::new(Box::new(JsonRenderer::new(Box::new(buffer.clone()), true))));
+        RendererObserver(renderer.clone()).on_message(&tool_call()).unwrap();
+        renderer.lock().unwrap().render(&OutputEvent::Usage { model: "gpt-4o".to_string(), usage: Usage::new(10, 2) });
+
+        let lines: Vec<serde_json::Value> = buffer.contents().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["type"], "assistant_text");
+        assert_eq!(lines[1]["type"], "tool_call");
+        assert_eq!(lines[1]["parameters"]["command"], "ls");
+        assert_eq!(lines[2]["input_tokens"], 10);
+    }
+
+    #[test]
+    fn test_json_writes_every_event_at_the_end() {
+        let buffer = Buffer::default();
+        let mut renderer = JsonRenderer::new(Box::new(buffer.clone()), false);
+        renderer.render(&OutputEvent::UserMessage { text: "Hi".to_string() });
+        renderer.render(&OutputEvent::Error { message: "failed".to_string(), hint: "retry".to_string() });
+        assert!(buffer.contents().is_empty());
+
+        renderer.finish().unwrap();
+        let events: serde_json::Value = serde_json::from_str(&buffer.contents()).unwrap();
+        assert_eq!(events[1]["type"], "error");
+        assert_eq!(events.as_array().unwrap().len(), 2);
+    }
+}
@@ -1,40 +1,103 @@
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::io::Write;
+use std::sync::{Arc, Mutex};
 use anyhow::{Result, Context};
 use chrono::DateTime;
 use colored::*;
-use log::{info, debug};
+use log::{info, debug, warn};
+use serde::Serialize;
 
-use crate::exchange::{Exchange, Message, create_provider};
+use crate::exchange::{
+    create_moderator, create_provider, Exchange, Message, ProviderError, RetryProvider, ToolRoundLimitExceeded,
+};
 use crate::input::{create_default_input_handler, InputHandler};
-use crate::stats::SessionStats;
-use crate::cli::config::{session_path, LOG_PATH};
-use crate::utils::session_file::read_or_create_file;
-use crate::toolkit::{Tool, Toolkit};
+use crate::stats::{SessionStats, StatsStore};
+use crate::toolkit::{Tool, Toolkit, ToolkitRegistry};
+use crate::toolkit::mcp::McpToolkit;
+use crate::toolkit::plan::{PlanProgress, PlanToolkit};
+use crate::cli::config::{ensure_config, session_path, LOG_PATH};
+use crate::cli::output::{OutputEvent, Renderer, RendererObserver, SharedRenderer, TextRenderer};
+use crate::models::{Plan, Profile, Usage};
+use crate::utils::session_file::{
+    add_session_usage, read_header, read_or_create_file, repair_tool_calls, session_len, truncate_session,
+    write_messages, SessionHeader, SessionLog,
+};
+use crate::utils::snippet;
 
 pub struct Session {
     pub name: String,
     pub profile_name: Option<String>,
+    pub profile: Profile,
     pub tracing: bool,
     pub session_file_path: PathBuf,
     pub messages: Vec<Message>,
     pub interrupted: Arc<AtomicBool>,
     pub exchange: Option<Exchange>,
     pub stats: SessionStats,
-    pub toolkits: Vec<Box<dyn Toolkit>>,
+    /// Where the stats are saved when the session ends
+    pub stats_store: StatsStore,
+    /// Shows what happens in the session, colored text unless another
+    /// output format was set
+    pub renderer: SharedRenderer,
+    /// The tasks done so far, when the session follows a plan
+    pub plan_progress: Option<Arc<Mutex<PlanProgress>>>,
+    /// The plan's first message, until it is sent
+    kickoff: Option<String>,
+    /// Logs the exchange's messages to the session file
+    session_log: Option<Arc<SessionLog>>,
+    /// The session was given a random name, which the accelerator replaces
+    /// once the first turn is done
+    auto_name: bool,
+}
+
+/// How a single-pass run ended, its value is the process exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunOutcome {
+    /// The agent finished with a final answer
+    Completed = 0,
+    /// The provider failed, the turn was discarded
+    Failed = 1,
+    /// There was no message to send
+    InvalidInput = 2,
+    /// The run was stopped with Ctrl-C
+    Interrupted = 130,
+}
+
+impl RunOutcome {
+    pub fn exit_code(self) -> i32 {
+        self as i32
+    }
 }
 
 impl Session {
     pub async fn new(
         name: Option<String>, 
         profile: Option<String>,
-        plan: Option<serde_yaml::Value>,
+        plan: Option<Plan>,
+        log_level: Option<String>,
+        tracing: bool,
+    ) -> Result<Self> {
+        let (profile_name, profile) = ensure_config(profile.as_deref())?;
+        Self::with_profile(name, profile_name, profile, plan, log_level, tracing).await
+    }
+
+    /// Create a session that uses the given profile rather than one loaded
+    /// from the profiles config
+    pub async fn with_profile(
+        name: Option<String>,
+        profile_name: String,
+        profile: Profile,
+        plan: Option<Plan>,
         _log_level: Option<String>,
         tracing: bool,
     ) -> Result<Self> {
-        let name = name.unwrap_or_else(|| generate_name());
+        profile.validate()
+            .with_context(|| format!("Profile '{}' is invalid", profile_name))?;
+
+        let auto_name = name.is_none();
+        let name = name.unwrap_or_else(generate_name);
         let session_file_path = session_path(&name);
         
         let interrupted = Arc::new(AtomicBool::new(false));
@@ -46,48 +109,99 @@ impl Session {
             if let Err(e) = ctrlc::set_handler(move || {
                 int_handler.store(true, Ordering::SeqCst);
             }) {
-                println!("Warning: Failed to set Ctrl-C handler: {}", e);
+                eprintln!("Warning: Failed to set Ctrl-C handler: {}", e);
             }
         }
 
-        let stats = SessionStats::new(name.clone());
-        
+        let mut stats = SessionStats::new(name.clone());
+        stats.profile = Some(profile_name.clone());
+
         let mut session = Session {
             name,
-            profile_name: profile,
+            profile_name: Some(profile_name),
+            profile,
             tracing,
             session_file_path,
             messages: Vec::new(),
             interrupted,
             exchange: None,
             stats,
-            toolkits: crate::toolkit::get_default_toolkits(),
+            stats_store: StatsStore::default(),
+            renderer: Arc::new(Mutex::new(Box::new(TextRenderer::default()))),
+            plan_progress: None,
+            kickoff: None,
+            session_log: None,
+            auto_name,
         };
 
         session.messages.extend(session.load_session()?);
+        if repair_tool_calls(&mut session.messages) {
+            info!("Added results for tool calls interrupted in session {}", session.name);
+            write_messages(&session.session_file_path, &session.messages)?;
+        }
 
-        // Initialize exchange with OpenAI provider
-        let provider = create_provider("openai")?;
-        session.exchange = Some(Exchange::new(provider).await?);
+        // The plan is kept in the header, so a resumed session carries on
+        // tracking its tasks
+        let plan = match plan {
+            Some(plan) => {
+                session.setup_plan(&plan)?;
+                Some(plan)
+            }
+            None => read_header(&session.session_file_path)?.and_then(|header| header.plan),
+        };
 
-        if session.messages.is_empty() && plan.is_some() {
-            session.setup_plan(plan.unwrap())?;
+        // Initialize exchange with the profile's provider, toolkits and the
+        // saved history, logging each new message to the session file before
+        // it is rendered
+        let mut exchange = Self::create_exchange(&session.profile).await?;
+        exchange.restore_messages(session.messages.clone()).await;
+        let mut header = SessionHeader::for_profile(session.profile_name.as_deref().unwrap_or_default(), &session.profile);
+        header.plan = plan.clone();
+        let session_log = Arc::new(SessionLog::open(&session.session_file_path, header)?);
+        exchange.add_observer(Box::new(Arc::clone(&session_log)));
+        session.session_log = Some(session_log);
+        exchange.add_observer(Box::new(RendererObserver(Arc::clone(&session.renderer))));
+        if let Some(plan) = plan {
+            let mut progress = PlanProgress::new(plan);
+            progress.restore(&session.messages);
+            let progress = Arc::new(Mutex::new(progress));
+            exchange.add_toolkit(Box::new(PlanToolkit::new(Arc::clone(&progress))));
+            session.plan_progress = Some(progress);
         }
+        session.exchange = Some(exchange);
 
         Ok(session)
     }
 
-    pub async fn run(&mut self, _new_session: bool) -> Result<()> {
+    /// Report what happens in the session with the renderer from now on
+    pub fn set_renderer(&mut self, renderer: Box<dyn Renderer>) {
+        *self.renderer.lock().unwrap() = renderer;
+    }
+
+    fn render(&self, event: OutputEvent) {
+        self.renderer.lock().unwrap().render(&event);
+    }
+
+    fn render_session_start(&self, resumed: bool) {
+        self.render(OutputEvent::SessionStart {
+            session: self.name.clone(),
+            profile: self.profile_name.clone().unwrap_or_else(|| "default".to_string()),
+            session_file: self.session_file_path.display().to_string(),
+            resumed,
+        });
+    }
+
+    pub async fn run(&mut self, new_session: bool) -> Result<()> {
         let time_start = chrono::Utc::now();
         
-        let profile = self.profile_name.as_deref().unwrap_or("default");
-        println!("{}", format!("starting session | name: {} profile: {}", 
-            self.name.cyan(), profile.cyan()).dimmed());
+        self.render_session_start(!new_session);
+        if !new_session {
+            self.render_recap();
+        }
 
         // Initialize exchange if not already done
         if self.exchange.is_none() {
-            let provider = create_provider("openai")?;
-            self.exchange = Some(Exchange::new(provider).await?);
+            self.exchange = Some(Self::create_exchange(&self.profile).await?);
         }
 
         // Main interaction loop
@@ -98,83 +212,302 @@ impl Session {
                 break;
             }
 
-            // Get user input using the input handler
-            let mut input_handler = create_default_input_handler();
-            debug!("Getting user input...");
-            let input = input_handler.get_user_input()?;
-            
-            debug!("Got user input: {}", input.text);
-            print!("Thinking... ");
-            std::io::stdout().flush()?;
-            if input.to_exit() {
-                break;
-            }
+            // Send the plan's kickoff message first, then get user input
+            // using the input handler
+            let text = match self.kickoff.take() {
+                Some(kickoff) => kickoff,
+                None => {
+                    let mut input_handler = create_default_input_handler();
+                    debug!("Getting user input...");
+                    let input = input_handler.get_user_input()?;
+
+                    debug!("Got user input: {}", input.text);
+                    if input.to_exit() {
+                        break;
+                    }
+                    input.text
+                }
+            };
 
             // Process the message
-            let message = Message::user(&input.text);
+            let message = Message::user(&text);
             if let Some(exchange) = &self.exchange {
-                // Add message to history
-                exchange.add_message(message.clone()).await?;
-                
-                // Generate response
-                // Collect all available tools from registered toolkits
-                let tools: Vec<Tool> = self.toolkits.iter()
-                    .flat_map(|toolkit| toolkit.tools())
-                    .collect();
-                
-                let response = exchange.generate(&[message], Some(tools)).await?;
-                
-                // Process any tool uses in the response
-                // TODO: Implement tool use handling
-                // Currently disabled as we're working on the implementation
-                /*if response.has_tool_use() {
-                    for tool_use in response.tool_use() {
-                        if let Ok(result) = exchange.process_tool_use(tool_use).await {
-                            println!("Tool result: {}", result);
+                // Add message to history and let the agent loop run any tools
+                let usage_before = exchange.get_model_usage().await;
+                let result = Self::reply(exchange, message, &self.session_file_path, &self.renderer, self.plan_progress.as_ref()).await;
+                add_model_usage(&mut self.stats, &self.session_file_path, &self.renderer, self.profile.pricing_provider(), &usage_before, exchange.get_model_usage().await);
+                let tool_calls = match result {
+                    Ok(tool_calls) => tool_calls,
+                    Err(e) => {
+                        if Self::render_provider_error(&self.renderer, &e) {
+                            break;
                         }
+                        continue;
                     }
-                }*/
-                println!("\r"); // Clear the thinking indicator
-                
-                if !response.text().is_empty() {
-                    println!("{}", response.text());
-                }
-                
+                };
+
                 // Update stats
                 self.stats.add_message();
-                self.stats.add_tokens(exchange.get_token_usage().await);
+                self.stats.add_tool_calls(tool_calls);
+                self.messages = exchange.get_messages().await;
+                self.name_after_first_prompt().await;
             }
         }
         
         let time_end = chrono::Utc::now();
         self.log_session_stats(time_start, time_end)?;
-        
-        Ok(())
+        self.save_stats()?;
+
+        self.renderer.lock().unwrap().finish()
     }
 
-    pub fn single_pass(&mut self, _initial_message: String) -> Result<()> {
-        let profile = self.profile_name.as_deref().unwrap_or("default");
-        println!("starting session | name: {} profile: {}", self.name, profile);
-        println!("saving to {}", self.session_file_path.display());
+    /// Complete the stats and add them to the store, so reports over past
+    /// sessions include this one
+    pub fn save_stats(&mut self) -> Result<()> {
+        self.stats.complete();
+        self.stats_store.append(&self.stats)
+            .with_context(|| format!("Failed to save the stats of session {}", self.name))
+    }
 
-        // TODO: Add message and process response
-        
-        println!("ended run | name: {} profile: {}", self.name, profile);
-        println!("to resume: goose session resume {} --profile {}", self.name, profile);
-        
-        Ok(())
+    /// Remind the user where a resumed conversation left off
+    fn render_recap(&self) {
+        let last_prompt = self.messages.iter()
+            .rev()
+            .find(|message| message.is_user() && message.tool_result().is_empty());
+        let last_reply = last_prompt.and_then(|last_prompt| {
+            self.messages.iter()
+                .rev()
+                .take_while(|message| message.id != last_prompt.id)
+                .find(|message| message.is_assistant() && !message.text().is_empty())
+        });
+        self.render(OutputEvent::Recap {
+            message_count: self.messages.len(),
+            last_prompt: last_prompt.map(|message| snippet(&message.text(), RECAP_CHARS)),
+            last_reply: last_reply.map(|message| snippet(&message.text(), RECAP_CHARS)),
+            plan: self.plan_progress.as_ref().map(|progress| progress.lock().unwrap().summary()),
+        });
+    }
+
+    /// Send one message and let the agent run tools until it has a final
+    /// answer, without asking the user for anything
+    pub async fn single_pass(&mut self, initial_message: &str) -> Result<RunOutcome> {
+        let resumed = !self.messages.is_empty();
+        self.render_session_start(resumed);
+        if resumed {
+            self.render_recap();
+        }
+
+        let outcome = if initial_message.trim().is_empty() {
+            self.render(OutputEvent::Error {
+                message: "The message is empty".to_string(),
+                hint: "There is nothing to run.".to_string(),
+            });
+            RunOutcome::InvalidInput
+        } else {
+            self.run_turn(initial_message).await?
+        };
+
+        let text = match outcome {
+            RunOutcome::Completed => self.messages.last().map(|message| message.text()),
+            _ => None,
+        };
+        let stats = self.get_stats();
+        self.render(OutputEvent::Result {
+            session: Some(self.name.clone()),
+            outcome,
+            exit_code: outcome.exit_code(),
+            text,
+            tool_calls: stats.tool_calls,
+            usage: stats.usage(),
+            cost: stats.total_cost,
+        });
+        self.renderer.lock().unwrap().finish()?;
+        Ok(outcome)
+    }
+
+    /// Reply to the message, stopping early if the session is interrupted,
+    /// and save the stats
+    async fn run_turn(&mut self, message: &str) -> Result<RunOutcome> {
+        if self.exchange.is_none() {
+            self.exchange = Some(Self::create_exchange(&self.profile).await?);
+        }
+        let exchange = self.exchange.as_ref().unwrap();
+
+        let usage_before = exchange.get_model_usage().await;
+        let outcome = tokio::select! {
+            result = Self::reply(exchange, Message::user(message), &self.session_file_path, &self.renderer, self.plan_progress.as_ref()) => match result {
+                Ok(tool_calls) => {
+                    self.stats.add_message();
+                    self.stats.add_tool_calls(tool_calls);
+                    RunOutcome::Completed
+                }
+                Err(e) => {
+                    Self::render_provider_error(&self.renderer, &e);
+                    RunOutcome::Failed
+                }
+            },
+            // What was logged so far stays in the session file, interrupted
+            // tool calls are repaired when the session is resumed
+            _ = wait_for_interrupt(&self.interrupted) => {
+                self.renderer.lock().unwrap().render(&OutputEvent::Error {
+                    message: "The run was interrupted".to_string(),
+                    hint: "Resume the session to continue from where it stopped.".to_string(),
+                });
+                RunOutcome::Interrupted
+            }
+        };
+        add_model_usage(&mut self.stats, &self.session_file_path, &self.renderer, self.profile.pricing_provider(), &usage_before, exchange.get_model_usage().await);
+        self.messages = exchange.get_messages().await;
+        if outcome == RunOutcome::Completed {
+            self.name_after_first_prompt().await;
+        }
+        self.save_stats()?;
+        Ok(outcome)
+    }
+
+    /// Replace the random name of a new session with one the accelerator
+    /// gives it from the first prompt, moving the session file to match
+    ///
+    /// Naming is best effort, the random name is kept if it fails and the
+    /// session is only named once.
+    async fn name_after_first_prompt(&mut self) {
+        if !std::mem::take(&mut self.auto_name) {
+            return;
+        }
+        let (Some(exchange), Some(session_log)) = (&self.exchange, &self.session_log) else {
+            return;
+        };
+        let prompt = self.messages.iter().find(|message| message.is_user() && message.tool_result().is_empty());
+        let Some(prompt) = prompt.filter(|_| exchange.has_accelerator()) else {
+            return;
+        };
+
+        let usage_before = exchange.get_model_usage().await;
+        let name = exchange.name_session(&prompt.text()).await;
+        let renamed = name.and_then(|name| {
+            let (name, path) = unused_session_path(&name);
+            session_log.rename(&path)?;
+            Ok((name, path))
+        });
+        match renamed {
+            Ok((name, path)) => {
+                info!("Renamed session {} to {}", self.name, name);
+                self.name = name;
+                self.session_file_path = path;
+                self.stats.session_id = self.name.clone();
+                self.render(OutputEvent::SessionRenamed {
+                    session: self.name.clone(),
+                    session_file: self.session_file_path.display().to_string(),
+                });
+            }
+            Err(e) => debug!("Keeping the session name {}: {:#}", self.name, e),
+        }
+        add_model_usage(&mut self.stats, &self.session_file_path, &self.renderer, self.profile.pricing_provider(), &usage_before, exchange.get_model_usage().await);
+    }
+
+    async fn create_exchange(profile: &Profile) -> Result<Exchange> {
+        let provider = create_provider(&profile.provider, &profile.processor, &profile.endpoints)?;
+        let provider = RetryProvider::new(provider, profile.retry.clone());
+        let mut exchange = Exchange::new(Box::new(provider)).await?;
+        exchange.set_moderator(create_moderator(&profile.moderator)?);
+        if let Some(max_tool_rounds) = profile.max_tool_rounds {
+            exchange.set_max_tool_rounds(max_tool_rounds);
+        }
+
+        if !profile.accelerator.is_empty() && profile.accelerator != "none" {
+            let accelerator = create_provider(&profile.provider, &profile.accelerator, &profile.endpoints)?;
+            exchange.set_accelerator(Box::new(RetryProvider::new(accelerator, profile.retry.clone()))).await?;
+        }
+
+        let mut tool_owners = HashMap::new();
+        let toolkits = ToolkitRegistry::default().create_for_profile(profile)?;
+        for (spec, toolkit) in profile.toolkits.iter().zip(toolkits) {
+            claim_tools(&mut tool_owners, &toolkit.tools(), format!("toolkit {}", spec.name))?;
+            exchange.add_toolkit(Box::new(toolkit));
+        }
+        for (name, server) in &profile.mcp_servers {
+            let toolkit = McpToolkit::connect(name, server)
+                .await
+                .with_context(|| format!("Failed to connect to MCP server {}", name))?;
+            claim_tools(&mut tool_owners, &toolkit.tools(), format!("MCP server {}", name))?;
+            exchange.add_toolkit(Box::new(toolkit));
+        }
+        Ok(exchange)
+    }
+
+    /// Run the agent loop for a user message, streaming the response to the
+    /// renderer
+    ///
+    /// If the provider fails the turn is discarded, including the user message,
+    /// from both the history and the session file so they stay valid for the
+    /// next attempt. The history is restored from a copy taken before the turn,
+    /// since the moderator may have rewritten it in the meantime, and so is
+    /// the plan's progress. Returns the number of tool calls the model made.
+    async fn reply(
+        exchange: &Exchange,
+        message: Message,
+        session_file: &Path,
+        renderer: &SharedRenderer,
+        plan_progress: Option<&Arc<Mutex<PlanProgress>>>,
+    ) -> Result<u32> {
+        let checkpoint = exchange.get_messages().await;
+        let plan_checkpoint = plan_progress.map(|progress| progress.lock().unwrap().clone());
+        let tool_calls_before = exchange.get_tool_calls().await;
+        let file_len = session_len(session_file)?;
+        exchange.add_message(message).await?;
+
+        let result = exchange.reply_streaming(|event| renderer.lock().unwrap().render_stream(event)).await;
+        if let Err(e) = result {
+            exchange.restore_messages(checkpoint).await;
+            if let (Some(progress), Some(plan_checkpoint)) = (plan_progress, plan_checkpoint) {
+                *progress.lock().unwrap() = plan_checkpoint;
+            }
+            truncate_session(session_file, file_len)?;
+            return Err(e);
+        }
+
+        Ok(exchange.get_tool_calls().await - tool_calls_before)
+    }
+
+    /// Explain a failed reply, returning whether the session has to end
+    fn render_provider_error(renderer: &SharedRenderer, error: &anyhow::Error) -> bool {
+        let (hint, fatal) = match error.downcast_ref::<ProviderError>() {
+            Some(ProviderError::Auth(_)) => {
+                ("Check the API key for your provider and start the session again.", true)
+            }
+            Some(ProviderError::ContextLengthExceeded(_)) => {
+                ("The conversation no longer fits in the model's context window, start a new session to continue.", false)
+            }
+            Some(ProviderError::RateLimited { .. }) => ("The provider is still rate limiting requests, try again shortly.", false),
+            Some(ProviderError::Server { .. }) | Some(ProviderError::Network(_)) => {
+                ("The provider could not be reached, try again shortly.", false)
+            }
+            _ if error.is::<ToolRoundLimitExceeded>() => (
+                "The model kept calling tools without finishing and the turn was discarded, \
+                 try a narrower request or raise max_tool_rounds in the profile.",
+                false,
+            ),
+            _ => ("The last message was discarded, you can try again.", false),
+        };
+        renderer.lock().unwrap().render(&OutputEvent::Error {
+            message: format!("{:#}", error),
+            hint: hint.to_string(),
+        });
+        fatal
     }
 
     fn load_session(&self) -> Result<Vec<Message>> {
         read_or_create_file(&self.session_file_path)
     }
 
-    fn setup_plan(&mut self, _plan: serde_yaml::Value) -> Result<()> {
+    /// Start the session with the plan's kickoff message, sent as soon as
+    /// the session runs
+    fn setup_plan(&mut self, plan: &Plan) -> Result<()> {
         if !self.messages.is_empty() {
             return Err(anyhow::anyhow!("The plan can only be set on an empty session."));
         }
 
-        // TODO: Implement plan setup
+        self.kickoff = Some(plan.kickoff_message());
         Ok(())
     }
 
@@ -194,25 +527,12 @@ impl Session {
         
         // Process through exchange if available
         if let Some(exchange) = &self.exchange {
-            // Show thinking indicator
-            print!("Thinking... ");
-            std::io::stdout().flush()?;
-
-            // Generate response
-            let response = exchange.generate(&self.messages, None).await?;
-            println!("\r"); // Clear the thinking indicator
-
-            // Add response to history
-            self.messages.push(response.clone());
-            
-            // Update token usage
-            self.stats.add_tokens(exchange.get_token_usage().await);
-            
-            // Display response using markdown formatting
-            if !response.text().is_empty() {
-                // TODO: Add markdown rendering support
-                println!("{}", response.text());
-            }
+            // Generate response, running any requested tools
+            let usage_before = exchange.get_model_usage().await;
+            let result = Self::reply(exchange, self.messages.last().unwrap().clone(), &self.session_file_path, &self.renderer, self.plan_progress.as_ref()).await;
+            add_model_usage(&mut self.stats, &self.session_file_path, &self.renderer, self.profile.pricing_provider(), &usage_before, exchange.get_model_usage().await);
+            self.messages = exchange.get_messages().await;
+            self.stats.add_tool_calls(result?);
         }
         
         Ok(())
@@ -276,6 +596,69 @@ impl Session {
     }
 }
 
+/// Resolve once the Ctrl-C handler has flagged the session as interrupted
+async fn wait_for_interrupt(interrupted: &AtomicBool) {
+    while !interrupted.load(Ordering::SeqCst) {
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+}
+
+/// Characters of each message shown when a session is resumed
+const RECAP_CHARS: usize = 200;
+
+/// Add the tokens each model used since `before` was taken to the stats and
+/// to the totals in the session's metadata, and render them
+fn add_model_usage(
+    stats: &mut SessionStats,
+    session_file: &Path,
+    renderer: &SharedRenderer,
+    provider: &str,
+    before: &HashMap<String, Usage>,
+    after: HashMap<String, Usage>,
+) {
+    let mut total = Usage::default();
+    for (model, usage) in after {
+        let used = usage - before.get(&model).copied().unwrap_or_default();
+        if !used.is_empty() {
+            stats.add_usage(provider, &model, &used);
+            renderer.lock().unwrap().render(&OutputEvent::Usage { model, usage: used });
+            total += used;
+        }
+    }
+    if !total.is_empty() {
+        if let Err(e) = add_session_usage(session_file, &total) {
+            warn!("Failed to update the token totals of {}: {:#}", session_file.display(), e);
+        }
+    }
+}
+
+/// Record `owner` as the provider of its tools, failing when another toolkit
+/// already provides one of them since calls would only ever reach the first
+fn claim_tools(owners: &mut HashMap<String, String>, tools: &[Tool], owner: String) -> Result<()> {
+    for tool in tools {
+        if let Some(other) = owners.get(&tool.name) {
+            anyhow::bail!(
+                "The tool {} is provided by both {} and {}, remove one of them from the profile",
+                tool.name, other, owner
+            );
+        }
+        owners.insert(tool.name.clone(), owner.clone());
+    }
+    Ok(())
+}
+
+/// The session file for `name`, numbered when a session already has the name
+fn unused_session_path(name: &str) -> (String, PathBuf) {
+    let mut candidate = name.to_string();
+    let mut number = 1;
+    while session_path(&candidate).exists() {
+        number += 1;
+        candidate = format!("{}-{}", name, number);
+    }
+    let path = session_path(&candidate);
+    (candidate, path)
+}
+
 fn generate_name() -> String {
     crate::utils::generate_name()
 }
\ No newline at end of file
@@ -0,0 +1,410 @@
+use std::env;
+use std::sync::Mutex;
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use log::debug;
+use serde_json::{json, Value};
+
+use crate::exchange::{Provider, ProviderError, ProviderStream, StreamEvent};
+use crate::exchange::stream::sse_data;
+use crate::models::{Message, Usage};
+use crate::models::message::{Content, Role};
+use crate::toolkit::Tool;
+
+pub const ANTHROPIC_HOST: &str = "https://api.anthropic.com";
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+// Configuration options for Anthropic provider
+#[derive(Debug, Clone)]
+pub struct AnthropicOptions {
+    pub model: String,
+    pub temperature: f32,
+    pub max_tokens: u32,
+    pub system_prompt: Option<String>,
+    pub host: String,
+}
+
+impl Default for AnthropicOptions {
+    fn default() -> Self {
+        Self {
+            model: "claude-3-5-sonnet-latest".to_string(),
+            temperature: 0.7,
+            max_tokens: 4096,
+            system_prompt: None,
+            host: ANTHROPIC_HOST.to_string(),
+        }
+    }
+}
+
+/// Provider for the Anthropic Messages API
+pub struct AnthropicProvider {
+    client: reqwest::Client,
+    api_key: String,
+    options: AnthropicOptions,
+    last_token_usage: Mutex<Usage>,
+}
+
+impl AnthropicProvider {
+    pub fn new(options: Option<AnthropicOptions>) -> Result<Self> {
+        // Check for API key
+        let api_key = env::var("ANTHROPIC_API_KEY")
+            .context("ANTHROPIC_API_KEY environment variable not set")?;
+
+        Ok(Self::with_api_key(api_key, options))
+    }
+
+    pub fn with_api_key(api_key: String, options: Option<AnthropicOptions>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_key,
+            options: options.unwrap_or_default(),
+            last_token_usage: Mutex::new(Usage::default()),
+        }
+    }
+
+    /// Read the usage block of a response or stream event, fields missing
+    /// from it are zero
+    fn parse_usage(usage: &Value) -> Usage {
+        let tokens = |field: &str| usage.get(field).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        Usage {
+            input_tokens: tokens("input_tokens"),
+            output_tokens: tokens("output_tokens"),
+            cache_read_tokens: tokens("cache_read_input_tokens"),
+            cache_write_tokens: tokens("cache_creation_input_tokens"),
+        }
+    }
+
+    fn convert_content_to_anthropic(content: &Content) -> Value {
+        match content {
+            Content::Text { text } => json!({
+                "type": "text",
+                "text": text,
+            }),
+            Content::Image { data, mime_type } => json!({
+                "type": "image",
+                "source": {
+                    "type": "base64",
+                    "media_type": mime_type,
+                    "data": data,
+                },
+            }),
+            Content::ToolUse { id, name, parameters } => json!({
+                "type": "tool_use",
+                "id": id,
+                "name": name,
+                "input": parameters,
+            }),
+            Content::ToolResult { tool_use_id, output, is_error } => json!({
+                "type": "tool_result",
+                "tool_use_id": tool_use_id,
+                "content": output,
+                "is_error": is_error,
+            }),
+        }
+    }
+
+    /// Convert the conversation into the Anthropic system prompt and messages.
+    ///
+    /// System messages are lifted into the top level system prompt and
+    /// consecutive messages with the same role are merged, as the API requires
+    /// the roles to alternate.
+    fn convert_messages_to_anthropic(&self, messages: &[Message]) -> (Option<String>, Vec<Value>) {
+        let mut system: Vec<String> = self.options.system_prompt.iter().cloned().collect();
+        let mut converted: Vec<(&str, Vec<Value>)> = Vec::new();
+
+        for message in messages {
+            let role = match message.role {
+                Role::System => {
+                    system.push(message.text());
+                    continue;
+                }
+                Role::User => "user",
+                Role::Assistant => "assistant",
+            };
+
+            let blocks = message.content.iter()
+                .filter(|content| !matches!(content, Content::Text { text } if text.is_empty()))
+                .map(Self::convert_content_to_anthropic);
+
+            match converted.last_mut() {
+                Some((last_role, last_blocks)) if *last_role == role => last_blocks.extend(blocks),
+                _ => converted.push((role, blocks.collect())),
+            }
+        }
+
+        let system = if system.is_empty() { None } else { Some(system.join("\n\n")) };
+        let messages = converted.into_iter()
+            .map(|(role, content)| json!({ "role": role, "content": content }))
+            .collect();
+        (system, messages)
+    }
+
+    fn create_request(&self, messages: &[Message], tools: Option<Vec<Tool>>) -> Value {
+        let (system, anthropic_messages) = self.convert_messages_to_anthropic(messages);
+
+        let mut request = json!({
+            "model": self.options.model,
+            "max_tokens": self.options.max_tokens,
+            "temperature": self.options.temperature,
+            "messages": anthropic_messages,
+        });
+
+        if let Some(system) = system {
+            request["system"] = json!(system);
+        }
+
+        // Add tools if provided
+        if let Some(tools) = tools {
+            request["tools"] = tools.into_iter()
+                .map(|tool| json!({
+                    "name": tool.name,
+                    "description": tool.description,
+                    "input_schema": tool.parameters,
+                }))
+                .collect();
+        }
+
+        request
+    }
+
+    async fn send(&self, request: &Value) -> Result<reqwest::Response> {
+        let response = self.client
+            .post(format!("{}/v1/messages", self.options.host.trim_end_matches('/')))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(request)
+            .send()
+            .await
+            .map_err(ProviderError::from)
+            .context("Failed to get response from Anthropic")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let headers = response.headers().clone();
+            let body: Value = response.json().await.unwrap_or_default();
+            let message = body.pointer("/error/message")
+                .and_then(|m| m.as_str())
+                .unwrap_or("unknown error");
+            return Err(ProviderError::from_response(status, &headers, message.to_string()).into());
+        }
+
+        Ok(response)
+    }
+
+    /// Convert a streamed event into stream events, tracking the input and
+    /// cache tokens reported when the message starts
+    fn convert_event_to_stream_events(event: &Value, prompt_usage: &mut Usage) -> Result<Vec<StreamEvent>> {
+        let index = event.get("index").and_then(|i| i.as_u64()).unwrap_or(0) as usize;
+        let events = match event.get("type").and_then(|t| t.as_str()) {
+            Some("message_start") => {
+                if let Some(usage) = event.pointer("/message/usage") {
+                    *prompt_usage = Usage { output_tokens: 0, ..Self::parse_usage(usage) };
+                }
+                vec![]
+            }
+            Some("content_block_start") => match event.pointer("/content_block/type").and_then(|t| t.as_str()) {
+                Some("tool_use") => vec![StreamEvent::ToolCall {
+                    index,
+                    id: event.pointer("/content_block/id").and_then(|v| v.as_str()).map(String::from),
+                    name: event.pointer("/content_block/name").and_then(|v| v.as_str()).map(String::from),
+                    arguments: String::new(),
+                }],
+                _ => vec![],
+            },
+            Some("content_block_delta") => match event.pointer("/delta/type").and_then(|t| t.as_str()) {
+                Some("text_delta") => vec![StreamEvent::Text(
+                    event.pointer("/delta/text").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+                )],
+                Some("input_json_delta") => vec![StreamEvent::ToolCall {
+                    index,
+                    id: None,
+                    name: None,
+                    arguments: event.pointer("/delta/partial_json").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+                }],
+                _ => vec![],
+            },
+            Some("message_delta") => {
+                let output_tokens = event.pointer("/usage/output_tokens")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0) as u32;
+                vec![StreamEvent::Usage(Usage { output_tokens, ..*prompt_usage })]
+            }
+            Some("error") => {
+                let message = event.pointer("/error/message")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or("unknown error")
+                    .to_string();
+                let error = match event.pointer("/error/type").and_then(|t| t.as_str()) {
+                    Some("rate_limit_error") => ProviderError::RateLimited { message, retry_after: None },
+                    Some("overloaded_error") => ProviderError::Server { status: 529, message },
+                    Some("api_error") => ProviderError::Server { status: 500, message },
+                    _ => return Err(anyhow::anyhow!("Anthropic API error: {}", message)),
+                };
+                return Err(error.into());
+            }
+            _ => vec![],
+        };
+        Ok(events)
+    }
+
+    fn convert_response_to_message(response: &Value) -> Result<Message> {
+        let blocks = response.get("content")
+            .and_then(|c| c.as_array())
+            .ok_or_else(|| anyhow::anyhow!("Response did not contain any content"))?;
+
+        let mut content = Vec::new();
+        for block in blocks {
+            match block.get("type").and_then(|t| t.as_str()) {
+                Some("text") => {
+                    let text = block.get("text").and_then(|t| t.as_str()).unwrap_or_default();
+                    if !text.is_empty() {
+                        content.push(Content::Text { text: text.to_string() });
+                    }
+                }
+                Some("tool_use") => {
+                    let field = |name: &str| {
+                        block.get(name)
+                            .and_then(|v| v.as_str())
+                            .filter(|v| !v.is_empty())
+                            .ok_or_else(|| anyhow::anyhow!("Anthropic returned a tool_use block without {}: {}", name, block))
+                    };
+                    content.push(Content::ToolUse {
+                        id: field("id")?.to_string(),
+                        name: field("name")?.to_string(),
+                        parameters: block.get("input").cloned().unwrap_or_else(|| json!({})),
+                    });
+                }
+                other => debug!("Ignoring unsupported Anthropic content block: {:?}", other),
+            }
+        }
+
+        if content.is_empty() {
+            return Err(anyhow::anyhow!("Response contained neither content nor tool calls"));
+        }
+
+        Ok(Message::new(Role::Assistant, content))
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for AnthropicProvider {
+    async fn initialize(&mut self) -> Result<()> {
+        debug!("Initializing Anthropic provider with model: {}", self.options.model);
+        Ok(())
+    }
+
+    async fn generate(&self, messages: &[Message], tools: Option<Vec<Tool>>) -> Result<Message> {
+        *self.last_token_usage.lock().unwrap() = Usage::default();
+        let request = self.create_request(messages, tools);
+
+        debug!("Sending request to Anthropic API");
+        let response = self.send(&request).await?;
+        let body: Value = response.json().await
+            .context("Failed to parse response from Anthropic")?;
+
+        // Update token usage tracking
+        if let Some(usage) = body.get("usage") {
+            let usage = Self::parse_usage(usage);
+            *self.last_token_usage.lock().unwrap() = usage;
+            debug!("Token usage for request: {:?}", usage);
+        }
+
+        Self::convert_response_to_message(&body)
+    }
+
+    async fn stream(&self, messages: &[Message], tools: Option<Vec<Tool>>) -> Result<ProviderStream> {
+        *self.last_token_usage.lock().unwrap() = Usage::default();
+        let mut request = self.create_request(messages, tools);
+        request["stream"] = json!(true);
+
+        debug!("Streaming request to Anthropic API");
+        let response = self.send(&request).await?;
+
+        let mut prompt_usage = Usage::default();
+        let events = sse_data(response).flat_map(move |data| {
+            let events = data.and_then(|data| {
+                let event: Value = serde_json::from_str(&data)
+                    .context("Failed to parse stream event from Anthropic")?;
+                Self::convert_event_to_stream_events(&event, &mut prompt_usage)
+            });
+            let events: Vec<Result<StreamEvent>> = match events {
+                Ok(events) => events.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            };
+            futures::stream::iter(events)
+        });
+        Ok(Box::pin(events))
+    }
+
+    fn get_token_usage(&self) -> Usage {
+        *self.last_token_usage.lock().unwrap()
+    }
+
+    fn model(&self) -> &str {
+        &self.options.model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_conversion() {
+        let provider = AnthropicProvider::with_api_key(
+            "test".to_string(),
+            Some(AnthropicOptions {
+                system_prompt: Some("You are goose.".to_string()),
+                ..Default::default()
+            }),
+        );
+
+        let messages = vec![
+            Message::system("Use the tools."),
+            Message::user("List the files"),
+            Message::new(Role::Assistant, vec![Content::ToolUse {
+                id: "toolu_1".to_string(),
+                name: "bash".to_string(),
+                parameters: json!({"command": "ls"}),
+            }]),
+            Message::new(Role::User, vec![Content::ToolResult {
+                tool_use_id: "toolu_1".to_string(),
+                output: "Cargo.toml".to_string(),
+                is_error: false,
+            }]),
+            Message::user("Thanks"),
+        ];
+
+        let (system, converted) = provider.convert_messages_to_anthropic(&messages);
+        assert_eq!(system.as_deref(), Some("You are goose.\n\nUse the tools."));
+
+        // The tool result and the follow up text are merged into one user turn
+        assert_eq!(converted.len(), 3);
+        assert_eq!(converted[1]["content"][0]["type"], "tool_use");
+        assert_eq!(converted[1]["content"][0]["input"]["command"], "ls");
+        assert_eq!(converted[2]["role"], "user");
+        assert_eq!(converted[2]["content"][0]["type"], "tool_result");
+        assert_eq!(converted[2]["content"][0]["tool_use_id"], "toolu_1");
+        assert_eq!(converted[2]["content"][1]["text"], "Thanks");
+    }
+
+    #[test]
+    fn test_response_conversion() {
+        let response = json!({
+            "content": [
+                {"type": "text", "text": "Let me look."},
+                {"type": "tool_use", "id": "toolu_1", "name": "bash", "input": {"command": "ls"}}
+            ]
+        });
+
+        let message = AnthropicProvider::convert_response_to_message(&response).unwrap();
+        assert!(message.is_assistant());
+        assert_eq!(message.text(), "Let me look.");
+        assert_eq!(message.tool_use().len(), 1);
+
+        // A tool call that cannot be answered is an error, not a nameless call
+        let response = json!({"content": [{"type": "tool_use", "id": "toolu_1", "input": {}}]});
+        let error = AnthropicProvider::convert_response_to_message(&response).unwrap_err();
+        assert!(error.to_string().starts_with("Anthropic returned a tool_use block without name"), "{}", error);
+    }
+}
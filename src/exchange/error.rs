@@ -0,0 +1,168 @@
+use std::fmt;
+use std::time::Duration;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+
+/// Errors reported by providers, classified so that callers can react to them
+///
+/// Providers return these wrapped in `anyhow::Error`, use `downcast_ref` to
+/// recover the classification.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProviderError {
+    /// The credentials are missing, invalid or lack permission
+    Auth(String),
+    /// The request does not fit in the model's context window
+    ContextLengthExceeded(String),
+    /// The provider is throttling requests
+    RateLimited {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// The provider failed or is overloaded
+    Server { status: u16, message: String },
+    /// The provider could not be reached or the connection dropped
+    Network(String),
+    /// Any other rejected request, which would fail again if retried
+    Request { status: u16, message: String },
+}
+
+impl ProviderError {
+    /// Classify an unsuccessful HTTP response
+    pub fn from_response(status: StatusCode, headers: &HeaderMap, message: String) -> Self {
+        match status.as_u16() {
+            401 | 403 => Self::Auth(message),
+            429 => Self::RateLimited {
+                message,
+                retry_after: retry_after(headers),
+            },
+            413 => Self::ContextLengthExceeded(message),
+            400 if is_context_length_message(&message) => Self::ContextLengthExceeded(message),
+            408 | 500..=599 => Self::Server { status: status.as_u16(), message },
+            status => Self::Request { status, message },
+        }
+    }
+
+    /// Whether the same request may succeed if sent again
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::RateLimited { .. } | Self::Server { .. } | Self::Network(_))
+    }
+
+    /// How long the provider asked us to wait before retrying
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ProviderError {
+    fn from(error: reqwest::Error) -> Self {
+        match error.status() {
+            Some(status) => Self::from_response(status, &HeaderMap::new(), error.to_string()),
+            None => Self::Network(error.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auth(message) => write!(f, "Authentication failed: {}", message),
+            Self::ContextLengthExceeded(message) => write!(f, "Context length exceeded: {}", message),
+            Self::RateLimited { message, .. } => write!(f, "Rate limited: {}", message),
+            Self::Server { status, message } => write!(f, "Server error ({}): {}", status, message),
+            Self::Network(message) => write!(f, "Network error: {}", message),
+            Self::Request { status, message } => write!(f, "Request failed ({}): {}", status, message),
+        }
+    }
+}
+
+impl std::error::Error for ProviderError {}
+
+/// The model still wanted to call tools after the most rounds of tool calls
+/// the exchange allows in a single reply
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolRoundLimitExceeded {
+    pub max_rounds: usize,
+}
+
+impl fmt::Display for ToolRoundLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The model was still calling tools after {} rounds of tool calls", self.max_rounds)
+    }
+}
+
+impl std::error::Error for ToolRoundLimitExceeded {}
+
+/// Read the delay requested by `retry-after-ms` or `retry-after`, which may
+/// hold either seconds or an HTTP date
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+    if let Some(millis) = header("retry-after-ms").and_then(|value| value.trim().parse::<f64>().ok()) {
+        return Some(Duration::from_secs_f64(millis.max(0.0) / 1000.0));
+    }
+
+    let value = header("retry-after")?.trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Some(Duration::from_secs_f64(seconds.max(0.0)));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.signed_duration_since(chrono::Utc::now());
+    Some(delay.to_std().unwrap_or(Duration::ZERO))
+}
+
+/// Providers report context overflow as a generic bad request, so look at the wording
+fn is_context_length_message(message: &str) -> bool {
+    let message = message.to_lowercase();
+    ["context_length_exceeded", "context length", "context window", "prompt is too long", "too many tokens"]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_classification() {
+        let headers = HeaderMap::new();
+        let classify = |status: u16, message: &str| {
+            ProviderError::from_response(StatusCode::from_u16(status).unwrap(), &headers, message.to_string())
+        };
+
+        assert!(matches!(classify(401, "invalid key"), ProviderError::Auth(_)));
+        assert!(matches!(
+            classify(400, "This model's maximum context length is 8192 tokens"),
+            ProviderError::ContextLengthExceeded(_)
+        ));
+        assert!(matches!(
+            classify(400, "prompt is too long: 210000 tokens > 200000 maximum"),
+            ProviderError::ContextLengthExceeded(_)
+        ));
+        assert!(matches!(classify(400, "invalid model"), ProviderError::Request { status: 400, .. }));
+        assert!(matches!(classify(529, "Overloaded"), ProviderError::Server { status: 529, .. }));
+
+        assert!(classify(503, "unavailable").is_retryable());
+        assert!(!classify(401, "invalid key").is_retryable());
+        assert!(!classify(404, "not found").is_retryable());
+    }
+
+    #[test]
+    fn test_retry_after_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("7"));
+        let error = ProviderError::from_response(StatusCode::TOO_MANY_REQUESTS, &headers, "slow down".to_string());
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(7)));
+
+        // The millisecond variant is more precise and takes precedence
+        headers.insert("retry-after-ms", HeaderValue::from_static("250"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_millis(250)));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"));
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+    }
+}
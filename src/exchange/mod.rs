@@ -1,14 +1,32 @@
 use std::sync::Arc;
 use anyhow::{Result, anyhow};
 use async_trait::async_trait;
+use futures::StreamExt;
+use log::debug;
 use tokio::sync::Mutex;
 
+use std::collections::HashMap;
 pub use crate::models::Message;
-use crate::toolkit::Tool;
-mod message;
-pub use message::Content;
+pub use crate::models::message::{Content, Role};
+use crate::models::{EndpointConfig, Usage};
+use crate::models::catalog::model_info;
+use crate::tokens::TokenCounter;
+use crate::toolkit::{Tool, Toolkit};
+use crate::utils::slugify;
+mod anthropic;
+pub use anthropic::{AnthropicOptions, AnthropicProvider};
+mod error;
+pub use error::{ProviderError, ToolRoundLimitExceeded};
+mod moderator;
+pub use moderator::{create_moderator, truncate, Moderator, PassiveModerator, SummarizeModerator, TruncateModerator};
+mod ollama;
+pub use ollama::{OllamaOptions, OllamaProvider};
 mod openai;
-pub use openai::{OpenAIOptions, OpenAIProvider};
+pub use openai::{CompatibleConfig, OpenAIOptions, OpenAIProvider};
+mod retry;
+pub use retry::RetryProvider;
+pub(crate) mod stream;
+pub use stream::{stream_from_message, MessageAssembler, ProviderStream, StreamEvent};
 
 /// Trait for LLM providers
 #[async_trait]
@@ -18,43 +36,222 @@ pub trait Provider: Send + Sync {
     
     /// Generate a response for the given messages
     async fn generate(&self, messages: &[Message], tools: Option<Vec<Tool>>) -> Result<Message>;
+
+    /// Stream a response for the given messages as text and tool call deltas
+    ///
+    /// Providers without native streaming fall back to a single complete response.
+    async fn stream(&self, messages: &[Message], tools: Option<Vec<Tool>>) -> Result<ProviderStream> {
+        let message = self.generate(messages, tools).await?;
+        Ok(stream_from_message(&message, self.get_token_usage()))
+    }
     
     /// Get the token usage for the last request
-    fn get_token_usage(&self) -> u32;
+    fn get_token_usage(&self) -> Usage;
+
+    /// The model requests are sent to, used to attribute token usage
+    fn model(&self) -> &str {
+        "unknown"
+    }
+}
+
+/// Receives every message as it is added to the history, so it can be
+/// persisted or shown before the conversation moves on
+pub trait MessageObserver: Send + Sync {
+    fn on_message(&self, message: &Message) -> Result<()>;
+}
+
+impl<T: MessageObserver + ?Sized> MessageObserver for Arc<T> {
+    fn on_message(&self, message: &Message) -> Result<()> {
+        (**self).on_message(message)
+    }
 }
 
 /// Create a new provider instance based on configuration
-pub fn create_provider(provider_name: &str) -> Result<Box<dyn Provider>> {
+///
+/// Named endpoints take precedence over the built in providers, so a profile
+/// can point `provider` at any OpenAI compatible endpoint it declares.
+pub fn create_provider(
+    provider_name: &str,
+    model: &str,
+    endpoints: &HashMap<String, EndpointConfig>,
+) -> Result<Box<dyn Provider>> {
+    let openai_options = || OpenAIOptions { model: model.to_string(), ..Default::default() };
+
+    if let Some(endpoint) = endpoints.get(provider_name) {
+        return Ok(Box::new(OpenAIProvider::from_endpoint(endpoint, Some(openai_options()))?));
+    }
+
     match provider_name {
-        "openai" => Ok(Box::new(OpenAIProvider::new(None)?)),
+        "openai" => Ok(Box::new(OpenAIProvider::new(Some(openai_options()))?)),
+        "anthropic" => Ok(Box::new(AnthropicProvider::new(Some(AnthropicOptions {
+            model: model.to_string(),
+            ..Default::default()
+        }))?)),
+        "ollama" => Ok(Box::new(OllamaProvider::new(Some(OllamaOptions {
+            model: model.to_string(),
+            ..Default::default()
+        }))?)),
         _ => Err(anyhow!("Unknown provider: {}", provider_name)),
     }
 }
 
+/// Tokens kept free in the context window for the response, at most a
+/// quarter of the window
+const RESPONSE_RESERVE: usize = 4096;
+
+/// Rounds of tool calls a single reply may run before giving up, so a model
+/// that never stops calling tools cannot spend tokens forever
+pub const DEFAULT_MAX_TOOL_ROUNDS: usize = 50;
+
+/// Tool output longer than this is condensed by the accelerator, when there is one
+const CONDENSE_TOOL_OUTPUT_CHARS: usize = 16_000;
+
+const SUMMARIZE_PROMPT: &str = "You summarize conversations between a user and an AI assistant that uses tools. \
+Write a concise summary that keeps the user's goals, the decisions made, the files and commands involved \
+and any work that remains. Reply with the summary only.";
+
+const NAME_PROMPT: &str = "You name conversations between a user and an AI assistant. \
+Reply with a name of two to five words that says what the user wants done, and nothing else.";
+
+const CONDENSE_PROMPT: &str = "You condense the output of a tool call for an AI assistant. \
+Keep errors, file paths, identifiers and anything the assistant would need to act on, drop repetition and noise. \
+Reply with the condensed output only.";
+
 /// Exchange handles communication with the LLM provider
+///
+/// The main agent loop runs on the processor model. Auxiliary tasks such as
+/// summarizing history and condensing long tool output run on the accelerator
+/// model, which is usually cheaper and faster, when one is configured.
 pub struct Exchange {
     provider: Arc<Box<dyn Provider>>,
+    accelerator: Option<Arc<Box<dyn Provider>>>,
+    toolkits: Vec<Box<dyn Toolkit>>,
+    moderator: Box<dyn Moderator>,
+    observers: Vec<Box<dyn MessageObserver>>,
+    context_limit: usize,
+    max_tool_rounds: usize,
+    token_counter: TokenCounter,
     messages: Arc<Mutex<Vec<Message>>>,
-    token_usage: Arc<Mutex<u32>>,
+    token_usage: Arc<Mutex<Usage>>,
+    model_usage: Arc<Mutex<HashMap<String, Usage>>>,
+    tool_calls: Arc<Mutex<u32>>,
 }
 
 impl Exchange {
     pub async fn new(provider: Box<dyn Provider>) -> Result<Self> {
         let mut provider = provider;
         provider.initialize().await?;
+        let model = provider.model().to_string();
         
         Ok(Self {
             provider: Arc::new(provider),
+            accelerator: None,
+            toolkits: Vec::new(),
+            moderator: Box::new(PassiveModerator),
+            observers: Vec::new(),
+            context_limit: model_info(&model).context_window,
+            max_tool_rounds: DEFAULT_MAX_TOOL_ROUNDS,
+            token_counter: TokenCounter::for_model(&model),
             messages: Arc::new(Mutex::new(Vec::new())),
-            token_usage: Arc::new(Mutex::new(0)),
+            token_usage: Arc::new(Mutex::new(Usage::default())),
+            model_usage: Arc::new(Mutex::new(HashMap::new())),
+            tool_calls: Arc::new(Mutex::new(0)),
         })
     }
+
+    /// Use a separate provider for auxiliary tasks
+    pub async fn set_accelerator(&mut self, provider: Box<dyn Provider>) -> Result<()> {
+        let mut provider = provider;
+        provider.initialize().await?;
+        self.accelerator = Some(Arc::new(provider));
+        Ok(())
+    }
+
+    /// Whether auxiliary tasks have a model of their own
+    pub fn has_accelerator(&self) -> bool {
+        self.accelerator.is_some()
+    }
+
+    /// Use a moderator to keep the history within the context window
+    pub fn set_moderator(&mut self, moderator: Box<dyn Moderator>) {
+        self.moderator = moderator;
+    }
+
+    /// Pass every message added to the history to an observer, after the
+    /// observers added before it
+    pub fn add_observer(&mut self, observer: Box<dyn MessageObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Set the context window of the processor model, in tokens, overriding
+    /// the model catalog
+    pub fn set_context_limit(&mut self, context_limit: usize) {
+        self.context_limit = context_limit;
+    }
+
+    /// Set how many rounds of tool calls a reply may run, replies that need
+    /// more fail with [`ToolRoundLimitExceeded`]
+    pub fn set_max_tool_rounds(&mut self, max_tool_rounds: usize) {
+        self.max_tool_rounds = max_tool_rounds;
+    }
+
+    /// Counts tokens for the processor model
+    pub fn token_counter(&self) -> &TokenCounter {
+        &self.token_counter
+    }
+
+    /// Count the tokens a request with this history will use, including the
+    /// system prompt and tool schemas
+    pub fn count_request_tokens(&self, system: &str, messages: &[Message], tools: &[Tool]) -> usize {
+        let system = if system.is_empty() { 0 } else { self.token_counter.count_message(&Message::system(system)) };
+        system + self.token_counter.count_messages(messages) + self.token_counter.count_tools(tools)
+    }
+
+    /// Register a toolkit whose tools are offered to the provider
+    pub fn add_toolkit(&mut self, toolkit: Box<dyn Toolkit>) {
+        self.toolkits.push(toolkit);
+    }
+
+    /// Get the toolkits registered with this exchange
+    pub fn toolkits(&self) -> &[Box<dyn Toolkit>] {
+        &self.toolkits
+    }
+
+    /// Combine the system prompts of all registered toolkits
+    pub fn system_prompt(&self) -> String {
+        self.toolkits.iter()
+            .map(|toolkit| toolkit.system())
+            .filter(|system| !system.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// Collect the tools of all registered toolkits
+    pub fn tools(&self) -> Vec<Tool> {
+        self.toolkits.iter()
+            .flat_map(|toolkit| toolkit.tools())
+            .collect()
+    }
     
     /// Add a message to the conversation history
     pub async fn add_message(&self, message: Message) -> Result<()> {
         message.validate()?;
-        let mut messages = self.messages.lock().await;
-        messages.push(message);
+        self.push_message(message).await
+    }
+
+    /// Replace the history with a saved conversation, which the observers
+    /// have already seen
+    pub async fn restore_messages(&self, messages: Vec<Message>) {
+        *self.messages.lock().await = messages;
+    }
+
+    /// Append to the history, letting the observers see the message first so
+    /// it is never in the history without having been persisted
+    async fn push_message(&self, message: Message) -> Result<()> {
+        for observer in &self.observers {
+            observer.on_message(&message)?;
+        }
+        self.messages.lock().await.push(message);
         Ok(())
     }
 
@@ -63,54 +260,274 @@ impl Exchange {
         let response = self.provider.generate(messages, tools).await?;
         
         // Update token usage
-        let mut token_usage = self.token_usage.lock().await;
-        *token_usage += self.provider.get_token_usage();
+        self.record_usage(self.provider.model(), self.provider.get_token_usage()).await;
         
         // Add response to messages
-        drop(token_usage); // Release token_usage lock before acquiring messages lock
-        let mut messages = self.messages.lock().await;
-        messages.push(response.clone());
-        
+        self.push_message(response.clone()).await?;
+
         Ok(response)
     }
 
+    /// Generate a response by streaming from the provider, passing each event
+    /// to `on_event` as it arrives
+    pub async fn generate_streaming<F>(&self, messages: &[Message], tools: Option<Vec<Tool>>, on_event: &mut F) -> Result<Message>
+    where
+        F: FnMut(&StreamEvent) + Send,
+    {
+        let mut stream = self.provider.stream(messages, tools).await?;
+        let mut assembler = MessageAssembler::new();
+        while let Some(event) = stream.next().await {
+            let event = event?;
+            on_event(&event);
+            assembler.push(&event);
+        }
+
+        // Update token usage
+        self.record_usage(self.provider.model(), assembler.token_usage()).await;
+
+        let response = assembler.finish()?;
+        self.push_message(response.clone()).await?;
+        Ok(response)
+    }
+
+    /// Reply to the conversation, running requested tools until the provider
+    /// produces a final answer.
+    ///
+    /// Each tool call is dispatched to the toolkit that owns the tool and the
+    /// results are appended as a user message before the provider is invoked
+    /// again. The toolkits' system prompts are sent ahead of the history as a
+    /// system message. Returns the final assistant message, or fails with
+    /// [`ToolRoundLimitExceeded`] if the provider keeps calling tools.
+    pub async fn reply(&self) -> Result<Message> {
+        self.reply_loop(None::<&mut fn(&StreamEvent)>).await
+    }
+
+    /// Reply to the conversation like [`Exchange::reply`], streaming the
+    /// provider responses and passing each event to `on_event` as it arrives
+    pub async fn reply_streaming<F>(&self, mut on_event: F) -> Result<Message>
+    where
+        F: FnMut(&StreamEvent) + Send,
+    {
+        self.reply_loop(Some(&mut on_event)).await
+    }
+
+    async fn reply_loop<F>(&self, mut on_event: Option<&mut F>) -> Result<Message>
+    where
+        F: FnMut(&StreamEvent) + Send,
+    {
+        let tools = self.tools();
+        let tools = if tools.is_empty() { None } else { Some(tools) };
+        let system = self.system_prompt();
+
+        let mut rounds = 0;
+        loop {
+            self.moderate(&system, &tools).await?;
+
+            let history = self.get_messages().await;
+            let request_tokens = self.count_request_tokens(&system, &history, tools.as_deref().unwrap_or_default());
+            // Estimates can be off, so only refuse to send when the count is exact
+            if self.token_counter.is_exact() && request_tokens > self.context_limit {
+                return Err(ProviderError::ContextLengthExceeded(format!(
+                    "the request needs {} tokens but {} has a context window of {}",
+                    request_tokens,
+                    self.provider.model(),
+                    self.context_limit
+                )).into());
+            }
+
+            let mut messages = Vec::new();
+            if !system.is_empty() {
+                messages.push(Message::system(&system));
+            }
+            messages.extend(history);
+            let response = match on_event.as_mut() {
+                Some(on_event) => self.generate_streaming(&messages, tools.clone(), *on_event).await?,
+                None => self.generate(&messages, tools.clone()).await?,
+            };
+
+            if !response.has_tool_use() {
+                return Ok(response);
+            }
+            if rounds == self.max_tool_rounds {
+                return Err(ToolRoundLimitExceeded { max_rounds: self.max_tool_rounds }.into());
+            }
+            rounds += 1;
+            *self.tool_calls.lock().await += response.tool_use().len() as u32;
+
+            let mut results = Vec::new();
+            for tool_use in response.tool_use() {
+                results.push(self.process_tool_use(tool_use).await?);
+            }
+            self.add_message(Message::new(Role::User, results)).await?;
+        }
+    }
+
+    /// Let the moderator rewrite the history to fit in what the context
+    /// window leaves after the system prompt, tools and response
+    ///
+    /// Only the history kept in memory is rewritten. Observers have already
+    /// seen every message, so a session file keeps the whole conversation and
+    /// a resumed session is moderated again from it.
+    async fn moderate(&self, system: &str, tools: &Option<Vec<Tool>>) -> Result<()> {
+        let reserve = RESPONSE_RESERVE.min(self.context_limit / 4);
+        let overhead = self.count_request_tokens(system, &[], tools.as_deref().unwrap_or_default());
+        let budget = self.context_limit.saturating_sub(overhead + reserve);
+
+        let messages = self.get_messages().await;
+        let moderated = self.moderator.rewrite(self, messages, budget).await?;
+        *self.messages.lock().await = moderated;
+        Ok(())
+    }
+
     /// Remove the last message from history
     pub async fn rewind(&self) -> Result<()> {
         let mut messages = self.messages.lock().await;
         messages.pop();
         Ok(())
     }
-    
+
     /// Get the total token usage
-    pub async fn get_token_usage(&self) -> u32 {
+    pub async fn get_token_usage(&self) -> Usage {
         *self.token_usage.lock().await
     }
 
+    /// Get the total token usage of each model
+    pub async fn get_model_usage(&self) -> HashMap<String, Usage> {
+        self.model_usage.lock().await.clone()
+    }
+
+    /// Get the number of tool calls the model has made
+    pub async fn get_tool_calls(&self) -> u32 {
+        *self.tool_calls.lock().await
+    }
+
+    async fn record_usage(&self, model: &str, usage: Usage) {
+        *self.token_usage.lock().await += usage;
+        *self.model_usage.lock().await.entry(model.to_string()).or_default() += usage;
+    }
+
+    /// Generate a one off response with the accelerator, falling back to the
+    /// processor when there is none. The history is left untouched.
+    pub async fn generate_auxiliary(&self, system: &str, messages: &[Message]) -> Result<Message> {
+        let provider = self.accelerator.as_ref().unwrap_or(&self.provider);
+        let mut request = vec![Message::system(system)];
+        request.extend_from_slice(messages);
+
+        let response = provider.generate(&request, None).await?;
+        self.record_usage(provider.model(), provider.get_token_usage()).await;
+        Ok(response)
+    }
+
+    /// Summarize part of the conversation with the accelerator
+    pub async fn summarize(&self, messages: &[Message]) -> Result<String> {
+        let transcript = messages.iter()
+            .map(Self::transcript_entry)
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let response = self.generate_auxiliary(SUMMARIZE_PROMPT, &[Message::user(&transcript)]).await?;
+        Ok(response.text())
+    }
+
+    /// Name a conversation after its first prompt with the accelerator, as a
+    /// slug that can be used in a file name
+    pub async fn name_session(&self, prompt: &str) -> Result<String> {
+        let response = self.generate_auxiliary(NAME_PROMPT, &[Message::user(prompt)]).await?;
+        let name = slugify(&response.text());
+        if name.is_empty() {
+            return Err(anyhow!("No usable name in the reply {:?}", response.text()));
+        }
+        Ok(name)
+    }
+
+    /// Render a message as plain text, including its tool calls and results
+    fn transcript_entry(message: &Message) -> String {
+        let role = match message.role {
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::System => "system",
+        };
+        let content = message.content.iter()
+            .map(|content| match content {
+                Content::Text { text } => text.clone(),
+                Content::Image { .. } => "[image]".to_string(),
+                Content::ToolUse { name, parameters, .. } => format!("[called {} with {}]", name, parameters),
+                Content::ToolResult { output, is_error, .. } => {
+                    format!("[tool {}: {}]", if *is_error { "error" } else { "result" }, output)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!("{}: {}", role, content)
+    }
+
+    /// Condense long tool output with the accelerator so it does not crowd
+    /// the processor's context, keeping the original if that fails
+    async fn condense_tool_output(&self, tool_name: &str, output: String) -> String {
+        if self.accelerator.is_none() || output.len() <= CONDENSE_TOOL_OUTPUT_CHARS {
+            return output;
+        }
+
+        let request = Message::user(&format!("Output of the {} tool:\n\n{}", tool_name, output));
+        match self.generate_auxiliary(CONDENSE_PROMPT, &[request]).await {
+            Ok(response) if !response.text().is_empty() => {
+                debug!("Condensed {} output from {} to {} characters", tool_name, output.len(), response.text().len());
+                response.text()
+            }
+            Ok(_) => output,
+            Err(e) => {
+                debug!("Failed to condense {} output: {}", tool_name, e);
+                output
+            }
+        }
+    }
+
     /// Get a reference to the messages
     pub async fn get_messages(&self) -> Vec<Message> {
         self.messages.lock().await.clone()
     }
 
     /// Process tool usage in a message
+    ///
+    /// Failures are reported back as an error tool result so that the model
+    /// can see them and recover, rather than aborting the exchange.
     pub async fn process_tool_use(&self, tool_use: &Content) -> Result<Content> {
         match tool_use {
-            Content::ToolUse { tool_call_id, name, parameters } => {
+            Content::ToolUse { id, name, parameters } => {
                 // Find matching tool in registered toolkits
+                let toolkit = self.toolkits.iter()
+                    .find(|toolkit| toolkit.tools().iter().any(|tool| &tool.name == name));
+
+                let Some(toolkit) = toolkit else {
+                    return Ok(Content::ToolResult {
+                        tool_use_id: id.clone(),
+                        output: format!("No registered toolkit provides the tool {}", name),
+                        is_error: true,
+                    });
+                };
+
                 let tool = Tool::new(
                     name,
                     "", // Description not needed for processing
                     parameters.clone(),
-                    vec![], // Required params already validated by OpenAI
+                    vec![], // Required params already validated by the provider
                 );
 
-                // Create tool result
-                Ok(Content::ToolResult {
-                    tool_use_id: tool_call_id.clone(),
-                    output: format!("Processed tool {} with parameters: {:?}", name, parameters),
-                    is_error: false,
-                })
+                debug!("Dispatching tool call {} ({})", name, id);
+                let result = match toolkit.process_tool(&tool).await {
+                    Ok(message) => Content::ToolResult {
+                        tool_use_id: id.clone(),
+                        output: self.condense_tool_output(name, message.text()).await,
+                        is_error: false,
+                    },
+                    Err(e) => Content::ToolResult {
+                        tool_use_id: id.clone(),
+                        output: e.to_string(),
+                        is_error: true,
+                    },
+                };
+                Ok(result)
             },
             _ => Err(anyhow!("Invalid tool use content type"))
         }
     }
-}
\ No newline at end of file
+}
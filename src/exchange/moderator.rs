@@ -0,0 +1,183 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use log::{debug, warn};
+
+use crate::exchange::Exchange;
+use crate::models::Message;
+use crate::models::message::Content;
+use crate::tokens::TokenCounter;
+
+/// Keeps the conversation history within the model's context window
+///
+/// The exchange invokes its moderator before every provider call with the
+/// number of tokens the history may use, and replaces its history with the
+/// messages the moderator returns. Use [`Exchange::token_counter`] to measure
+/// messages against the budget.
+#[async_trait]
+pub trait Moderator: Send + Sync {
+    async fn rewrite(&self, exchange: &Exchange, messages: Vec<Message>, budget: usize) -> Result<Vec<Message>>;
+}
+
+/// Create a moderator by the name profiles refer to it with
+pub fn create_moderator(name: &str) -> Result<Box<dyn Moderator>> {
+    match name {
+        "passive" => Ok(Box::new(PassiveModerator)),
+        "truncate" => Ok(Box::new(TruncateModerator)),
+        // Profiles written by earlier versions default to synopsis
+        "summarize" | "synopsis" => Ok(Box::new(SummarizeModerator)),
+        _ => Err(anyhow!("Unknown moderator: {}", name)),
+    }
+}
+
+/// Leaves the history untouched, the provider reports when it no longer fits
+pub struct PassiveModerator;
+
+#[async_trait]
+impl Moderator for PassiveModerator {
+    async fn rewrite(&self, _exchange: &Exchange, messages: Vec<Message>, _budget: usize) -> Result<Vec<Message>> {
+        Ok(messages)
+    }
+}
+
+/// Drops the oldest messages until the history fits
+pub struct TruncateModerator;
+
+#[async_trait]
+impl Moderator for TruncateModerator {
+    async fn rewrite(&self, exchange: &Exchange, messages: Vec<Message>, budget: usize) -> Result<Vec<Message>> {
+        Ok(truncate(messages, budget, exchange.token_counter()))
+    }
+}
+
+/// Replaces the oldest messages with a summary written by the accelerator
+pub struct SummarizeModerator;
+
+#[async_trait]
+impl Moderator for SummarizeModerator {
+    async fn rewrite(&self, exchange: &Exchange, mut messages: Vec<Message>, budget: usize) -> Result<Vec<Message>> {
+        let counter = exchange.token_counter();
+        if counter.count_messages(&messages) <= budget {
+            return Ok(messages);
+        }
+
+        // Leave room for the conversation to grow before summarizing again
+        let Some(split) = split_point(&messages, budget / 2, counter) else {
+            warn!("The history does not fit in the context window but has no turn boundary to summarize at");
+            return Ok(messages);
+        };
+
+        let mut kept = messages.split_off(split);
+        let summary = exchange.summarize(&messages).await?;
+        debug!("Summarized {} messages into {} characters", messages.len(), summary.len());
+
+        kept[0].content.insert(0, Content::Text {
+            text: format!("Summary of the conversation so far:\n{}\n\n", summary),
+        });
+        Ok(kept)
+    }
+}
+
+/// Drop the oldest messages until the rest fits in `budget`, or as close as
+/// the turn boundaries allow
+pub fn truncate(mut messages: Vec<Message>, budget: usize, counter: &TokenCounter) -> Vec<Message> {
+    if counter.count_messages(&messages) <= budget {
+        return messages;
+    }
+
+    match split_point(&messages, budget, counter) {
+        Some(split) => {
+            debug!("Truncating {} of {} messages", split, messages.len());
+            messages.split_off(split)
+        }
+        None => {
+            warn!("The history does not fit in the context window but has no turn boundary to truncate at");
+            messages
+        }
+    }
+}
+
+/// Find where to cut the history so the messages after the cut fit in
+/// `budget`, preferring to keep as much as possible
+///
+/// The kept history has to start with a user message that is not a tool
+/// result, so tool calls are never separated from their results. Falls back
+/// to the latest such boundary when nothing fits.
+fn split_point(messages: &[Message], budget: usize, counter: &TokenCounter) -> Option<usize> {
+    let boundaries: Vec<usize> = (1..messages.len())
+        .filter(|&i| messages[i].is_user() && messages[i].tool_result().is_empty())
+        .collect();
+
+    boundaries.iter()
+        .copied()
+        .find(|&i| counter.count_messages(&messages[i..]) <= budget)
+        .or_else(|| boundaries.last().copied())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::message::Role;
+    use serde_json::json;
+
+    fn counter() -> TokenCounter {
+        TokenCounter::for_model("gpt-4o")
+    }
+
+    fn text(role: Role, words: usize) -> Message {
+        Message::new(role, vec![Content::Text { text: "word ".repeat(words) }])
+    }
+
+    fn tool_round(id: &str) -> Vec<Message> {
+        vec![
+            Message::new(Role::Assistant, vec![Content::ToolUse {
+                id: id.to_string(),
+                name: "bash".to_string(),
+                parameters: json!({"command": "ls"}),
+            }]),
+            Message::new(Role::User, vec![Content::ToolResult {
+                tool_use_id: id.to_string(),
+                output: "file ".repeat(200),
+                is_error: false,
+            }]),
+        ]
+    }
+
+    #[test]
+    fn test_truncate_keeps_history_that_fits() {
+        let messages = vec![text(Role::User, 10), text(Role::Assistant, 10)];
+        assert_eq!(truncate(messages.clone(), 1000, &counter()), messages);
+    }
+
+    #[test]
+    fn test_truncate_keeps_tool_pairs_together() {
+        let mut messages = vec![text(Role::User, 400)];
+        messages.extend(tool_round("call_1"));
+        messages.push(text(Role::Assistant, 50));
+        messages.push(text(Role::User, 20));
+        messages.extend(tool_round("call_2"));
+
+        let truncated = truncate(messages.clone(), 600, &counter());
+
+        // The first turn is dropped whole, starting over at the next user prompt
+        assert_eq!(truncated, messages[4..].to_vec());
+        assert!(truncated[0].is_user() && truncated[0].tool_result().is_empty());
+        assert!(truncated.last().unwrap().tool_result().len() == 1);
+    }
+
+    #[test]
+    fn test_truncate_without_boundary_leaves_history() {
+        let mut messages = vec![text(Role::User, 400)];
+        messages.extend(tool_round("call_1"));
+        messages.extend(tool_round("call_2"));
+
+        assert_eq!(truncate(messages.clone(), 10, &counter()), messages);
+    }
+
+    #[test]
+    fn test_create_moderator() {
+        for name in ["passive", "truncate", "summarize", "synopsis"] {
+            assert!(create_moderator(name).is_ok());
+        }
+        assert_eq!(create_moderator("magic").err().unwrap().to_string(), "Unknown moderator: magic");
+    }
+}
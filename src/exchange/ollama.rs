@@ -0,0 +1,292 @@
+use std::env;
+use std::sync::Mutex;
+use anyhow::{Context, Result};
+use log::debug;
+use serde_json::{json, Value};
+
+use crate::exchange::{Provider, ProviderError};
+use crate::models::{Message, Usage};
+use crate::models::message::{Content, Role};
+use crate::toolkit::Tool;
+
+pub const OLLAMA_HOST: &str = "http://localhost:11434";
+
+// Configuration options for Ollama provider
+#[derive(Debug, Clone)]
+pub struct OllamaOptions {
+    pub model: String,
+    pub temperature: f32,
+    pub max_tokens: u32,
+    pub system_prompt: Option<String>,
+    pub host: String,
+}
+
+impl Default for OllamaOptions {
+    fn default() -> Self {
+        // Honour the same variable as the ollama CLI, which may omit the scheme
+        let host = env::var("OLLAMA_HOST")
+            .map(|host| if host.contains("://") { host } else { format!("http://{}", host) })
+            .unwrap_or_else(|_| OLLAMA_HOST.to_string());
+
+        Self {
+            model: "llama3.1".to_string(),
+            temperature: 0.7,
+            max_tokens: 2048,
+            system_prompt: None,
+            host,
+        }
+    }
+}
+
+/// Provider for a local Ollama server, which needs no API key
+pub struct OllamaProvider {
+    client: reqwest::Client,
+    options: OllamaOptions,
+    last_token_usage: Mutex<Usage>,
+}
+
+impl OllamaProvider {
+    pub fn new(options: Option<OllamaOptions>) -> Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::new(),
+            options: options.unwrap_or_default(),
+            last_token_usage: Mutex::new(Usage::default()),
+        })
+    }
+
+    fn convert_message_to_ollama(message: &Message) -> Vec<Value> {
+        match message.role {
+            Role::User => {
+                // Tool results are sent back as separate tool messages
+                let mut converted: Vec<Value> = message.tool_result()
+                    .into_iter()
+                    .filter_map(|content| match content {
+                        Content::ToolResult { output, .. } => Some(json!({
+                            "role": "tool",
+                            "content": output,
+                        })),
+                        _ => None,
+                    })
+                    .collect();
+
+                let images: Vec<&str> = message.images()
+                    .into_iter()
+                    .filter_map(|content| match content {
+                        Content::Image { data, .. } => Some(data.as_str()),
+                        _ => None,
+                    })
+                    .collect();
+
+                let text = message.text();
+                if !text.is_empty() || !images.is_empty() || converted.is_empty() {
+                    let mut user = json!({ "role": "user", "content": text });
+                    if !images.is_empty() {
+                        user["images"] = json!(images);
+                    }
+                    converted.push(user);
+                }
+                converted
+            }
+            Role::Assistant => {
+                let mut assistant = json!({
+                    "role": "assistant",
+                    "content": message.text(),
+                });
+
+                let tool_calls: Vec<Value> = message.tool_use()
+                    .into_iter()
+                    .filter_map(|content| match content {
+                        Content::ToolUse { name, parameters, .. } => Some(json!({
+                            "function": {
+                                "name": name,
+                                "arguments": parameters,
+                            }
+                        })),
+                        _ => None,
+                    })
+                    .collect();
+                if !tool_calls.is_empty() {
+                    assistant["tool_calls"] = json!(tool_calls);
+                }
+                vec![assistant]
+            }
+            Role::System => vec![json!({
+                "role": "system",
+                "content": message.text(),
+            })],
+        }
+    }
+
+    fn convert_response_to_message(response: &Value) -> Result<Message> {
+        let message = response.get("message")
+            .ok_or_else(|| anyhow::anyhow!("Response did not contain a message"))?;
+
+        let mut content = Vec::new();
+        if let Some(text) = message.get("content").and_then(|c| c.as_str()) {
+            if !text.is_empty() {
+                content.push(Content::Text { text: text.to_string() });
+            }
+        }
+
+        if let Some(tool_calls) = message.get("tool_calls").and_then(|t| t.as_array()) {
+            debug!("Received {} tool call(s) from Ollama", tool_calls.len());
+            for tool_call in tool_calls {
+                let function = tool_call.get("function")
+                    .ok_or_else(|| anyhow::anyhow!("Tool call is missing its function"))?;
+
+                // Arguments are usually an object, but some models return a JSON string
+                let parameters = match function.get("arguments") {
+                    Some(Value::String(arguments)) => serde_json::from_str(arguments)
+                        .map_err(|e| anyhow::anyhow!("Failed to parse tool arguments: {}", e))?,
+                    Some(arguments) => arguments.clone(),
+                    None => json!({}),
+                };
+
+                // Ollama does not assign ids to tool calls
+                content.push(Content::ToolUse {
+                    id: format!("call_{}", uuid::Uuid::new_v4()),
+                    name: function.get("name").and_then(|n| n.as_str()).unwrap_or_default().to_string(),
+                    parameters,
+                });
+            }
+        }
+
+        if content.is_empty() {
+            return Err(anyhow::anyhow!("Response contained neither content nor tool calls"));
+        }
+
+        Ok(Message::new(Role::Assistant, content))
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for OllamaProvider {
+    async fn initialize(&mut self) -> Result<()> {
+        debug!("Initializing Ollama provider with model: {} at {}", self.options.model, self.options.host);
+        Ok(())
+    }
+
+    async fn generate(&self, messages: &[Message], tools: Option<Vec<Tool>>) -> Result<Message> {
+        *self.last_token_usage.lock().unwrap() = Usage::default();
+        let mut ollama_messages = Vec::new();
+
+        // Add system message if configured
+        if let Some(prompt) = &self.options.system_prompt {
+            ollama_messages.push(json!({ "role": "system", "content": prompt }));
+        }
+
+        // Add conversation history
+        ollama_messages.extend(messages.iter().flat_map(Self::convert_message_to_ollama));
+
+        let mut request = json!({
+            "model": self.options.model,
+            "messages": ollama_messages,
+            "stream": false,
+            "options": {
+                "temperature": self.options.temperature,
+                "num_predict": self.options.max_tokens,
+            },
+        });
+
+        // Add tools if provided
+        if let Some(tools) = tools {
+            request["tools"] = tools.into_iter()
+                .map(|tool| json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                    }
+                }))
+                .collect();
+        }
+
+        debug!("Sending request to Ollama");
+        let response = self.client
+            .post(format!("{}/api/chat", self.options.host.trim_end_matches('/')))
+            .json(&request)
+            .send()
+            .await
+            .map_err(ProviderError::from)
+            .with_context(|| format!("Failed to get response from Ollama at {}", self.options.host))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let headers = response.headers().clone();
+            let body: Value = response.json().await.unwrap_or_default();
+            let message = body.get("error")
+                .and_then(|m| m.as_str())
+                .unwrap_or("unknown error");
+            return Err(ProviderError::from_response(status, &headers, message.to_string()).into());
+        }
+
+        let body: Value = response.json().await
+            .context("Failed to parse response from Ollama")?;
+
+        // Update token usage tracking
+        let tokens = |field: &str| body.get(field).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        let usage = Usage::new(tokens("prompt_eval_count"), tokens("eval_count"));
+        *self.last_token_usage.lock().unwrap() = usage;
+        debug!("Token usage for request: {:?}", usage);
+
+        Self::convert_response_to_message(&body)
+    }
+
+    fn get_token_usage(&self) -> Usage {
+        *self.last_token_usage.lock().unwrap()
+    }
+
+    fn model(&self) -> &str {
+        &self.options.model
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_conversion() {
+        let assistant_msg = Message::new(Role::Assistant, vec![Content::ToolUse {
+            id: "call_1".to_string(),
+            name: "bash".to_string(),
+            parameters: json!({"command": "ls"}),
+        }]);
+        let result_msg = Message::new(Role::User, vec![Content::ToolResult {
+            tool_use_id: "call_1".to_string(),
+            output: "Cargo.toml".to_string(),
+            is_error: false,
+        }]);
+
+        let converted = OllamaProvider::convert_message_to_ollama(&assistant_msg);
+        assert_eq!(converted[0]["tool_calls"][0]["function"]["name"], "bash");
+        assert_eq!(converted[0]["tool_calls"][0]["function"]["arguments"]["command"], "ls");
+
+        let converted = OllamaProvider::convert_message_to_ollama(&result_msg);
+        assert_eq!(converted.len(), 1);
+        assert_eq!(converted[0]["role"], "tool");
+        assert_eq!(converted[0]["content"], "Cargo.toml");
+    }
+
+    #[test]
+    fn test_response_conversion_with_string_arguments() {
+        let response = json!({
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{"function": {"name": "bash", "arguments": "{\"command\": \"pwd\"}"}}]
+            }
+        });
+
+        let message = OllamaProvider::convert_response_to_message(&response).unwrap();
+        match message.tool_use().as_slice() {
+            [Content::ToolUse { id, name, parameters }] => {
+                assert!(id.starts_with("call_"));
+                assert_eq!(name, "bash");
+                assert_eq!(parameters["command"], "pwd");
+            }
+            other => panic!("Expected a single tool use, got {:?}", other),
+        }
+    }
+}
@@ -1,22 +1,29 @@
 use std::env;
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
 use anyhow::{Context, Result};
 use async_openai::{
-    config::{Config, OpenAIConfig},
+    config::{Config, OPENAI_API_BASE, OPENAI_ORGANIZATION_HEADER},
     types::{
         ChatCompletionRequestMessage, ChatCompletionRequestMessageContentPart, 
+        ChatCompletionRequestMessageContentPartImage, ImageUrl, ImageUrlDetail,
         CreateChatCompletionRequest, Role,
         ChatCompletionRequestUserMessage, ChatCompletionRequestAssistantMessage,
-        ChatCompletionRequestSystemMessage, ChatCompletionTool,
-        ChatCompletionFunctions,
+        ChatCompletionRequestSystemMessage, ChatCompletionRequestToolMessage,
+        ChatCompletionTool, ChatCompletionFunctions, ChatCompletionMessageToolCall,
+        ChatCompletionToolType, CreateChatCompletionResponse, FunctionCall,
     },
-    Client,
 };
+use futures::StreamExt;
 use log::debug;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+use secrecy::Secret;
+use serde_json::Value;
 
-use crate::exchange::Provider;
-use crate::models::Message;
-use crate::toolkit::{Tool, Toolkit};
+use crate::exchange::{Provider, ProviderError, ProviderStream, StreamEvent};
+use crate::exchange::stream::sse_data;
+use crate::models::{EndpointConfig, Message, Usage};
+use crate::models::message::Content;
+use crate::toolkit::Tool;
 
 // Configuration options for OpenAI provider
 #[derive(Debug, Clone)]
@@ -38,75 +45,231 @@ impl Default for OpenAIOptions {
     }
 }
 
+/// Client configuration for any OpenAI compatible endpoint
+#[derive(Clone, Debug)]
+pub struct CompatibleConfig {
+    api_base: String,
+    api_key: Secret<String>,
+    headers: HeaderMap,
+    query: Vec<(String, String)>,
+}
+
+impl CompatibleConfig {
+    pub fn from_endpoint(endpoint: &EndpointConfig) -> Result<Self> {
+        let api_key = match &endpoint.api_key_env {
+            Some(var) => env::var(var)
+                .with_context(|| format!("{} environment variable not set", var))?,
+            None => String::new(),
+        };
+
+        let mut headers = HeaderMap::new();
+        if !api_key.is_empty() {
+            match &endpoint.api_key_header {
+                Some(name) => headers.insert(HeaderName::try_from(name.as_str())?, HeaderValue::try_from(api_key.as_str())?),
+                None => headers.insert(AUTHORIZATION, HeaderValue::try_from(format!("Bearer {}", api_key))?),
+            };
+        }
+        if let Some(organization) = &endpoint.organization {
+            headers.insert(OPENAI_ORGANIZATION_HEADER, HeaderValue::try_from(organization.as_str())?);
+        }
+        for (name, value) in &endpoint.headers {
+            headers.insert(
+                HeaderName::try_from(name.as_str())
+                    .with_context(|| format!("Invalid header name: {}", name))?,
+                HeaderValue::try_from(value.as_str())
+                    .with_context(|| format!("Invalid value for header {}", name))?,
+            );
+        }
+
+        let query = endpoint.api_version.iter()
+            .map(|version| ("api-version".to_string(), version.clone()))
+            .collect();
+
+        Ok(Self {
+            api_base: endpoint.base_url.as_deref()
+                .unwrap_or(OPENAI_API_BASE)
+                .trim_end_matches('/')
+                .to_string(),
+            api_key: Secret::new(api_key),
+            headers,
+            query,
+        })
+    }
+}
+
+impl Config for CompatibleConfig {
+    fn headers(&self) -> HeaderMap {
+        self.headers.clone()
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.api_base, path)
+    }
+
+    fn query(&self) -> Vec<(&str, &str)> {
+        self.query.iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect()
+    }
+
+    fn api_base(&self) -> &str {
+        &self.api_base
+    }
+
+    fn api_key(&self) -> &Secret<String> {
+        &self.api_key
+    }
+}
+
 pub struct OpenAIProvider {
-    client: Client<OpenAIConfig>,
+    http: reqwest::Client,
+    config: CompatibleConfig,
     options: OpenAIOptions,
-    last_token_usage: AtomicU32,
+    last_token_usage: Mutex<Usage>,
 }
 
 impl OpenAIProvider {
     pub fn new(options: Option<OpenAIOptions>) -> Result<Self> {
-        // Check for API key
-        let api_key = env::var("OPENAI_API_KEY")
-            .context("OPENAI_API_KEY environment variable not set")?;
+        let endpoint = EndpointConfig {
+            api_key_env: Some("OPENAI_API_KEY".to_string()),
+            ..Default::default()
+        };
+        Self::from_endpoint(&endpoint, options)
+    }
+
+    /// Create a provider for an OpenAI compatible endpoint
+    pub fn from_endpoint(endpoint: &EndpointConfig, options: Option<OpenAIOptions>) -> Result<Self> {
+        let config = CompatibleConfig::from_endpoint(endpoint)?;
+        debug!("Using OpenAI compatible endpoint at {}", config.api_base());
 
-        let config = OpenAIConfig::new().with_api_key(api_key);
-        
         Ok(Self {
-            client: Client::with_config(config),
+            http: reqwest::Client::new(),
+            config,
             options: options.unwrap_or_default(),
-            last_token_usage: AtomicU32::new(0),
+            last_token_usage: Mutex::new(Usage::default()),
         })
     }
 
-    fn convert_message_to_openai(message: &Message) -> ChatCompletionRequestMessage {
+    fn convert_message_to_openai(message: &Message) -> Vec<ChatCompletionRequestMessage> {
         match message.role {
             crate::models::message::Role::User => {
-                ChatCompletionRequestMessage::User(
-                    ChatCompletionRequestUserMessage {
-                        content: Some(vec![ChatCompletionRequestMessageContentPart::Text(message.text().into())].into()),
-                        name: None,
-                        role: Role::User,
+                // Tool results are sent back as separate tool messages
+                let mut converted: Vec<ChatCompletionRequestMessage> = message.tool_result()
+                    .into_iter()
+                    .filter_map(|content| match content {
+                        Content::ToolResult { tool_use_id, output, .. } => Some(
+                            ChatCompletionRequestMessage::Tool(
+                                ChatCompletionRequestToolMessage {
+                                    role: Role::Tool,
+                                    content: Some(output.clone()),
+                                    tool_call_id: tool_use_id.clone(),
+                                }
+                            )
+                        ),
+                        _ => None,
+                    })
+                    .collect();
+
+                let text = message.text();
+                let mut parts = Vec::new();
+                if !text.is_empty() || (converted.is_empty() && message.images().is_empty()) {
+                    parts.push(ChatCompletionRequestMessageContentPart::Text(text.into()));
+                }
+                for image in message.images() {
+                    if let Content::Image { data, mime_type } = image {
+                        parts.push(ChatCompletionRequestMessageContentPart::Image(
+                            ChatCompletionRequestMessageContentPartImage {
+                                r#type: "image_url".to_string(),
+                                image_url: ImageUrl {
+                                    url: format!("data:{};base64,{}", mime_type, data),
+                                    detail: ImageUrlDetail::Auto,
+                                },
+                            }
+                        ));
                     }
-                )
+                }
+                if !parts.is_empty() {
+                    converted.push(ChatCompletionRequestMessage::User(
+                        ChatCompletionRequestUserMessage {
+                            content: Some(parts.into()),
+                            name: None,
+                            role: Role::User,
+                        }
+                    ));
+                }
+                converted
             }
             crate::models::message::Role::Assistant => {
-                ChatCompletionRequestMessage::Assistant(
+                let tool_calls: Vec<ChatCompletionMessageToolCall> = message.tool_use()
+                    .into_iter()
+                    .filter_map(|content| match content {
+                        Content::ToolUse { id, name, parameters } => Some(ChatCompletionMessageToolCall {
+                            id: id.clone(),
+                            r#type: ChatCompletionToolType::Function,
+                            function: FunctionCall {
+                                name: name.clone(),
+                                arguments: parameters.to_string(),
+                            },
+                        }),
+                        _ => None,
+                    })
+                    .collect();
+
+                let text = message.text();
+                vec![ChatCompletionRequestMessage::Assistant(
                     ChatCompletionRequestAssistantMessage {
-                        content: Some(message.text()),
+                        content: if text.is_empty() { None } else { Some(text) },
                         name: None,
                         role: Role::Assistant,
-                        function_call: None,
-                        tool_calls: None,
+                        tool_calls: if tool_calls.is_empty() { None } else { Some(tool_calls) },
+                        ..Default::default()
                     }
-                )
+                )]
+            }
+            crate::models::message::Role::System => {
+                vec![ChatCompletionRequestMessage::System(
+                    ChatCompletionRequestSystemMessage {
+                        content: Some(message.text()),
+                        name: None,
+                        role: Role::System,
+                    }
+                )]
             }
         }
     }
 
-    fn create_system_message(&self) -> Option<ChatCompletionRequestMessage> {
-        self.options.system_prompt.as_ref().map(|prompt| {
-            ChatCompletionRequestMessage::System(
-                ChatCompletionRequestSystemMessage {
-                    content: Some(prompt.clone()),
-                    name: None,
-                    role: Role::System,
-                }
-            )
-        })
-    }
-}
+    async fn send(&self, request: &Value) -> Result<reqwest::Response> {
+        let response = self.http
+            .post(self.config.url("/chat/completions"))
+            .query(&self.config.query())
+            .headers(self.config.headers())
+            .json(request)
+            .send()
+            .await
+            .map_err(ProviderError::from)
+            .context("Failed to get response from OpenAI")?;
 
-#[async_trait::async_trait]
-impl Provider for OpenAIProvider {
-    async fn initialize(&mut self) -> Result<()> {
-        debug!("Initializing OpenAI provider with model: {}", self.options.model);
-        Ok(())
+        let status = response.status();
+        if !status.is_success() {
+            let headers = response.headers().clone();
+            let body = response.text().await.unwrap_or_default();
+            let error = serde_json::from_str::<Value>(&body).ok()
+                .and_then(|body| body.get("error").cloned())
+                .unwrap_or_default();
+            let mut message = error.get("message").and_then(|m| m.as_str()).map(String::from).unwrap_or(body);
+            // The error code is more reliable than the message for spotting context overflow
+            if let Some(code) = error.get("code").and_then(|c| c.as_str()) {
+                message = format!("{} ({})", message, code);
+            }
+            return Err(ProviderError::from_response(status, &headers, message).into());
+        }
+
+        Ok(response)
     }
-    
-    async fn generate(&self, messages: &[Message], tools: Option<Vec<Tool>>) -> Result<Message> {
+
+    fn create_request(&self, messages: &[Message], tools: Option<Vec<Tool>>) -> CreateChatCompletionRequest {
         let mut openai_messages = Vec::new();
-        
+
         // Add system message if configured
         if let Some(system_msg) = self.create_system_message() {
             openai_messages.push(system_msg);
@@ -115,7 +278,7 @@ impl Provider for OpenAIProvider {
         // Add conversation history
         openai_messages.extend(
             messages.iter()
-                .map(Self::convert_message_to_openai)
+                .flat_map(Self::convert_message_to_openai)
         );
 
         let mut request = CreateChatCompletionRequest {
@@ -140,124 +303,166 @@ impl Provider for OpenAIProvider {
             }).collect());
         }
 
+        request
+    }
+
+    /// Convert a streamed chat completion chunk into stream events
+    fn convert_chunk_to_events(chunk: &Value) -> Vec<StreamEvent> {
+        let mut events = Vec::new();
+
+        if let Some(delta) = chunk.pointer("/choices/0/delta") {
+            if let Some(text) = delta.get("content").and_then(|c| c.as_str()) {
+                if !text.is_empty() {
+                    events.push(StreamEvent::Text(text.to_string()));
+                }
+            }
+
+            for tool_call in delta.get("tool_calls").and_then(|t| t.as_array()).into_iter().flatten() {
+                let string = |pointer: &str| tool_call.pointer(pointer)
+                    .and_then(|v| v.as_str())
+                    .map(String::from);
+                events.push(StreamEvent::ToolCall {
+                    index: tool_call.get("index").and_then(|i| i.as_u64()).unwrap_or(0) as usize,
+                    id: string("/id"),
+                    name: string("/function/name"),
+                    arguments: string("/function/arguments").unwrap_or_default(),
+                });
+            }
+        }
+
+        if let Some(usage) = chunk.get("usage").filter(|u| !u.is_null()) {
+            events.push(StreamEvent::Usage(Self::parse_usage(usage)));
+        }
+
+        events
+    }
+
+    /// Split the reported usage so cached prompt tokens are not counted as input
+    fn parse_usage(usage: &Value) -> Usage {
+        let tokens = |pointer: &str| usage.pointer(pointer).and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        let cached = tokens("/prompt_tokens_details/cached_tokens");
+        Usage {
+            input_tokens: tokens("/prompt_tokens").saturating_sub(cached),
+            output_tokens: tokens("/completion_tokens"),
+            cache_read_tokens: cached,
+            cache_write_tokens: 0,
+        }
+    }
+
+    fn create_system_message(&self) -> Option<ChatCompletionRequestMessage> {
+        self.options.system_prompt.as_ref().map(|prompt| {
+            ChatCompletionRequestMessage::System(
+                ChatCompletionRequestSystemMessage {
+                    content: Some(prompt.clone()),
+                    name: None,
+                    role: Role::System,
+                }
+            )
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Provider for OpenAIProvider {
+    async fn initialize(&mut self) -> Result<()> {
+        debug!("Initializing OpenAI provider with model: {}", self.options.model);
+        Ok(())
+    }
+    
+    async fn generate(&self, messages: &[Message], tools: Option<Vec<Tool>>) -> Result<Message> {
+        // Usage is per request, a response without any must not repeat the last one
+        *self.last_token_usage.lock().unwrap() = Usage::default();
+        let request = serde_json::to_value(self.create_request(messages, tools))?;
+
         debug!("Sending request to OpenAI API");
-        let response = self.client
-            .chat()
-            .create(request)
+        let body: Value = self.send(&request).await?
+            .json()
             .await
-            .context("Failed to get response from OpenAI")?;
+            .context("Failed to parse response from OpenAI")?;
 
         // Update token usage tracking
-        if let Some(usage) = response.usage {
-            self.last_token_usage.store(usage.total_tokens, Ordering::SeqCst);
-            debug!("Token usage for request: {}", usage.total_tokens);
+        if let Some(usage) = body.get("usage").filter(|u| !u.is_null()) {
+            let usage = Self::parse_usage(usage);
+            *self.last_token_usage.lock().unwrap() = usage;
+            debug!("Token usage for request: {:?}", usage);
         }
 
-        // Extract the response content or tool calls
+        let response: CreateChatCompletionResponse = serde_json::from_value(body)
+            .context("Failed to parse response from OpenAI")?;
+
+        // Extract the response content and tool calls
         let message = &response.choices[0].message;
-        
+        let mut content = Vec::new();
+
+        if let Some(text) = &message.content {
+            if !text.is_empty() {
+                content.push(Content::Text { text: text.clone() });
+            }
+        }
+
         if let Some(tool_calls) = &message.tool_calls {
-            debug!("Received tool call response from OpenAI API");
-            
-            // Create a Tool instance from each tool call
-            let mut results = Vec::new();
+            debug!("Received {} tool call(s) from OpenAI API", tool_calls.len());
             for tool_call in tool_calls {
-                let tool = Tool::new(
-                    &tool_call.function.name,
-                    "", // Description not needed for execution
+                let parameters = if tool_call.function.arguments.trim().is_empty() {
+                    serde_json::json!({})
+                } else {
                     serde_json::from_str(&tool_call.function.arguments)
-                        .map_err(|e| anyhow::anyhow!("Failed to parse tool arguments: {}", e))?,
-                    vec![], // Required params already validated by OpenAI
-                );
-                
-                // Execute the tool using the default toolkit
-                let toolkit = crate::toolkit::default::DefaultToolkit::new();
-                let result = toolkit.process_tool(&tool).await?;
-                results.push(result.text());
+                        .map_err(|e| anyhow::anyhow!("Failed to parse tool arguments: {}", e))?
+                };
+                content.push(Content::ToolUse {
+                    id: tool_call.id.clone(),
+                    name: tool_call.function.name.clone(),
+                    parameters,
+                });
             }
-            
-            // Combine all results
-            let content = results.join("\n\n");
-            Ok(Message::assistant(&content))
-        } else if let Some(content) = &message.content {
-            debug!("Received text response from OpenAI API");
-            Ok(Message::assistant(content))
-        } else {
-            Err(anyhow::anyhow!("Response contained neither content nor tool calls"))
         }
-    }
 
-    fn get_token_usage(&self) -> u32 {
-        self.last_token_usage.load(Ordering::SeqCst)
+        if content.is_empty() {
+            return Err(anyhow::anyhow!("Response contained neither content nor tool calls"));
+        }
+
+        Ok(Message::new(crate::models::message::Role::Assistant, content))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use dotenv::dotenv;
+    async fn stream(&self, messages: &[Message], tools: Option<Vec<Tool>>) -> Result<ProviderStream> {
+        *self.last_token_usage.lock().unwrap() = Usage::default();
+        let mut request = serde_json::to_value(self.create_request(messages, tools))?;
+        request["stream"] = Value::Bool(true);
+        request["stream_options"] = serde_json::json!({ "include_usage": true });
 
-    #[tokio::test]
-    async fn test_openai_conversation() -> Result<()> {
-        // Load environment variables
-        dotenv().ok();
+        debug!("Streaming request to OpenAI API");
+        let response = self.send(&request).await?;
 
-        // Create provider with test options
-        let options = OpenAIOptions {
-            model: "gpt-4".to_string(),
-            temperature: 0.7,
-            max_tokens: 2048,
-            system_prompt: Some("You are a helpful assistant.".to_string()),
-        };
-        let provider = OpenAIProvider::new(Some(options)).unwrap();
-        
-        // Test a simple conversation
-        let messages = vec![Message::user("Hello!")];
-        
-        let response = provider.generate(&messages, None).await?;
-        assert!(!response.text().is_empty());
-        
-        Ok(())
+        let events = sse_data(response).flat_map(|data| {
+            let events = data.and_then(|data| {
+                let chunk: Value = serde_json::from_str(&data)
+                    .context("Failed to parse stream chunk from OpenAI")?;
+                if let Some(message) = chunk.pointer("/error/message").and_then(|m| m.as_str()) {
+                    return Err(anyhow::anyhow!("OpenAI API error: {}", message));
+                }
+                Ok(Self::convert_chunk_to_events(&chunk))
+            });
+            let events: Vec<Result<StreamEvent>> = match events {
+                Ok(events) => events.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            };
+            futures::stream::iter(events)
+        });
+        Ok(Box::pin(events))
     }
 
-    #[tokio::test]
-    async fn test_openai_tool_response() -> Result<()> {
-        dotenv().ok();
-
-        let options = OpenAIOptions {
-            model: "gpt-4".to_string(),
-            temperature: 0.7,
-            max_tokens: 2048,
-            system_prompt: None,
-        };
-        let provider = OpenAIProvider::new(Some(options)).unwrap();
-
-        // Create a test tool using bash which is supported
-        let tool = Tool::new(
-            "bash",
-            "Execute a bash command",
-            serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "command": {
-                        "type": "string",
-                        "description": "The command to execute"
-                    }
-                },
-                "required": ["command"]
-            }),
-            vec!["command".to_string()],
-        );
+    fn get_token_usage(&self) -> Usage {
+        *self.last_token_usage.lock().unwrap()
+    }
 
-        // Test conversation with tool
-        let messages = vec![Message::user("Run the bash command")];
-        let response = provider.generate(&messages, Some(vec![tool])).await?;
-        
-        // Response should contain either content or tool call info
-        assert!(!response.text().is_empty());
-        
-        Ok(())
+    fn model(&self) -> &str {
+        &self.options.model
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
     #[tokio::test]
     async fn test_message_conversion() {
@@ -267,20 +472,81 @@ mod tests {
         let openai_user = OpenAIProvider::convert_message_to_openai(&user_msg);
         let openai_assistant = OpenAIProvider::convert_message_to_openai(&assistant_msg);
 
-        match openai_user {
-            ChatCompletionRequestMessage::User(msg) => {
+        match &openai_user[..] {
+            [ChatCompletionRequestMessage::User(msg)] => {
                 assert_eq!(msg.role, Role::User);
                 assert!(msg.content.is_some());
             }
             _ => panic!("Expected User message"),
         }
 
-        match openai_assistant {
-            ChatCompletionRequestMessage::Assistant(msg) => {
+        match &openai_assistant[..] {
+            [ChatCompletionRequestMessage::Assistant(msg)] => {
                 assert_eq!(msg.role, Role::Assistant);
-                assert_eq!(msg.content.unwrap(), "Hi there");
+                assert_eq!(msg.content.as_deref(), Some("Hi there"));
+            }
+            _ => panic!("Expected Assistant message"),
+        }
+    }
+
+    #[test]
+    fn test_tool_message_conversion() {
+        let assistant_msg = Message::new(
+            crate::models::message::Role::Assistant,
+            vec![Content::ToolUse {
+                id: "call_1".to_string(),
+                name: "bash".to_string(),
+                parameters: serde_json::json!({"command": "ls"}),
+            }],
+        );
+        let result_msg = Message::new(
+            crate::models::message::Role::User,
+            vec![Content::ToolResult {
+                tool_use_id: "call_1".to_string(),
+                output: "Cargo.toml".to_string(),
+                is_error: false,
+            }],
+        );
+
+        match &OpenAIProvider::convert_message_to_openai(&assistant_msg)[..] {
+            [ChatCompletionRequestMessage::Assistant(msg)] => {
+                assert!(msg.content.is_none());
+                let calls = msg.tool_calls.as_ref().unwrap();
+                assert_eq!(calls[0].id, "call_1");
+                assert_eq!(calls[0].function.name, "bash");
+                assert_eq!(calls[0].function.arguments, r#"{"command":"ls"}"#);
             }
             _ => panic!("Expected Assistant message"),
         }
+
+        match &OpenAIProvider::convert_message_to_openai(&result_msg)[..] {
+            [ChatCompletionRequestMessage::Tool(msg)] => {
+                assert_eq!(msg.tool_call_id, "call_1");
+                assert_eq!(msg.content.as_deref(), Some("Cargo.toml"));
+            }
+            _ => panic!("Expected a single Tool message"),
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_system_and_image_conversion() {
+        match &OpenAIProvider::convert_message_to_openai(&Message::system("Be brief"))[..] {
+            [ChatCompletionRequestMessage::System(msg)] => {
+                assert_eq!(msg.content.as_deref(), Some("Be brief"));
+            }
+            _ => panic!("Expected System message"),
+        }
+
+        let image_msg = Message::new(
+            crate::models::message::Role::User,
+            vec![Content::Image { data: "aGVsbG8=".to_string(), mime_type: "image/png".to_string() }],
+        );
+        match &OpenAIProvider::convert_message_to_openai(&image_msg)[..] {
+            [ChatCompletionRequestMessage::User(msg)] => {
+                let json = serde_json::to_value(msg).unwrap();
+                assert_eq!(json["content"][0]["image_url"]["url"], "data:image/png;base64,aGVsbG8=");
+            }
+            _ => panic!("Expected User message"),
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::time::Duration;
+use anyhow::Result;
+use async_trait::async_trait;
+use log::warn;
+use rand::Rng;
+
+use crate::exchange::{Provider, ProviderError, ProviderStream};
+use crate::models::{Message, RetryConfig, Usage};
+use crate::toolkit::Tool;
+
+/// Wraps a provider to retry rate limits, server errors and network failures
+/// with exponential backoff and jitter
+///
+/// Streams are only retried while the request is being established, an error
+/// part way through a response is returned to the caller.
+pub struct RetryProvider {
+    inner: Box<dyn Provider>,
+    config: RetryConfig,
+}
+
+impl RetryProvider {
+    pub fn new(inner: Box<dyn Provider>, config: RetryConfig) -> Self {
+        Self { inner, config }
+    }
+
+    /// The delay before the given retry, starting from 0, `None` when the
+    /// provider asks to wait longer than the maximum delay
+    fn backoff(&self, attempt: u32, error: &ProviderError) -> Option<Duration> {
+        if let Some(retry_after) = error.retry_after() {
+            return (retry_after <= Duration::from_millis(self.config.max_delay_ms)).then_some(retry_after);
+        }
+
+        let delay = self.config.initial_delay_ms as f64 * self.config.backoff_multiplier.powi(attempt as i32);
+        let delay = delay.min(self.config.max_delay_ms as f64);
+        // Jitter keeps concurrent sessions from retrying in lockstep
+        let delay = rand::thread_rng().gen_range(delay / 2.0..=delay);
+        Some(Duration::from_millis(delay as u64))
+    }
+
+    async fn with_retries<T, F, Fut>(&self, mut request: F) -> Result<T>
+    where
+        F: FnMut() -> Fut + Send,
+        Fut: std::future::Future<Output = Result<T>> + Send,
+    {
+        let mut attempt = 0;
+        loop {
+            let error = match request().await {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+
+            let retryable = match error.downcast_ref::<ProviderError>() {
+                Some(provider_error) if provider_error.is_retryable() && attempt < self.config.max_retries => provider_error,
+                _ => return Err(error),
+            };
+
+            let Some(delay) = self.backoff(attempt, retryable) else {
+                warn!(
+                    "{}, the provider asks to wait longer than the maximum delay of {}ms",
+                    retryable,
+                    self.config.max_delay_ms
+                );
+                return Err(error);
+            };
+            attempt += 1;
+            warn!(
+                "{}, retrying in {:.1}s (attempt {} of {})",
+                retryable,
+                delay.as_secs_f64(),
+                attempt,
+                self.config.max_retries
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[async_trait]
+impl Provider for RetryProvider {
+    async fn initialize(&mut self) -> Result<()> {
+        self.inner.initialize().await
+    }
+
+    async fn generate(&self, messages: &[Message], tools: Option<Vec<Tool>>) -> Result<Message> {
+        self.with_retries(|| self.inner.generate(messages, tools.clone())).await
+    }
+
+    async fn stream(&self, messages: &[Message], tools: Option<Vec<Tool>>) -> Result<ProviderStream> {
+        self.with_retries(|| self.inner.stream(messages, tools.clone())).await
+    }
+
+    fn get_token_usage(&self) -> Usage {
+        self.inner.get_token_usage()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    /// Fails with the given error a number of times before answering
+    struct FlakyProvider {
+        failures: u32,
+        error: ProviderError,
+        calls: Arc<AtomicU32>,
+    }
+
+    #[async_trait]
+    impl Provider for FlakyProvider {
+        async fn initialize(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn generate(&self, _messages: &[Message], _tools: Option<Vec<Tool>>) -> Result<Message> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                return Err(self.error.clone().into());
+            }
+            Ok(Message::assistant("ok"))
+        }
+
+        fn get_token_usage(&self) -> Usage {
+            Usage::default()
+        }
+    }
+
+    fn retry_provider(failures: u32, error: ProviderError) -> (RetryProvider, Arc<AtomicU32>) {
+        let calls = Arc::new(AtomicU32::new(0));
+        let inner = FlakyProvider { failures, error, calls: Arc::clone(&calls) };
+        let config = RetryConfig {
+            max_retries: 2,
+            initial_delay_ms: 1,
+            max_delay_ms: 5,
+            backoff_multiplier: 2.0,
+        };
+        (RetryProvider::new(Box::new(inner), config), calls)
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors() {
+        let (provider, calls) = retry_provider(2, ProviderError::Network("connection reset".to_string()));
+        let response = provider.generate(&[Message::user("hi")], None).await.unwrap();
+        assert_eq!(response.text(), "ok");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        // Gives up once the retries are exhausted
+        let (provider, calls) = retry_provider(3, ProviderError::Server { status: 503, message: "down".to_string() });
+        let error = provider.generate(&[Message::user("hi")], None).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<ProviderError>(), Some(ProviderError::Server { .. })));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_does_not_retry_auth_errors() {
+        let (provider, calls) = retry_provider(1, ProviderError::Auth("invalid key".to_string()));
+        let error = provider.generate(&[Message::user("hi")], None).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<ProviderError>(), Some(ProviderError::Auth(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_backoff_is_bounded() {
+        let (provider, _) = retry_provider(0, ProviderError::Network(String::new()));
+        let error = ProviderError::Network(String::new());
+        for attempt in 0..10 {
+            assert!(provider.backoff(attempt, &error).unwrap() <= Duration::from_millis(5));
+        }
+
+        let rate_limited = |retry_after| ProviderError::RateLimited {
+            message: String::new(),
+            retry_after: Some(retry_after),
+        };
+        assert_eq!(provider.backoff(0, &rate_limited(Duration::from_millis(3))), Some(Duration::from_millis(3)));
+        // Waiting longer than the maximum delay is not worth it
+        assert_eq!(provider.backoff(0, &rate_limited(Duration::from_secs(30))), None);
+    }
+}
@@ -0,0 +1,261 @@
+use std::pin::Pin;
+use anyhow::{anyhow, Result};
+use futures::{Stream, StreamExt};
+use serde_json::json;
+
+use crate::models::{Message, Usage};
+use crate::models::message::{Content, Role};
+
+/// An incremental piece of a provider response
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// A fragment of assistant text
+    Text(String),
+    /// A fragment of a tool call, fragments sharing an index belong to the same call
+    ToolCall {
+        index: usize,
+        id: Option<String>,
+        name: Option<String>,
+        arguments: String,
+    },
+    /// Token usage reported for the request
+    Usage(Usage),
+}
+
+/// A stream of response events from a provider
+pub type ProviderStream = Pin<Box<dyn Stream<Item = Result<StreamEvent>> + Send>>;
+
+/// Convert a complete message into stream events, for providers without native streaming
+pub fn stream_from_message(message: &Message, token_usage: Usage) -> ProviderStream {
+    let mut events = Vec::new();
+    let mut index = 0;
+    for content in &message.content {
+        match content {
+            Content::Text { text } => events.push(Ok(StreamEvent::Text(text.clone()))),
+            Content::ToolUse { id, name, parameters } => {
+                events.push(Ok(StreamEvent::ToolCall {
+                    index,
+                    id: Some(id.clone()),
+                    name: Some(name.clone()),
+                    arguments: parameters.to_string(),
+                }));
+                index += 1;
+            }
+            _ => {}
+        }
+    }
+    events.push(Ok(StreamEvent::Usage(token_usage)));
+    Box::pin(futures::stream::iter(events))
+}
+
+#[derive(Debug, Default)]
+struct PartialToolCall {
+    id: String,
+    name: String,
+    arguments: String,
+}
+
+/// Assembles stream events back into a complete assistant message
+#[derive(Debug, Default)]
+pub struct MessageAssembler {
+    text: String,
+    tool_calls: Vec<(usize, PartialToolCall)>,
+    token_usage: Usage,
+}
+
+impl MessageAssembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, event: &StreamEvent) {
+        match event {
+            StreamEvent::Text(text) => self.text.push_str(text),
+            StreamEvent::ToolCall { index, id, name, arguments } => {
+                let position = match self.tool_calls.iter().position(|(i, _)| i == index) {
+                    Some(position) => position,
+                    None => {
+                        self.tool_calls.push((*index, PartialToolCall::default()));
+                        self.tool_calls.len() - 1
+                    }
+                };
+                let call = &mut self.tool_calls[position].1;
+                if let Some(id) = id {
+                    call.id.push_str(id);
+                }
+                if let Some(name) = name {
+                    call.name.push_str(name);
+                }
+                call.arguments.push_str(arguments);
+            }
+            StreamEvent::Usage(usage) => self.token_usage += *usage,
+        }
+    }
+
+    /// Token usage reported by the stream so far
+    pub fn token_usage(&self) -> Usage {
+        self.token_usage
+    }
+
+    /// Build the assistant message from everything received
+    pub fn finish(self) -> Result<Message> {
+        let mut content = Vec::new();
+        if !self.text.is_empty() {
+            content.push(Content::Text { text: self.text });
+        }
+
+        for (_, call) in self.tool_calls {
+            let parameters = if call.arguments.trim().is_empty() {
+                json!({})
+            } else {
+                serde_json::from_str(&call.arguments)
+                    .map_err(|e| anyhow!("Failed to parse tool arguments: {}", e))?
+            };
+            let id = if call.id.is_empty() {
+                format!("call_{}", uuid::Uuid::new_v4())
+            } else {
+                call.id
+            };
+            content.push(Content::ToolUse { id, name: call.name, parameters });
+        }
+
+        if content.is_empty() {
+            return Err(anyhow!("Response contained neither content nor tool calls"));
+        }
+
+        Ok(Message::new(Role::Assistant, content))
+    }
+}
+
+/// Split a streaming HTTP response body into lines
+pub(crate) fn response_lines(response: reqwest::Response) -> impl Stream<Item = Result<String>> + Send {
+    let body = Box::pin(response.bytes_stream());
+    futures::stream::unfold((body, Vec::new(), false), |(mut body, mut buffer, mut done)| async move {
+        loop {
+            if let Some(position) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=position).collect();
+                let line = String::from_utf8_lossy(&line).trim_end().to_string();
+                return Some((Ok(line), (body, buffer, done)));
+            }
+            if done {
+                if buffer.is_empty() {
+                    return None;
+                }
+                let line = String::from_utf8_lossy(&buffer).trim_end().to_string();
+                buffer.clear();
+                return Some((Ok(line), (body, buffer, done)));
+            }
+            match body.next().await {
+                Some(Ok(chunk)) => buffer.extend_from_slice(&chunk),
+                Some(Err(e)) => return Some((Err(anyhow!("Failed to read response stream: {}", e)), (body, Vec::new(), true))),
+                None => done = true,
+            }
+        }
+    })
+}
+
+/// Extract the data payloads of a server-sent events response
+pub(crate) fn sse_data(response: reqwest::Response) -> impl Stream<Item = Result<String>> + Send {
+    response_lines(response).filter_map(|line| async move {
+        match line {
+            Ok(line) => line.strip_prefix("data:")
+                .map(|data| data.trim().to_string())
+                .filter(|data| !data.is_empty() && data != "[DONE]")
+                .map(Ok),
+            Err(e) => Some(Err(e)),
+        }
+    })
+}
+
+/// An event of a server-sent events response
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SseEvent {
+    /// The event name, `None` for unnamed `message` events
+    pub event: Option<String>,
+    pub data: String,
+}
+
+/// Split a server-sent events response into its events, keeping their names
+pub(crate) fn sse_events(response: reqwest::Response) -> impl Stream<Item = Result<SseEvent>> + Send {
+    let lines = Box::pin(response_lines(response));
+    futures::stream::unfold(Some(lines), |lines| async move {
+        let mut lines = lines?;
+        let mut event = None;
+        let mut data: Vec<String> = Vec::new();
+        loop {
+            match lines.next().await {
+                Some(Ok(line)) if line.is_empty() => {
+                    if !data.is_empty() {
+                        return Some((Ok(SseEvent { event, data: data.join("\n") }), Some(lines)));
+                    }
+                    event = None;
+                }
+                Some(Ok(line)) => {
+                    let (field, value) = line.split_once(':').unwrap_or((&line, ""));
+                    let value = value.strip_prefix(' ').unwrap_or(value);
+                    match field {
+                        "event" => event = Some(value.to_string()),
+                        "data" => data.push(value.to_string()),
+                        _ => {} // Comments, ids and retry hints
+                    }
+                }
+                Some(Err(e)) => return Some((Err(e), None)),
+                None if data.is_empty() => return None,
+                None => return Some((Ok(SseEvent { event, data: data.join("\n") }), None)),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assemble_interleaved_tool_calls() {
+        let mut assembler = MessageAssembler::new();
+        let events = vec![
+            StreamEvent::Text("Checking".to_string()),
+            StreamEvent::ToolCall { index: 0, id: Some("call_a".to_string()), name: Some("bash".to_string()), arguments: String::new() },
+            StreamEvent::ToolCall { index: 1, id: Some("call_b".to_string()), name: Some("text_editor".to_string()), arguments: "{\"command\":".to_string() },
+            StreamEvent::ToolCall { index: 0, id: None, name: None, arguments: "{\"command\": \"ls\"}".to_string() },
+            StreamEvent::ToolCall { index: 1, id: None, name: None, arguments: " \"view\"}".to_string() },
+            StreamEvent::Text(" files".to_string()),
+            StreamEvent::Usage(Usage::new(5, 2)),
+        ];
+        for event in &events {
+            assembler.push(event);
+        }
+        assert_eq!(assembler.token_usage().total(), 7);
+
+        let message = assembler.finish().unwrap();
+        assert_eq!(message.text(), "Checking files");
+        match message.tool_use().as_slice() {
+            [Content::ToolUse { id: first, parameters: first_params, .. }, Content::ToolUse { id: second, parameters: second_params, .. }] => {
+                assert_eq!(first, "call_a");
+                assert_eq!(first_params["command"], "ls");
+                assert_eq!(second, "call_b");
+                assert_eq!(second_params["command"], "view");
+            }
+            other => panic!("Expected two tool uses, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_from_message_round_trip() {
+        let message = Message::new(Role::Assistant, vec![
+            Content::Text { text: "Hi".to_string() },
+            Content::ToolUse { id: "call_1".to_string(), name: "bash".to_string(), parameters: json!({"command": "ls"}) },
+        ]);
+
+        let mut assembler = MessageAssembler::new();
+        let mut stream = stream_from_message(&message, Usage::new(2, 1));
+        while let Some(event) = stream.next().await {
+            assembler.push(&event.unwrap());
+        }
+        assert_eq!(assembler.token_usage(), Usage::new(2, 1));
+
+        let assembled = assembler.finish().unwrap();
+        assert_eq!(assembled.content, message.content);
+    }
+}
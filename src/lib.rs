@@ -2,8 +2,8 @@ pub mod cli;
 pub mod exchange;
 pub mod input;
 pub mod models;
-pub mod session;
 pub mod stats;
+pub mod tokens;
 pub mod toolkit;
 pub mod utils;
 
@@ -2,6 +2,18 @@ use anyhow::Result;
 use clap::{Parser, Subcommand, CommandFactory};
 use colored::*;
 use std::path::PathBuf;
+use chrono::NaiveDate;
+use rust_goose::utils::session_file::{
+    delete_session, list_sessions, list_sorted_session_files, parse_age, read_header, sessions_to_clear,
+};
+use rust_goose::cli::output::{create_renderer, OutputEvent, OutputFormat, Renderer};
+use rust_goose::cli::session::RunOutcome;
+use rust_goose::models::plan::{parse_plan_arg, Plan};
+use rust_goose::models::Usage;
+use rust_goose::stats::report::{render, ReportColumns, ReportFormat, ReportRow};
+use rust_goose::toolkit::ToolkitRegistry;
+use rust_goose::toolkit::mcp::McpServer;
+use rust_goose::stats::{Period, StatsFilter, StatsStore, StatsTracker};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -27,9 +39,14 @@ enum Commands {
         #[command(subcommand)]
         command: ToolkitCommands,
     },
+    /// Work with the Model Context Protocol
+    Mcp {
+        #[command(subcommand)]
+        command: McpCommands,
+    },
     /// Run a single-pass session with a message from a markdown input file
     Run {
-        /// Path to message file (optional)
+        /// Path to message file, read from stdin if not given
         message_file: Option<PathBuf>,
         /// Profile to use
         #[arg(long)]
@@ -43,6 +60,9 @@ enum Commands {
         /// Enable tracing
         #[arg(long)]
         tracing: bool,
+        /// Output format: text, json or stream-json
+        #[arg(long, default_value = "text")]
+        output_format: OutputFormat,
     },
 }
 
@@ -58,6 +78,9 @@ enum SessionCommands {
         /// Plan file path
         #[arg(long)]
         plan: Option<PathBuf>,
+        /// Argument for the plan as key=value, may be repeated
+        #[arg(long = "args", value_parser = parse_plan_arg, requires = "plan")]
+        args: Vec<(String, String)>,
         /// Log level
         #[arg(long, default_value = "INFO")]
         log_level: String,
@@ -83,6 +106,12 @@ enum SessionCommands {
         /// Keep this many entries
         #[arg(long, default_value = "3")]
         keep: u32,
+        /// Only delete sessions last modified longer ago than this, such as 30d, 2w or 12h
+        #[arg(long, value_parser = parse_age)]
+        older_than: Option<chrono::Duration>,
+        /// Show what would be deleted without deleting anything
+        #[arg(long)]
+        dry_run: bool,
     },
     /// Show session statistics
     Stats {
@@ -97,6 +126,24 @@ enum SessionCommands {
         /// Show all sessions
         #[arg(long)]
         all: bool,
+        /// Only include sessions started on or after this day (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<NaiveDate>,
+        /// Only include sessions started on or before this day (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<NaiveDate>,
+        /// Only include sessions run with this profile
+        #[arg(long)]
+        profile: Option<String>,
+        /// Only count usage of this model
+        #[arg(long)]
+        model: Option<String>,
+        /// Total the usage per day, week or month
+        #[arg(long)]
+        by: Option<Period>,
+        /// Output format: table, json or csv
+        #[arg(long, default_value = "table")]
+        format: ReportFormat,
     },
 }
 
@@ -106,6 +153,16 @@ enum ToolkitCommands {
     List,
 }
 
+#[derive(Subcommand)]
+enum McpCommands {
+    /// Serve toolkits as an MCP server over stdin and stdout
+    Serve {
+        /// Toolkit to serve, may be repeated
+        #[arg(long = "toolkit", default_value = "default")]
+        toolkits: Vec<String>,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
@@ -120,57 +177,156 @@ async fn main() -> Result<()> {
 
     match cli.command {
         Some(Commands::Session { command }) => match command {
-            SessionCommands::Start { name, profile, plan: _, log_level: _, tracing: _ } => {
+            SessionCommands::Start { name, profile, plan, args, log_level: _, tracing: _ } => {
+                let plan = match plan {
+                    Some(path) => Some(Plan::load(&path)?.render(&args.into_iter().collect())?),
+                    None => None,
+                };
                 println!("Starting session...");
                 let mut session = rust_goose::cli::session::Session::new(
                     name,
                     profile,
-                    None,
+                    plan,
                     Some("INFO".to_string()),
                     false,
-                ).await.unwrap();
-                session.run(true).await.unwrap();
+                ).await?;
+                session.run(true).await?;
             }
             SessionCommands::List => {
-                println!("Listing sessions...");
-                // TODO: Implement session list
+                let sessions = list_sessions(&rust_goose::cli::config::sessions_dir())?;
+                if sessions.is_empty() {
+                    println!("No sessions found");
+                }
+                for session in sessions {
+                    let model = session.header.as_ref()
+                        .and_then(|header| header.processor.as_deref())
+                        .unwrap_or("-");
+                    println!(
+                        "{}  {}  {} messages  {} tokens  {}  {}",
+                        session.name.cyan(),
+                        session.modified.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M"),
+                        session.message_count,
+                        session.metadata.usage.total(),
+                        session.profile().unwrap_or("-"),
+                        model,
+                    );
+                    if let Some(description) = &session.description {
+                        println!("    {}", rust_goose::utils::snippet(description, 60).dimmed());
+                    }
+                }
             }
             SessionCommands::Resume { name, profile, log_level } => {
-                println!("Resuming session...");
-                let mut session = rust_goose::session::SessionLoop::new(
-                    name.unwrap_or_else(|| rust_goose::utils::generate_name()),
+                let sessions_dir = rust_goose::cli::config::sessions_dir();
+                let name = match name {
+                    Some(name) => {
+                        if !rust_goose::cli::config::session_path(&name).exists() {
+                            anyhow::bail!("No session named {} in {}", name, sessions_dir.display());
+                        }
+                        name
+                    }
+                    None => match latest_session()? {
+                        Some(name) => name,
+                        None => anyhow::bail!("No sessions to resume in {}", sessions_dir.display()),
+                    },
+                };
+                let profile = profile_for_session(&name, profile)?;
+                let mut session = rust_goose::cli::session::Session::new(
+                    Some(name),
                     profile,
-                );
-                session.run(false).await.unwrap();
-            }
-            SessionCommands::Clear { keep: _ } => {
-                println!("Clearing old sessions...");
-                // TODO: Implement session clear
-            }
-            SessionCommands::Stats { name, tokens, cost, all } => {
-                println!("Showing session statistics...");
-                let mut session = rust_goose::session::SessionLoop::new(
-                    name.unwrap_or_else(|| rust_goose::utils::generate_name()),
                     None,
-                );
-                if all {
-                    if let Ok(total_stats) = session.get_total_stats().await {
-                        println!("{}", total_stats.summary());
+                    Some(log_level),
+                    false,
+                ).await?;
+                session.run(false).await?;
+            }
+            SessionCommands::Clear { keep, older_than, dry_run } => {
+                let sessions = list_sessions(&rust_goose::cli::config::sessions_dir())?;
+                let to_clear = sessions_to_clear(sessions, keep as usize, older_than, chrono::Utc::now());
+                if to_clear.is_empty() {
+                    println!("No sessions to clear");
+                }
+                for session in &to_clear {
+                    if dry_run {
+                        println!("Would delete {}", session.name);
+                    } else {
+                        delete_session(session)?;
+                        println!("Deleted {}", session.name);
                     }
+                }
+            }
+            SessionCommands::Stats { name, tokens, cost, all, since, until, profile, model, by, format } => {
+                let tracker = StatsTracker::load(&StatsStore::default())?;
+                let filter = StatsFilter { since, until, profile, model };
+                let rows: Vec<ReportRow> = if let Some(period) = by {
+                    tracker.aggregate(&filter, period).iter().map(ReportRow::from_period).collect()
+                } else if all {
+                    tracker.filter(&filter).iter().map(ReportRow::from_session).collect()
+                } else {
+                    let stats = match &name {
+                        Some(name) => tracker.get_session_stats(name),
+                        None => tracker.latest().and_then(|latest| tracker.get_session_stats(&latest.session_id)),
+                    };
+                    stats.and_then(|stats| filter.apply(&stats)).iter().map(ReportRow::from_session).collect()
+                };
+                if rows.is_empty() && format == ReportFormat::Table {
+                    println!("No stats recorded for {}", name.as_deref().unwrap_or("the selected sessions"));
                 } else {
-                    println!("{}", session.get_stats().summary());
+                    println!("{}", render(&rows, format, ReportColumns::new(tokens, cost))?);
                 }
             }
         },
         Some(Commands::Toolkit { command }) => match command {
             ToolkitCommands::List => {
                 println!("Available toolkits:");
-                // TODO: Implement toolkit list
+                for toolkit in ToolkitRegistry::default().list() {
+                    println!("\n{}  {}", toolkit.name.cyan().bold(), toolkit.description);
+                    if !toolkit.requires.is_empty() {
+                        println!("    requires: {}", toolkit.requires.join(", "));
+                    }
+                    let width = toolkit.tools.iter().map(|tool| tool.name.len()).max().unwrap_or(0);
+                    for tool in toolkit.tools {
+                        println!(
+                            "    {}  {}",
+                            format!("{:<width$}", tool.name, width = width).green(),
+                            rust_goose::utils::snippet(&tool.description, 70).dimmed(),
+                        );
+                    }
+                }
             }
         },
-        Some(Commands::Run { message_file: _, profile: _, log_level: _, resume_session: _, tracing: _ }) => {
-            println!("Running single-pass session...");
-            // TODO: Implement run command
+        Some(Commands::Mcp { command }) => match command {
+            McpCommands::Serve { toolkits } => {
+                let registry = ToolkitRegistry::default();
+                let toolkits = toolkits.iter()
+                    .map(|name| registry.create(name))
+                    .collect::<Result<Vec<_>>>()?;
+                McpServer::new(toolkits).serve(tokio::io::stdin(), tokio::io::stdout()).await?;
+            }
+        },
+        Some(Commands::Run { message_file, profile, log_level, resume_session, tracing, output_format }) => {
+            // Created first so a run that cannot start still reports it in
+            // the requested format
+            let renderer = create_renderer(output_format);
+            let message = match read_run_message(message_file.as_deref()) {
+                Ok(message) => message,
+                Err(e) => exit_before_run(renderer, RunOutcome::InvalidInput, &e, "There is nothing to run."),
+            };
+            let session = async {
+                // Without a session to resume, the run starts a new one
+                let name = if resume_session { latest_session()? } else { None };
+                let profile = match &name {
+                    Some(name) => profile_for_session(name, profile)?,
+                    None => profile,
+                };
+                rust_goose::cli::session::Session::new(name, profile, None, Some(log_level), tracing).await
+            }.await;
+            let mut session = match session {
+                Ok(session) => session,
+                Err(e) => exit_before_run(renderer, RunOutcome::Failed, &e, "Check the profile and try the run again."),
+            };
+            session.set_renderer(renderer);
+            let outcome = session.single_pass(&message).await?;
+            std::process::exit(outcome.exit_code());
         }
         None => {
             println!("{}", <Cli as CommandFactory>::command().render_help());
@@ -180,8 +336,68 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Report a run that failed before its session started, then exit with the
+/// outcome's code
+fn exit_before_run(mut renderer: Box<dyn Renderer>, outcome: RunOutcome, error: &anyhow::Error, hint: &str) -> ! {
+    renderer.render(&OutputEvent::Error {
+        message: format!("{:#}", error),
+        hint: hint.to_string(),
+    });
+    renderer.render(&OutputEvent::Result {
+        session: None,
+        outcome,
+        exit_code: outcome.exit_code(),
+        text: None,
+        tool_calls: 0,
+        usage: Usage::default(),
+        cost: 0.0,
+    });
+    if let Err(e) = renderer.finish() {
+        eprintln!("{}", format!("{:#}", e).red());
+    }
+    std::process::exit(outcome.exit_code());
+}
+
+/// The most recently used session, if there is any
+fn latest_session() -> Result<Option<String>> {
+    let sessions_dir = rust_goose::cli::config::sessions_dir();
+    Ok(list_sorted_session_files(&sessions_dir)?.into_iter().next().map(|(name, _)| name))
+}
+
+/// The profile to continue a session with, the one it was started with
+/// unless another was asked for
+fn profile_for_session(name: &str, profile: Option<String>) -> Result<Option<String>> {
+    match profile {
+        Some(profile) => Ok(Some(profile)),
+        None => Ok(read_header(&rust_goose::cli::config::session_path(name))?.and_then(|header| header.profile)),
+    }
+}
+
+/// Read the message for `run` from the file, or from stdin when there is
+/// none and it is not a terminal
+fn read_run_message(message_file: Option<&std::path::Path>) -> Result<String> {
+    use std::io::{IsTerminal, Read};
+
+    let message = match message_file {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?,
+        None if std::io::stdin().is_terminal() => {
+            anyhow::bail!("No message to run, pass a file or pipe the message on stdin")
+        }
+        None => {
+            let mut message = String::new();
+            std::io::stdin().read_to_string(&mut message)?;
+            message
+        }
+    };
+    if message.trim().is_empty() {
+        anyhow::bail!("The message is empty, there is nothing to run");
+    }
+    Ok(message)
+}
+
 fn print_version() {
     println!("{}: {}", "Rust-goose".green(), env!("CARGO_PKG_VERSION").cyan().bold());
     println!("{}:", "Plugins".green());
     // TODO: Implement plugin version listing
-}
\ No newline at end of file
+}
@@ -0,0 +1,81 @@
+/// How tokens are counted for a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tokenizer {
+    /// The BPE encoding of GPT-4o and later OpenAI models
+    O200kBase,
+    /// The BPE encoding of GPT-4 and GPT-3.5
+    Cl100kBase,
+    /// No public tokenizer, tokens are estimated from the text length
+    Estimate,
+}
+
+/// What we know about a model's limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelInfo {
+    /// Tokens the prompt and response may use together
+    pub context_window: usize,
+    /// Tokens the model may generate in a single response
+    pub max_output_tokens: usize,
+    pub tokenizer: Tokenizer,
+}
+
+impl ModelInfo {
+    const fn new(context_window: usize, max_output_tokens: usize, tokenizer: Tokenizer) -> Self {
+        Self { context_window, max_output_tokens, tokenizer }
+    }
+}
+
+/// Assumed for models missing from the catalog
+pub const DEFAULT_MODEL_INFO: ModelInfo = ModelInfo::new(128_000, 4096, Tokenizer::Estimate);
+
+/// Known models keyed by name prefix, the longest matching prefix wins so
+/// dated snapshots and local model tags resolve to their family
+const MODELS: &[(&str, ModelInfo)] = &[
+    ("gpt-4o", ModelInfo::new(128_000, 16_384, Tokenizer::O200kBase)),
+    ("gpt-4.1", ModelInfo::new(1_047_576, 32_768, Tokenizer::O200kBase)),
+    ("gpt-4-turbo", ModelInfo::new(128_000, 4096, Tokenizer::Cl100kBase)),
+    ("gpt-4-32k", ModelInfo::new(32_768, 4096, Tokenizer::Cl100kBase)),
+    ("gpt-4", ModelInfo::new(8192, 4096, Tokenizer::Cl100kBase)),
+    ("gpt-3.5-turbo", ModelInfo::new(16_385, 4096, Tokenizer::Cl100kBase)),
+    ("o1", ModelInfo::new(200_000, 100_000, Tokenizer::O200kBase)),
+    ("o3", ModelInfo::new(200_000, 100_000, Tokenizer::O200kBase)),
+    ("o4-mini", ModelInfo::new(200_000, 100_000, Tokenizer::O200kBase)),
+    ("claude-3-5", ModelInfo::new(200_000, 8192, Tokenizer::Estimate)),
+    ("claude-3-7", ModelInfo::new(200_000, 64_000, Tokenizer::Estimate)),
+    ("claude-3", ModelInfo::new(200_000, 4096, Tokenizer::Estimate)),
+    ("claude-sonnet-4", ModelInfo::new(200_000, 64_000, Tokenizer::Estimate)),
+    ("claude-opus-4", ModelInfo::new(200_000, 32_000, Tokenizer::Estimate)),
+    ("llama3.1", ModelInfo::new(131_072, 4096, Tokenizer::Estimate)),
+    ("llama3.2", ModelInfo::new(131_072, 4096, Tokenizer::Estimate)),
+    ("llama3", ModelInfo::new(8192, 4096, Tokenizer::Estimate)),
+    ("mistral", ModelInfo::new(32_768, 4096, Tokenizer::Estimate)),
+    ("mixtral", ModelInfo::new(32_768, 4096, Tokenizer::Estimate)),
+    ("qwen2.5", ModelInfo::new(32_768, 8192, Tokenizer::Estimate)),
+    ("gemma2", ModelInfo::new(8192, 4096, Tokenizer::Estimate)),
+];
+
+/// Look up a model, ignoring any routing prefix such as `openai/`
+pub fn model_info(model: &str) -> ModelInfo {
+    let name = model.rsplit('/').next().unwrap_or(model);
+    MODELS.iter()
+        .filter(|(prefix, _)| name.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, info)| *info)
+        .unwrap_or(DEFAULT_MODEL_INFO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_info_lookup() {
+        assert_eq!(model_info("gpt-4o-mini-2024-07-18").tokenizer, Tokenizer::O200kBase);
+        assert_eq!(model_info("gpt-4").context_window, 8192);
+        assert_eq!(model_info("gpt-4-turbo-preview").context_window, 128_000);
+        assert_eq!(model_info("openai/gpt-4o").context_window, 128_000);
+        assert_eq!(model_info("claude-3-5-sonnet-latest").context_window, 200_000);
+        assert_eq!(model_info("llama3.1:70b").context_window, 131_072);
+        assert_eq!(model_info("served-model"), DEFAULT_MODEL_INFO);
+    }
+}
@@ -2,15 +2,23 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use serde::{Serialize, Deserialize};
 use anyhow::Result;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Role {
     User,
     Assistant,
+    System,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A single block of message content, shared by the exchange, providers,
+/// toolkits and session files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Content {
     Text { text: String },
+    /// Base64 encoded image data
+    Image {
+        data: String,
+        mime_type: String,
+    },
     ToolUse { 
         id: String,
         name: String,
@@ -23,7 +31,26 @@ pub enum Content {
     },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl std::fmt::Display for Content {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Content::Text { text } => write!(f, "{}", text),
+            Content::Image { mime_type, .. } => write!(f, "Image: {}", mime_type),
+            Content::ToolUse { name, parameters, .. } => {
+                write!(f, "Tool use: {} with parameters: {}", name, parameters)
+            },
+            Content::ToolResult { output, is_error, .. } => {
+                if *is_error {
+                    write!(f, "Tool error: {}", output)
+                } else {
+                    write!(f, "Tool result: {}", output)
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Message {
     pub role: Role,
     pub id: String,
@@ -60,6 +87,13 @@ impl Message {
         )
     }
 
+    pub fn system(text: &str) -> Self {
+        Self::new(
+            Role::System,
+            vec![Content::Text { text: text.to_string() }],
+        )
+    }
+
     pub fn text(&self) -> String {
         self.content
             .iter()
@@ -81,6 +115,13 @@ impl Message {
             .collect()
     }
 
+    pub fn images(&self) -> Vec<&Content> {
+        self.content
+            .iter()
+            .filter(|content| matches!(content, Content::Image { .. }))
+            .collect()
+    }
+
     pub fn tool_result(&self) -> Vec<&Content> {
         self.content
             .iter()
@@ -96,6 +137,10 @@ impl Message {
         matches!(self.role, Role::Assistant)
     }
 
+    pub fn is_system(&self) -> bool {
+        matches!(self.role, Role::System)
+    }
+
     pub fn has_tool_use(&self) -> bool {
         self.content.iter().any(|c| matches!(c, Content::ToolUse { .. }))
     }
@@ -103,8 +148,8 @@ impl Message {
     pub fn validate(&self) -> Result<()> {
         match self.role {
             Role::User => {
-                if !self.content.iter().any(|c| matches!(c, Content::Text { .. } | Content::ToolResult { .. })) {
-                    anyhow::bail!("User message must include a Text or ToolResult");
+                if !self.content.iter().any(|c| matches!(c, Content::Text { .. } | Content::Image { .. } | Content::ToolResult { .. })) {
+                    anyhow::bail!("User message must include a Text, Image or ToolResult");
                 }
                 if self.content.iter().any(|c| matches!(c, Content::ToolUse { .. })) {
                     anyhow::bail!("User message does not support ToolUse");
@@ -114,8 +159,13 @@ impl Message {
                 if !self.content.iter().any(|c| matches!(c, Content::Text { .. } | Content::ToolUse { .. })) {
                     anyhow::bail!("Assistant message must include a Text or ToolUse");
                 }
-                if self.content.iter().any(|c| matches!(c, Content::ToolResult { .. })) {
-                    anyhow::bail!("Assistant message does not support ToolResult");
+                if self.content.iter().any(|c| matches!(c, Content::ToolResult { .. } | Content::Image { .. })) {
+                    anyhow::bail!("Assistant message does not support ToolResult or Image");
+                }
+            }
+            Role::System => {
+                if !self.content.iter().all(|c| matches!(c, Content::Text { .. })) || self.content.is_empty() {
+                    anyhow::bail!("System message must only include Text");
                 }
             }
         }
@@ -126,8 +176,71 @@ impl Message {
         let role = match self.role {
             Role::User => "user",
             Role::Assistant => "assistant",
+            Role::System => "system",
         };
         
         format!("message:{}\n{}", role, self.text())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_message_serde_round_trip() {
+        let messages = vec![
+            Message::system("You are goose"),
+            Message::new(
+                Role::User,
+                vec![
+                    Content::Text { text: "What is in this image?".to_string() },
+                    Content::Image { data: "aGVsbG8=".to_string(), mime_type: "image/png".to_string() },
+                ],
+            ),
+            Message::new(
+                Role::User,
+                vec![Content::Image { data: "d29ybGQ=".to_string(), mime_type: "image/jpeg".to_string() }],
+            ),
+            Message::new(
+                Role::Assistant,
+                vec![
+                    Content::Text { text: "Let me check".to_string() },
+                    Content::ToolUse {
+                        id: "call_1".to_string(),
+                        name: "bash".to_string(),
+                        parameters: json!({"command": "ls", "nested": {"list": [1, 2.5, null]}}),
+                    },
+                ],
+            ),
+            Message::new(
+                Role::User,
+                vec![Content::ToolResult {
+                    tool_use_id: "call_1".to_string(),
+                    output: "Cargo.toml\nsrc".to_string(),
+                    is_error: true,
+                }],
+            ),
+        ];
+
+        for message in messages {
+            let line = serde_json::to_string(&message).unwrap();
+            let parsed: Message = serde_json::from_str(&line).unwrap();
+            assert_eq!(parsed, message);
+            assert!(parsed.validate().is_ok(), "{:?} should be valid", parsed);
+        }
+    }
+
+    #[test]
+    fn test_validate_roles() {
+        assert!(Message::system("rules").validate().is_ok());
+        assert!(Message::new(Role::System, vec![]).validate().is_err());
+
+        let image = Content::Image { data: String::new(), mime_type: "image/png".to_string() };
+        assert!(Message::new(Role::User, vec![Content::Text { text: "look".to_string() }, image.clone()]).validate().is_ok());
+        assert!(Message::new(Role::User, vec![image.clone()]).validate().is_ok());
+        assert!(Message::new(Role::User, vec![]).validate().is_err());
+        assert!(Message::new(Role::Assistant, vec![Content::Text { text: "look".to_string() }, image]).validate().is_err());
+    }
+}
@@ -1,5 +1,11 @@
+pub mod catalog;
 pub mod message;
+pub mod plan;
+pub mod pricing;
 pub mod profile;
+pub mod usage;
 
 pub use message::Message;
-pub use profile::Profile;
\ No newline at end of file
+pub use plan::Plan;
+pub use profile::{EndpointConfig, McpHttpTransport, McpServerConfig, Profile, RetryConfig};
+pub use usage::Usage;
\ No newline at end of file
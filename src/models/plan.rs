@@ -0,0 +1,186 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use anyhow::{anyhow, Context, Result};
+use log::warn;
+use serde::{Serialize, Deserialize};
+
+/// A reusable runbook that seeds a new session with its first message
+///
+/// The kickoff message, task descriptions and checks may refer to
+/// arguments as `{{ name }}`, which are filled in by [`Plan::render`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    pub kickoff: String,
+    #[serde(default)]
+    pub tasks: Vec<Task>,
+}
+
+/// A step of a plan, written in the plan file either as just its
+/// description or with the checks that tell when it is done
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "TaskSpec")]
+pub struct Task {
+    pub description: String,
+    /// Acceptance checks the agent confirms before completing the task
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checks: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TaskSpec {
+    Description(String),
+    Full {
+        description: String,
+        #[serde(default)]
+        checks: Vec<String>,
+    },
+}
+
+impl From<TaskSpec> for Task {
+    fn from(spec: TaskSpec) -> Self {
+        match spec {
+            TaskSpec::Description(description) => Task { description, checks: Vec::new() },
+            TaskSpec::Full { description, checks } => Task { description, checks },
+        }
+    }
+}
+
+impl Plan {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read plan from {}", path.display()))?;
+        serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse plan in {}", path.display()))
+    }
+
+    /// Fill in the arguments the plan refers to, every one of them has to
+    /// be given
+    pub fn render(&self, args: &HashMap<String, String>) -> Result<Plan> {
+        let mut missing = BTreeSet::new();
+        let mut used = BTreeSet::new();
+        let mut fill = |text: &str| substitute(text, args, &mut missing, &mut used);
+
+        let plan = Plan {
+            kickoff: fill(&self.kickoff),
+            tasks: self.tasks.iter()
+                .map(|task| Task {
+                    description: fill(&task.description),
+                    checks: task.checks.iter().map(|check| fill(check)).collect(),
+                })
+                .collect(),
+        };
+        if !missing.is_empty() {
+            let missing: Vec<_> = missing.into_iter().collect();
+            return Err(anyhow!("The plan needs arguments that were not given: {}", missing.join(", ")));
+        }
+        for name in args.keys().filter(|name| !used.contains(name.as_str())) {
+            warn!("The plan does not use the argument {}", name);
+        }
+        Ok(plan)
+    }
+
+    /// The first message of a session following the plan
+    pub fn kickoff_message(&self) -> String {
+        let mut message = self.kickoff.trim().to_string();
+        if self.tasks.is_empty() {
+            return message;
+        }
+
+        message.push_str("\n\nWork through these tasks in order:\n");
+        for (i, task) in self.tasks.iter().enumerate() {
+            message.push_str(&format!("{}. {}\n", i + 1, task.description));
+            for check in &task.checks {
+                message.push_str(&format!("   - Done when: {}\n", check));
+            }
+        }
+        message.push_str("\nOnce a task is done and its checks pass, call complete_task with its number.");
+        message
+    }
+}
+
+/// Replace each `{{ name }}` in the text with its argument
+fn substitute(
+    text: &str,
+    args: &HashMap<String, String>,
+    missing: &mut BTreeSet<String>,
+    used: &mut BTreeSet<String>,
+) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + end].trim();
+        result.push_str(&rest[..start]);
+        match args.get(name) {
+            Some(value) => {
+                result.push_str(value);
+                used.insert(name.to_string());
+            }
+            None => {
+                missing.insert(name.to_string());
+            }
+        }
+        rest = &rest[start + end + 2..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Parse a `key=value` plan argument
+pub fn parse_plan_arg(arg: &str) -> Result<(String, String)> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.to_string())),
+        _ => Err(anyhow!("Invalid plan argument: {}, expected key=value", arg)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAN: &str = r#"
+kickoff: Upgrade {{ crate }} in this repository
+tasks:
+  - Find where {{crate}} is used
+  - description: Bump the version to {{ version }}
+    checks:
+      - cargo test passes
+"#;
+
+    #[test]
+    fn test_parse_and_render_plan() {
+        let plan: Plan = serde_yaml::from_str(PLAN).unwrap();
+        assert_eq!(plan.tasks[0].checks, Vec::<String>::new());
+        assert_eq!(plan.tasks[1].checks, vec!["cargo test passes"]);
+
+        let args = HashMap::from([
+            ("crate".to_string(), "serde".to_string()),
+            ("version".to_string(), "1.0.200".to_string()),
+        ]);
+        let plan = plan.render(&args).unwrap();
+        assert_eq!(plan.kickoff, "Upgrade serde in this repository");
+        assert_eq!(plan.tasks[0].description, "Find where serde is used");
+
+        let message = plan.kickoff_message();
+        assert!(message.starts_with("Upgrade serde in this repository\n\nWork through these tasks in order:\n"));
+        assert!(message.contains("2. Bump the version to 1.0.200\n   - Done when: cargo test passes\n"));
+    }
+
+    #[test]
+    fn test_missing_arguments_are_an_error() {
+        let plan: Plan = serde_yaml::from_str(PLAN).unwrap();
+        let args = HashMap::from([("crate".to_string(), "serde".to_string())]);
+        let error = plan.render(&args).unwrap_err().to_string();
+        assert!(error.ends_with("not given: version"), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_plan_arg() {
+        assert_eq!(parse_plan_arg("name=a=b").unwrap(), ("name".to_string(), "a=b".to_string()));
+        assert!(parse_plan_arg("name").is_err());
+        assert!(parse_plan_arg("=value").is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use crate::models::Usage;
+
+/// Prices in US dollars per million tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pricing {
+    pub input: f64,
+    pub output: f64,
+    pub cache_read: f64,
+    pub cache_write: f64,
+}
+
+impl Pricing {
+    const fn new(input: f64, output: f64, cache_read: f64, cache_write: f64) -> Self {
+        Self { input, output, cache_read, cache_write }
+    }
+
+    /// Local models cost nothing per token
+    pub const FREE: Pricing = Pricing::new(0.0, 0.0, 0.0, 0.0);
+
+    /// The cost of the usage in US dollars
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        (usage.input_tokens as f64 * self.input
+            + usage.output_tokens as f64 * self.output
+            + usage.cache_read_tokens as f64 * self.cache_read
+            + usage.cache_write_tokens as f64 * self.cache_write)
+            / 1_000_000.0
+    }
+}
+
+/// Published prices keyed by provider and model name prefix, the longest
+/// matching prefix wins so dated snapshots resolve to their family
+const PRICES: &[(&str, &str, Pricing)] = &[
+    ("openai", "gpt-4o", Pricing::new(2.50, 10.00, 1.25, 0.0)),
+    ("openai", "gpt-4o-mini", Pricing::new(0.15, 0.60, 0.075, 0.0)),
+    ("openai", "gpt-4.1", Pricing::new(2.00, 8.00, 0.50, 0.0)),
+    ("openai", "gpt-4.1-mini", Pricing::new(0.40, 1.60, 0.10, 0.0)),
+    ("openai", "gpt-4.1-nano", Pricing::new(0.10, 0.40, 0.025, 0.0)),
+    ("openai", "gpt-4-turbo", Pricing::new(10.00, 30.00, 0.0, 0.0)),
+    ("openai", "gpt-4", Pricing::new(30.00, 60.00, 0.0, 0.0)),
+    ("openai", "gpt-3.5-turbo", Pricing::new(0.50, 1.50, 0.0, 0.0)),
+    ("openai", "o1", Pricing::new(15.00, 60.00, 7.50, 0.0)),
+    ("openai", "o1-mini", Pricing::new(1.10, 4.40, 0.55, 0.0)),
+    ("openai", "o3", Pricing::new(2.00, 8.00, 0.50, 0.0)),
+    ("openai", "o3-mini", Pricing::new(1.10, 4.40, 0.55, 0.0)),
+    ("openai", "o4-mini", Pricing::new(1.10, 4.40, 0.275, 0.0)),
+    ("anthropic", "claude-3-5-sonnet", Pricing::new(3.00, 15.00, 0.30, 3.75)),
+    ("anthropic", "claude-3-7-sonnet", Pricing::new(3.00, 15.00, 0.30, 3.75)),
+    ("anthropic", "claude-sonnet-4", Pricing::new(3.00, 15.00, 0.30, 3.75)),
+    ("anthropic", "claude-3-5-haiku", Pricing::new(0.80, 4.00, 0.08, 1.00)),
+    ("anthropic", "claude-3-haiku", Pricing::new(0.25, 1.25, 0.03, 0.30)),
+    ("anthropic", "claude-3-opus", Pricing::new(15.00, 75.00, 1.50, 18.75)),
+    ("anthropic", "claude-opus-4", Pricing::new(15.00, 75.00, 1.50, 18.75)),
+];
+
+/// Look up the price of a model, ignoring any routing prefix such as
+/// `openai/`, `None` when it is not known
+pub fn pricing(provider: &str, model: &str) -> Option<Pricing> {
+    if provider == "ollama" {
+        return Some(Pricing::FREE);
+    }
+
+    let name = model.rsplit('/').next().unwrap_or(model);
+    // Providers without prices of their own, such as OpenAI compatible
+    // endpoints, are priced by whichever catalog knows the model
+    let has_prices = PRICES.iter().any(|(price_provider, _, _)| *price_provider == provider);
+    PRICES.iter()
+        .filter(|(price_provider, prefix, _)| {
+            (!has_prices || *price_provider == provider) && name.starts_with(prefix)
+        })
+        .max_by_key(|(_, prefix, _)| prefix.len())
+        .map(|(_, _, pricing)| *pricing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pricing_lookup() {
+        assert_eq!(pricing("openai", "gpt-4o-mini-2024-07-18"), pricing("openai", "gpt-4o-mini"));
+        assert_ne!(pricing("openai", "gpt-4o-mini"), pricing("openai", "gpt-4o"));
+        assert_eq!(pricing("anthropic", "claude-3-5-sonnet-latest").unwrap().cache_write, 3.75);
+        assert_eq!(pricing("ollama", "llama3.1"), Some(Pricing::FREE));
+        assert_eq!(pricing("groq", "llama-3.1-70b"), None);
+        // A provider with its own catalog only matches its own models
+        assert_eq!(pricing("anthropic", "gpt-4o"), None);
+        // Named endpoints fall back to the model name
+        assert_eq!(pricing("work-proxy", "gpt-4o-2024-08-06"), pricing("openai", "gpt-4o"));
+        assert_eq!(pricing("work-proxy", "claude-3-5-haiku-latest"), pricing("anthropic", "claude-3-5-haiku"));
+        // Routers such as OpenRouter prefix the model with its provider
+        assert_eq!(pricing("openrouter", "openai/gpt-4o"), pricing("openai", "gpt-4o"));
+        assert_eq!(pricing("openrouter", "anthropic/claude-3-5-sonnet"), pricing("anthropic", "claude-3-5-sonnet"));
+        assert_eq!(pricing("openrouter", "meta-llama/llama-3.1-70b"), None);
+    }
+
+    #[test]
+    fn test_cost() {
+        let usage = Usage {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            cache_read_tokens: 2_000_000,
+            cache_write_tokens: 0,
+        };
+        let cost = pricing("openai", "gpt-4o").unwrap().cost(&usage);
+        assert!((cost - (2.50 + 1.00 + 2.50)).abs() < 1e-9);
+    }
+}
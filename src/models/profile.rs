@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use serde::{Serialize, Deserialize};
 use anyhow::Result;
 
@@ -9,6 +9,104 @@ pub struct ToolkitSpec {
     pub requires: HashMap<String, String>,
 }
 
+/// Connection settings for an OpenAI compatible endpoint, such as Azure
+/// OpenAI, vLLM, LiteLLM, OpenRouter or Groq
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EndpointConfig {
+    /// Base URL of the API, defaults to https://api.openai.com/v1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Environment variable holding the API key, no key is sent if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+    /// Header carrying the API key instead of `Authorization: Bearer` (Azure uses `api-key`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_header: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    /// Sent as the `api-version` query parameter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// Provider whose prices apply, e.g. `openai` for an Azure deployment,
+    /// by default the model name alone picks the price
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<String>,
+}
+
+/// How failed provider requests are retried
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Retries after the first attempt, 0 disables retrying
+    pub max_retries: u32,
+    /// Delay before the first retry
+    pub initial_delay_ms: u64,
+    /// Upper bound for the backoff delay, a request is not retried when the
+    /// provider asks to wait longer
+    pub max_delay_ms: u64,
+    pub backoff_multiplier: f64,
+}
+
+impl RetryConfig {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_delay_ms: 1000,
+            max_delay_ms: 60_000,
+            backoff_multiplier: 2.0,
+        }
+    }
+}
+
+/// How goose reaches a server on the older HTTP transport of MCP
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum McpHttpTransport {
+    /// Requests are POSTed and answered in the response, the current transport
+    #[default]
+    StreamableHttp,
+    /// Responses arrive on a server-sent events stream, the 2024-11-05 transport
+    Sse,
+}
+
+/// A Model Context Protocol server whose tools the session can use, either
+/// launched as a program or reached at a URL
+///
+/// Values in `env` and `headers` may refer to environment variables as
+/// `$NAME` or `${NAME}`, to keep secrets out of the profile.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct McpServerConfig {
+    /// Program speaking MCP over its stdin and stdout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Set for the program in addition to goose's own environment
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub transport: McpHttpTransport,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// How long to wait for the server to answer a request
+    #[serde(default = "default_mcp_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_mcp_timeout_secs() -> u64 {
+    300
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Profile {
     pub provider: String,
@@ -16,6 +114,18 @@ pub struct Profile {
     pub accelerator: String,
     pub moderator: String,
     pub toolkits: Vec<ToolkitSpec>,
+    /// Named OpenAI compatible endpoints that `provider` may refer to
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub endpoints: HashMap<String, EndpointConfig>,
+    #[serde(default, skip_serializing_if = "RetryConfig::is_default")]
+    pub retry: RetryConfig,
+    /// MCP servers whose tools are added to the session, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub mcp_servers: BTreeMap<String, McpServerConfig>,
+    /// Rounds of tool calls a single reply may run, the exchange's default
+    /// when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tool_rounds: Option<usize>,
 }
 
 impl Profile {
@@ -32,18 +142,43 @@ impl Profile {
             accelerator,
             moderator,
             toolkits,
+            endpoints: HashMap::new(),
+            retry: RetryConfig::default(),
+            mcp_servers: BTreeMap::new(),
+            max_tool_rounds: None,
         }
     }
 
+    /// The provider used to look up prices, an endpoint's pricing key when
+    /// it has one
+    pub fn pricing_provider(&self) -> &str {
+        self.endpoints.get(&self.provider)
+            .and_then(|endpoint| endpoint.pricing.as_deref())
+            .unwrap_or(&self.provider)
+    }
+
     pub fn validate(&self) -> Result<()> {
-        let installed_toolkits: std::collections::HashSet<_> = self.toolkits
-            .iter()
-            .map(|t| t.name.as_str())
-            .collect();
+        for (name, server) in &self.mcp_servers {
+            if server.command.is_some() == server.url.is_some() {
+                anyhow::bail!("MCP server {} needs either a command or a url", name);
+            }
+        }
+        self.toolkit_order().map(|_| ())
+    }
+
+    /// The profile's toolkits ordered so each one comes after the toolkits
+    /// it requires, failing if a requirement is missing or they form a cycle
+    pub fn toolkit_order(&self) -> Result<Vec<&ToolkitSpec>> {
+        let mut by_name = HashMap::new();
+        for toolkit in &self.toolkits {
+            if by_name.insert(toolkit.name.as_str(), toolkit).is_some() {
+                anyhow::bail!("Toolkit {} is listed more than once", toolkit.name);
+            }
+        }
 
         for toolkit in &self.toolkits {
-            for (_, req) in &toolkit.requires {
-                if !installed_toolkits.contains(req.as_str()) {
+            for req in toolkit.requires.values() {
+                if !by_name.contains_key(req.as_str()) {
                     anyhow::bail!(
                         "Toolkit {} requires {} but it is not present",
                         toolkit.name,
@@ -52,7 +187,14 @@ impl Profile {
                 }
             }
         }
-        Ok(())
+
+        let mut order = Vec::new();
+        let mut done = HashSet::new();
+        let mut path = Vec::new();
+        for toolkit in &self.toolkits {
+            visit_toolkit(toolkit, &by_name, &mut done, &mut path, &mut order)?;
+        }
+        Ok(order)
     }
 
     pub fn profile_info(&self) -> String {
@@ -68,6 +210,38 @@ impl Profile {
     }
 }
 
+/// Add the toolkit to `order` after the toolkits it requires, `path` holds
+/// the toolkits being visited to catch a requirement cycle
+fn visit_toolkit<'a>(
+    toolkit: &'a ToolkitSpec,
+    by_name: &HashMap<&str, &'a ToolkitSpec>,
+    done: &mut HashSet<&'a str>,
+    path: &mut Vec<&'a str>,
+    order: &mut Vec<&'a ToolkitSpec>,
+) -> Result<()> {
+    let name = toolkit.name.as_str();
+    if done.contains(name) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|visiting| *visiting == name) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(name);
+        anyhow::bail!("Toolkit requirements form a cycle: {}", cycle.join(" -> "));
+    }
+
+    path.push(name);
+    let mut requires: Vec<&String> = toolkit.requires.values().collect();
+    requires.sort();
+    for req in requires {
+        visit_toolkit(by_name[req.as_str()], by_name, done, path, order)?;
+    }
+    path.pop();
+
+    done.insert(name);
+    order.push(toolkit);
+    Ok(())
+}
+
 pub fn default_profile(
     provider: String,
     processor: String,
@@ -79,8 +253,105 @@ pub fn default_profile(
         accelerator,
         "synopsis".to_string(),
         vec![ToolkitSpec {
-            name: "synopsis".to_string(),
+            name: "default".to_string(),
             requires: HashMap::new(),
         }],
     )
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_requires() {
+        let mut profile: Profile = serde_yaml::from_str(r#"
+provider: openai
+processor: gpt-4o
+accelerator: gpt-4o-mini
+moderator: passive
+toolkits:
+  - name: default
+  - name: github
+    requires:
+      shell: default
+"#).unwrap();
+        assert!(profile.validate().is_ok());
+
+        profile.toolkits.remove(0);
+        let error = profile.validate().unwrap_err().to_string();
+        assert_eq!(error, "Toolkit github requires default but it is not present");
+    }
+
+    #[test]
+    fn test_mcp_servers() {
+        let mut profile: Profile = serde_yaml::from_str(r#"
+provider: openai
+processor: gpt-4o
+accelerator: gpt-4o-mini
+moderator: passive
+toolkits: []
+mcp_servers:
+  files:
+    command: npx
+    args: [-y, "@modelcontextprotocol/server-filesystem", /tmp]
+  search:
+    url: https://search.example.com/sse
+    transport: sse
+    headers:
+      Authorization: Bearer ${SEARCH_TOKEN}
+"#).unwrap();
+        assert!(profile.validate().is_ok());
+        assert_eq!(profile.mcp_servers["files"].args.len(), 3);
+        assert_eq!(profile.mcp_servers["files"].transport, McpHttpTransport::StreamableHttp);
+        assert_eq!(profile.mcp_servers["search"].transport, McpHttpTransport::Sse);
+        assert_eq!(profile.mcp_servers["search"].timeout_secs, 300);
+
+        profile.mcp_servers.get_mut("search").unwrap().command = Some("search".to_string());
+        let error = profile.validate().unwrap_err().to_string();
+        assert_eq!(error, "MCP server search needs either a command or a url");
+    }
+
+    #[test]
+    fn test_pricing_provider() {
+        let mut profile: Profile = serde_yaml::from_str(r#"
+provider: azure
+processor: gpt4
+accelerator: gpt4
+moderator: passive
+toolkits: []
+endpoints:
+  azure:
+    base_url: https://goose.openai.azure.com/openai/deployments/gpt4
+    pricing: openai
+"#).unwrap();
+        assert_eq!(profile.pricing_provider(), "openai");
+
+        profile.endpoints.get_mut("azure").unwrap().pricing = None;
+        assert_eq!(profile.pricing_provider(), "azure");
+    }
+
+    #[test]
+    fn test_toolkit_order() {
+        let mut profile: Profile = serde_yaml::from_str(r#"
+provider: openai
+processor: gpt-4o
+accelerator: gpt-4o-mini
+moderator: passive
+toolkits:
+  - name: github
+    requires:
+      shell: default
+      tracker: jira
+  - name: jira
+    requires:
+      shell: default
+  - name: default
+"#).unwrap();
+        let order: Vec<_> = profile.toolkit_order().unwrap().iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(order, vec!["default", "jira", "github"]);
+
+        profile.toolkits[2].requires.insert("issues".to_string(), "github".to_string());
+        let error = profile.validate().unwrap_err().to_string();
+        assert_eq!(error, "Toolkit requirements form a cycle: github -> default -> github");
+    }
+}
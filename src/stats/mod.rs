@@ -1,26 +1,67 @@
+pub mod report;
+pub mod store;
+
+use std::collections::BTreeMap;
 use std::time::Duration;
-use chrono::{DateTime, Utc};
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Days, NaiveDate, Utc};
 use serde::{Serialize, Deserialize};
 
+use crate::models::pricing::pricing;
+use crate::models::Usage;
+
+pub use store::StatsStore;
+
+/// Token usage and cost attributed to a single model
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelStats {
+    pub provider: String,
+    #[serde(flatten)]
+    pub usage: Usage,
+    pub cost: f64,
+    /// The model has no known price, so its usage is not part of the cost
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unpriced: bool,
+}
+
+impl ModelStats {
+    fn merge(&mut self, other: &ModelStats) {
+        self.usage += other.usage;
+        self.cost += other.cost;
+        self.unpriced |= other.unpriced;
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionStats {
     pub session_id: String,
+    /// The profile the session ran with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
     pub start_time: DateTime<Utc>,
     pub end_time: Option<DateTime<Utc>>,
     pub total_messages: u32,
+    #[serde(default)]
+    pub tool_calls: u32,
     pub total_tokens: u32,
     pub total_cost: f64,
+    /// Usage per model, the totals above also include it
+    #[serde(default)]
+    pub models: BTreeMap<String, ModelStats>,
 }
 
 impl SessionStats {
     pub fn new(session_id: String) -> Self {
         Self {
             session_id,
+            profile: None,
             start_time: Utc::now(),
             end_time: None,
             total_messages: 0,
+            tool_calls: 0,
             total_tokens: 0,
             total_cost: 0.0,
+            models: BTreeMap::new(),
         }
     }
 
@@ -39,15 +80,49 @@ impl SessionStats {
         self.total_messages += 1;
     }
 
+    pub fn add_tool_calls(&mut self, tool_calls: u32) {
+        self.tool_calls += tool_calls;
+    }
+
+    /// The usage of all models combined
+    pub fn usage(&self) -> Usage {
+        self.models.values().fold(Usage::default(), |total, model| total + model.usage)
+    }
+
+    /// Add tokens that cannot be attributed to a model, they carry no cost
     pub fn add_tokens(&mut self, tokens: u32) {
         self.total_tokens += tokens;
-        // Update cost based on token usage
-        // TODO: Implement proper cost calculation based on model
-        self.total_cost += (tokens as f64) * 0.0001;
+    }
+
+    /// Add usage of a model, priced from the catalog and counted in the
+    /// totals too
+    pub fn add_usage(&mut self, provider: &str, model: &str, usage: &Usage) {
+        let price = pricing(provider, model);
+        let stats = ModelStats {
+            provider: provider.to_string(),
+            usage: *usage,
+            cost: price.map(|price| price.cost(usage)).unwrap_or(0.0),
+            unpriced: price.is_none(),
+        };
+
+        self.total_tokens += usage.total();
+        self.total_cost += stats.cost;
+        self.merge_model(model, &stats);
+    }
+
+    fn merge_model(&mut self, model: &str, stats: &ModelStats) {
+        self.models.entry(model.to_string())
+            .or_insert_with(|| ModelStats { provider: stats.provider.clone(), ..Default::default() })
+            .merge(stats);
+    }
+
+    /// Whether the cost leaves out models without a known price
+    pub fn has_unpriced_usage(&self) -> bool {
+        self.models.values().any(|model| model.unpriced)
     }
 
     pub fn summary(&self) -> String {
-        format!(
+        let mut summary = format!(
             "Session {} stats:\n\
              Duration: {:?}\n\
              Messages: {}\n\
@@ -58,10 +133,125 @@ impl SessionStats {
             self.total_messages,
             self.total_tokens,
             self.total_cost
-        )
+        );
+        if self.has_unpriced_usage() {
+            summary.push_str(" (excludes unpriced models)");
+        }
+        for (model, stats) in &self.models {
+            let cost = if stats.unpriced {
+                "unpriced".to_string()
+            } else {
+                format!("${:.4}", stats.cost)
+            };
+            summary.push_str(&format!(
+                "\n  {} ({}): {} input, {} output, {} cached tokens, {}",
+                model,
+                stats.provider,
+                stats.usage.input_tokens,
+                stats.usage.output_tokens,
+                stats.usage.cache_read_tokens + stats.usage.cache_write_tokens,
+                cost
+            ));
+        }
+        summary
+    }
+
+    /// The part of these stats that comes from `model`, `None` if the
+    /// session did not use it
+    pub fn for_model(&self, model: &str) -> Option<SessionStats> {
+        let model_stats = self.models.get(model)?;
+        Some(SessionStats {
+            total_tokens: model_stats.usage.total(),
+            total_cost: model_stats.cost,
+            models: BTreeMap::from([(model.to_string(), model_stats.clone())]),
+            ..self.clone()
+        })
+    }
+
+    fn merge(&mut self, other: &SessionStats) {
+        self.total_messages += other.total_messages;
+        self.tool_calls += other.tool_calls;
+        self.total_tokens += other.total_tokens;
+        self.total_cost += other.total_cost;
+        for (model, model_stats) in &other.models {
+            self.merge_model(model, model_stats);
+        }
+    }
+}
+
+/// Selects stored sessions by when they started, their profile and the
+/// models they used
+#[derive(Debug, Clone, Default)]
+pub struct StatsFilter {
+    /// First day to include
+    pub since: Option<NaiveDate>,
+    /// Last day to include
+    pub until: Option<NaiveDate>,
+    pub profile: Option<String>,
+    /// Only count usage of this model
+    pub model: Option<String>,
+}
+
+impl StatsFilter {
+    /// The stats to report for a session, `None` if it is filtered out
+    pub fn apply(&self, stats: &SessionStats) -> Option<SessionStats> {
+        let day = stats.start_time.date_naive();
+        if self.since.is_some_and(|since| day < since) || self.until.is_some_and(|until| day > until) {
+            return None;
+        }
+        if self.profile.is_some() && stats.profile != self.profile {
+            return None;
+        }
+        match &self.model {
+            Some(model) => stats.for_model(model),
+            None => Some(stats.clone()),
+        }
+    }
+}
+
+/// The length of the periods usage is aggregated over
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    /// Weeks start on Monday
+    Week,
+    Month,
+}
+
+impl Period {
+    /// The first day of the period containing `day`
+    pub fn start(&self, day: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => day,
+            Period::Week => day - Days::new(day.weekday().num_days_from_monday() as u64),
+            Period::Month => day.with_day(1).unwrap(),
+        }
+    }
+}
+
+impl std::str::FromStr for Period {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "day" => Ok(Period::Day),
+            "week" => Ok(Period::Week),
+            "month" => Ok(Period::Month),
+            _ => Err(anyhow::anyhow!("Unknown period: {}, expected day, week or month", s)),
+        }
     }
 }
 
+/// Usage summed over the sessions that started within a period
+#[derive(Debug, Clone)]
+pub struct PeriodStats {
+    pub start: NaiveDate,
+    pub sessions: u32,
+    /// The sessions' durations added up
+    pub duration: Duration,
+    pub stats: SessionStats,
+}
+
 #[derive(Default)]
 pub struct StatsTracker {
     stats: Vec<SessionStats>,
@@ -72,23 +262,71 @@ impl StatsTracker {
         Self::default()
     }
 
+    /// Track every session recorded in the store
+    pub fn load(store: &StatsStore) -> Result<Self> {
+        Ok(Self { stats: store.load()? })
+    }
+
+    pub fn sessions(&self) -> &[SessionStats] {
+        &self.stats
+    }
+
+    /// The most recently started session
+    pub fn latest(&self) -> Option<&SessionStats> {
+        self.stats.iter().max_by_key(|s| s.start_time)
+    }
+
     pub fn track_session(&mut self, stats: SessionStats) {
         self.stats.push(stats);
     }
 
-    pub fn get_session_stats(&self, session_id: &str) -> Option<&SessionStats> {
-        self.stats.iter().find(|s| s.session_id == session_id)
+    /// The stats of a session across all of its runs, a resumed session has
+    /// a record for every run
+    pub fn get_session_stats(&self, session_id: &str) -> Option<SessionStats> {
+        let mut runs = self.stats.iter().filter(|s| s.session_id == session_id);
+        let mut total = runs.next()?.clone();
+        for run in runs {
+            total.start_time = total.start_time.min(run.start_time);
+            total.end_time = total.end_time.max(run.end_time);
+            total.merge(run);
+        }
+        Some(total)
     }
 
     pub fn get_total_stats(&self) -> SessionStats {
+        self.get_filtered_stats(&StatsFilter::default())
+    }
+
+    /// The sessions that pass the filter, narrowed to its model if it has one
+    pub fn filter(&self, filter: &StatsFilter) -> Vec<SessionStats> {
+        self.stats.iter().filter_map(|stats| filter.apply(stats)).collect()
+    }
+
+    pub fn get_filtered_stats(&self, filter: &StatsFilter) -> SessionStats {
         let mut total = SessionStats::new("total".to_string());
-        for stats in &self.stats {
-            total.total_messages += stats.total_messages;
-            total.total_tokens += stats.total_tokens;
-            total.total_cost += stats.total_cost;
+        for stats in self.filter(filter) {
+            total.merge(&stats);
         }
         total
     }
+
+    /// Totals for each period that has sessions passing the filter, oldest first
+    pub fn aggregate(&self, filter: &StatsFilter, period: Period) -> Vec<PeriodStats> {
+        let mut periods: BTreeMap<NaiveDate, PeriodStats> = BTreeMap::new();
+        for stats in self.filter(filter) {
+            let start = period.start(stats.start_time.date_naive());
+            let entry = periods.entry(start).or_insert_with(|| PeriodStats {
+                start,
+                sessions: 0,
+                duration: Duration::ZERO,
+                stats: SessionStats::new(start.to_string()),
+            });
+            entry.sessions += 1;
+            entry.duration += stats.duration();
+            entry.stats.merge(&stats);
+        }
+        periods.into_values().collect()
+    }
 }
 
 #[cfg(test)]
@@ -134,4 +372,126 @@ mod tests {
         let total = tracker.get_total_stats();
         assert_eq!(total.total_tokens, 300);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_session_stats_across_runs() {
+        let mut tracker = StatsTracker::new();
+        let mut first = stats_on("2026-10-05", "default", "gpt-4o", 100);
+        first.session_id = "resumed".to_string();
+        let mut second = stats_on("2026-10-06", "default", "gpt-4o", 200);
+        second.session_id = "resumed".to_string();
+        second.add_usage("openai", "gpt-4o-mini", &Usage::new(0, 50));
+        tracker.track_session(first.clone());
+        tracker.track_session(stats_on("2026-10-06", "default", "gpt-4o", 400));
+        tracker.track_session(second.clone());
+
+        let stats = tracker.get_session_stats("resumed").unwrap();
+        assert_eq!(stats.total_tokens, 350);
+        assert!((stats.total_cost - (first.total_cost + second.total_cost)).abs() < 1e-12);
+        assert_eq!(stats.models["gpt-4o"].usage.input_tokens, 300);
+        assert_eq!(stats.models["gpt-4o-mini"].usage.output_tokens, 50);
+        assert_eq!(stats.start_time, first.start_time);
+        assert!(tracker.get_session_stats("missing").is_none());
+    }
+
+    #[test]
+    fn test_model_stats() {
+        let mut stats = SessionStats::new("test".to_string());
+        stats.add_usage("openai", "gpt-4o", &Usage::new(1_000_000, 0));
+        stats.add_usage("openai", "gpt-4o-mini", &Usage::new(0, 1_000_000));
+        stats.add_usage("openai", "gpt-4o", &Usage {
+            cache_read_tokens: 1_000_000,
+            ..Usage::new(0, 100_000)
+        });
+
+        assert_eq!(stats.total_tokens, 3_100_000);
+        assert_eq!(stats.models["gpt-4o"].usage.output_tokens, 100_000);
+        assert!((stats.models["gpt-4o"].cost - (2.50 + 1.25 + 1.00)).abs() < 1e-9);
+        assert!((stats.models["gpt-4o-mini"].cost - 0.60).abs() < 1e-9);
+        assert!((stats.total_cost - 5.35).abs() < 1e-9);
+        assert!(!stats.has_unpriced_usage());
+    }
+
+    #[test]
+    fn test_unpriced_model() {
+        let mut stats = SessionStats::new("test".to_string());
+        stats.add_usage("groq", "llama-3.1-70b", &Usage::new(1000, 200));
+        stats.add_usage("ollama", "llama3.1", &Usage::new(1000, 200));
+
+        assert_eq!(stats.total_tokens, 2400);
+        assert_eq!(stats.total_cost, 0.0);
+        assert!(stats.models["llama-3.1-70b"].unpriced);
+        assert!(!stats.models["llama3.1"].unpriced);
+        assert!(stats.summary().contains("llama-3.1-70b (groq): 1000 input, 200 output, 0 cached tokens, unpriced"));
+    }
+
+    #[test]
+    fn test_model_stats_serialization() {
+        let mut stats = SessionStats::new("test".to_string());
+        stats.add_usage("anthropic", "claude-3-5-haiku-latest", &Usage::new(10, 5));
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["models"]["claude-3-5-haiku-latest"]["input_tokens"], 10);
+        let parsed: SessionStats = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.models, stats.models);
+    }
+
+    fn stats_on(day: &str, profile: &str, model: &str, tokens: u32) -> SessionStats {
+        let mut stats = SessionStats::new(format!("{}-{}", day, model));
+        stats.profile = Some(profile.to_string());
+        stats.start_time = format!("{}T12:00:00Z", day).parse().unwrap();
+        stats.add_usage("openai", model, &Usage::new(tokens, 0));
+        stats
+    }
+
+    fn tracker() -> StatsTracker {
+        let mut tracker = StatsTracker::new();
+        // 2026-10-05 is a Monday
+        tracker.track_session(stats_on("2026-09-30", "default", "gpt-4o", 100));
+        tracker.track_session(stats_on("2026-10-05", "default", "gpt-4o", 200));
+        tracker.track_session(stats_on("2026-10-07", "work", "gpt-4o-mini", 400));
+        tracker.track_session(stats_on("2026-10-12", "work", "gpt-4o", 800));
+        tracker
+    }
+
+    #[test]
+    fn test_filter_stats() {
+        let tracker = tracker();
+        let day = |s: &str| Some(s.parse::<NaiveDate>().unwrap());
+
+        let october = StatsFilter { since: day("2026-10-01"), until: day("2026-10-07"), ..Default::default() };
+        assert_eq!(tracker.get_filtered_stats(&october).total_tokens, 600);
+
+        let work = StatsFilter { profile: Some("work".to_string()), ..Default::default() };
+        assert_eq!(tracker.filter(&work).len(), 2);
+
+        // Filtering by model only counts that model's usage
+        let mut session = stats_on("2026-10-13", "default", "gpt-4o", 1000);
+        session.add_usage("openai", "gpt-4o-mini", &Usage::new(5, 0));
+        let mut tracker = tracker;
+        tracker.track_session(session);
+        let mini = StatsFilter { model: Some("gpt-4o-mini".to_string()), ..Default::default() };
+        let total = tracker.get_filtered_stats(&mini);
+        assert_eq!(total.total_tokens, 405);
+        assert_eq!(total.models.keys().collect::<Vec<_>>(), vec!["gpt-4o-mini"]);
+    }
+
+    #[test]
+    fn test_aggregate_stats() {
+        let tracker = tracker();
+        let tokens = |period| tracker.aggregate(&StatsFilter::default(), period)
+            .into_iter()
+            .map(|p| (p.start.to_string(), p.sessions, p.stats.total_tokens))
+            .collect::<Vec<_>>();
+
+        assert_eq!(tokens(Period::Week), vec![
+            ("2026-09-28".to_string(), 1, 100),
+            ("2026-10-05".to_string(), 2, 600),
+            ("2026-10-12".to_string(), 1, 800),
+        ]);
+        assert_eq!(tokens(Period::Month), vec![
+            ("2026-09-01".to_string(), 1, 100),
+            ("2026-10-01".to_string(), 3, 1400),
+        ]);
+        assert_eq!(tokens(Period::Day).len(), 4);
+    }
+}
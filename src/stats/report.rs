@@ -0,0 +1,322 @@
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::stats::{PeriodStats, SessionStats};
+
+/// How a stats report is written out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl std::str::FromStr for ReportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(ReportFormat::Table),
+            "json" => Ok(ReportFormat::Json),
+            "csv" => Ok(ReportFormat::Csv),
+            _ => Err(anyhow!("Unknown format: {}, expected table, json or csv", s)),
+        }
+    }
+}
+
+/// Which columns a report shows besides the session details
+#[derive(Debug, Clone, Copy)]
+pub struct ReportColumns {
+    pub tokens: bool,
+    pub cost: bool,
+}
+
+impl ReportColumns {
+    /// Show the token and cost columns that were asked for, or both if
+    /// neither was
+    pub fn new(tokens: bool, cost: bool) -> Self {
+        if tokens || cost {
+            Self { tokens, cost }
+        } else {
+            Self { tokens: true, cost: true }
+        }
+    }
+}
+
+/// One line of a report, covering a session, a period or the total
+#[derive(Debug, Clone, Serialize)]
+pub struct ReportRow {
+    pub name: String,
+    pub started: String,
+    pub sessions: u32,
+    pub duration_secs: u64,
+    pub turns: u32,
+    pub tool_calls: u32,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub cached_tokens: u32,
+    pub cost: f64,
+    /// Some of the usage has no known price and is not part of the cost
+    pub unpriced: bool,
+    pub models: Vec<String>,
+}
+
+impl ReportRow {
+    fn new(name: String, started: String, sessions: u32, duration: Duration, stats: &SessionStats) -> Self {
+        let usage = stats.usage();
+        Self {
+            name,
+            started,
+            sessions,
+            duration_secs: duration.as_secs(),
+            turns: stats.total_messages,
+            tool_calls: stats.tool_calls,
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+            cached_tokens: usage.cache_read_tokens + usage.cache_write_tokens,
+            cost: stats.total_cost,
+            unpriced: stats.has_unpriced_usage(),
+            models: stats.models.keys().cloned().collect(),
+        }
+    }
+
+    pub fn from_session(stats: &SessionStats) -> Self {
+        Self::new(stats.session_id.clone(), stats.start_time.to_rfc3339(), 1, stats.duration(), stats)
+    }
+
+    pub fn from_period(period: &PeriodStats) -> Self {
+        Self::new(period.start.to_string(), period.start.to_string(), period.sessions, period.duration, &period.stats)
+    }
+
+    /// Add up the rows, listing every model used in any of them
+    pub fn total(rows: &[ReportRow]) -> Self {
+        let mut total = Self {
+            name: "total".to_string(),
+            started: rows.iter().map(|row| row.started.clone()).min().unwrap_or_default(),
+            sessions: 0,
+            duration_secs: 0,
+            turns: 0,
+            tool_calls: 0,
+            input_tokens: 0,
+            output_tokens: 0,
+            cached_tokens: 0,
+            cost: 0.0,
+            unpriced: false,
+            models: Vec::new(),
+        };
+        for row in rows {
+            total.sessions += row.sessions;
+            total.duration_secs += row.duration_secs;
+            total.turns += row.turns;
+            total.tool_calls += row.tool_calls;
+            total.input_tokens += row.input_tokens;
+            total.output_tokens += row.output_tokens;
+            total.cached_tokens += row.cached_tokens;
+            total.cost += row.cost;
+            total.unpriced |= row.unpriced;
+            total.models.extend(row.models.iter().cloned());
+        }
+        total.models.sort();
+        total.models.dedup();
+        total
+    }
+
+    /// The cells under `headers`, tables flag unpriced cost with a `*` where
+    /// CSV has a column for it so the cost stays a number
+    fn cells(&self, columns: ReportColumns, format: ReportFormat) -> Vec<String> {
+        let mut cells = vec![
+            self.name.clone(),
+            self.started.clone(),
+            self.sessions.to_string(),
+            self.duration_secs.to_string(),
+            self.turns.to_string(),
+            self.tool_calls.to_string(),
+        ];
+        if columns.tokens {
+            cells.push(self.input_tokens.to_string());
+            cells.push(self.output_tokens.to_string());
+            cells.push(self.cached_tokens.to_string());
+        }
+        if columns.cost {
+            if format == ReportFormat::Csv {
+                cells.push(format!("{:.4}", self.cost));
+                cells.push(self.unpriced.to_string());
+            } else {
+                let unpriced = if self.unpriced { "*" } else { "" };
+                cells.push(format!("{:.4}{}", self.cost, unpriced));
+            }
+        }
+        cells.push(self.models.join(" "));
+        cells
+    }
+}
+
+fn headers(columns: ReportColumns, format: ReportFormat) -> Vec<&'static str> {
+    let mut headers = vec!["name", "started", "sessions", "duration_secs", "turns", "tool_calls"];
+    if columns.tokens {
+        headers.extend(["input_tokens", "output_tokens", "cached_tokens"]);
+    }
+    if columns.cost {
+        headers.push("cost_usd");
+        if format == ReportFormat::Csv {
+            headers.push("unpriced");
+        }
+    }
+    headers.push("models");
+    headers
+}
+
+/// Render the rows followed by their total
+pub fn render(rows: &[ReportRow], format: ReportFormat, columns: ReportColumns) -> Result<String> {
+    let total = ReportRow::total(rows);
+    match format {
+        ReportFormat::Json => render_json(rows, &total, columns),
+        ReportFormat::Csv => Ok(render_csv(rows, &total, columns)),
+        ReportFormat::Table => Ok(render_table(rows, &total, columns)),
+    }
+}
+
+fn render_json(rows: &[ReportRow], total: &ReportRow, columns: ReportColumns) -> Result<String> {
+    let to_json = |row: &ReportRow| -> Result<serde_json::Value> {
+        let mut value = serde_json::to_value(row)?;
+        let object = value.as_object_mut().unwrap();
+        if !columns.tokens {
+            for key in ["input_tokens", "output_tokens", "cached_tokens"] {
+                object.remove(key);
+            }
+        }
+        if !columns.cost {
+            object.remove("cost");
+            object.remove("unpriced");
+        }
+        Ok(value)
+    };
+    let report = serde_json::json!({
+        "rows": rows.iter().map(to_json).collect::<Result<Vec<_>>>()?,
+        "total": to_json(total)?,
+    });
+    Ok(serde_json::to_string_pretty(&report)?)
+}
+
+fn render_csv(rows: &[ReportRow], total: &ReportRow, columns: ReportColumns) -> String {
+    let line = |cells: Vec<String>| cells.iter().map(|cell| csv_field(cell)).collect::<Vec<_>>().join(",");
+    let mut lines = vec![line(headers(columns, ReportFormat::Csv).into_iter().map(String::from).collect())];
+    lines.extend(rows.iter().chain(Some(total)).map(|row| line(row.cells(columns, ReportFormat::Csv))));
+    lines.join("\n")
+}
+
+/// Quote a field when it holds a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn render_table(rows: &[ReportRow], total: &ReportRow, columns: ReportColumns) -> String {
+    let headers = headers(columns, ReportFormat::Table);
+    let body: Vec<Vec<String>> = rows.iter()
+        .chain(Some(total))
+        .map(|row| row.cells(columns, ReportFormat::Table))
+        .collect();
+
+    let mut widths: Vec<usize> = headers.iter().map(|header| header.len()).collect();
+    for cells in &body {
+        for (width, cell) in widths.iter_mut().zip(cells) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    // The first two and the last column hold text, the rest are numbers
+    let last = headers.len() - 1;
+    let format_line = |cells: &[String]| {
+        cells.iter()
+            .enumerate()
+            .map(|(i, cell)| match i {
+                0 | 1 => format!("{:<width$}", cell, width = widths[i]),
+                i if i == last => cell.clone(),
+                _ => format!("{:>width$}", cell, width = widths[i]),
+            })
+            .collect::<Vec<_>>()
+            .join("  ")
+    };
+
+    let header: Vec<String> = headers.iter().map(|header| header.to_string()).collect();
+    let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    let mut lines = vec![format_line(&header), format_line(&separator)];
+    for (i, cells) in body.iter().enumerate() {
+        if i == body.len() - 1 {
+            lines.push(format_line(&separator));
+        }
+        lines.push(format_line(cells));
+    }
+    if columns.cost && total.unpriced {
+        lines.push("* includes usage of models without a known price, which is not in the cost".to_string());
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Usage;
+
+    fn rows() -> Vec<ReportRow> {
+        let mut first = SessionStats::new("first".to_string());
+        first.add_message();
+        first.add_tool_calls(2);
+        first.add_usage("openai", "gpt-4o", &Usage::new(1_000_000, 0));
+        first.complete();
+
+        let mut second = SessionStats::new("second, again".to_string());
+        second.add_message();
+        second.add_usage("groq", "llama-3.1-70b", &Usage::new(100, 20));
+        second.complete();
+
+        vec![ReportRow::from_session(&first), ReportRow::from_session(&second)]
+    }
+
+    #[test]
+    fn test_total_row() {
+        let total = ReportRow::total(&rows());
+        assert_eq!(total.sessions, 2);
+        assert_eq!(total.turns, 2);
+        assert_eq!(total.tool_calls, 2);
+        assert_eq!(total.input_tokens, 1_000_100);
+        assert!((total.cost - 2.50).abs() < 1e-9);
+        assert!(total.unpriced);
+        assert_eq!(total.models, vec!["gpt-4o", "llama-3.1-70b"]);
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let csv = render(&rows(), ReportFormat::Csv, ReportColumns::new(false, true)).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "name,started,sessions,duration_secs,turns,tool_calls,cost_usd,unpriced,models");
+        assert!(lines[2].starts_with("\"second, again\","));
+        assert!(lines[3].starts_with("total,"));
+        assert!(lines[3].ends_with(",2.5000,true,gpt-4o llama-3.1-70b"));
+    }
+
+    #[test]
+    fn test_render_json() {
+        let json = render(&rows(), ReportFormat::Json, ReportColumns::new(true, false)).unwrap();
+        let report: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(report["rows"].as_array().unwrap().len(), 2);
+        assert_eq!(report["total"]["output_tokens"], 20);
+        assert!(report["total"].get("cost").is_none());
+    }
+
+    #[test]
+    fn test_render_table() {
+        let table = render(&rows(), ReportFormat::Table, ReportColumns::new(false, false)).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("name           started"));
+        // Every row lines up with the header
+        let models_column = lines[0].find("models").unwrap();
+        assert!(lines[2].get(models_column..).unwrap().starts_with("gpt-4o"));
+        assert!(lines.last().unwrap().starts_with("* includes usage"));
+    }
+}
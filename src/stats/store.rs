@@ -0,0 +1,91 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use anyhow::{Context, Result};
+use log::warn;
+
+use crate::cli::config::STATS_PATH;
+use crate::stats::SessionStats;
+
+/// Completed session stats, one JSON record per line
+pub struct StatsStore {
+    path: PathBuf,
+}
+
+impl Default for StatsStore {
+    fn default() -> Self {
+        Self::new(shellexpand::tilde(STATS_PATH).into_owned())
+    }
+}
+
+impl StatsStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, stats: &SessionStats) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .with_context(|| format!("Failed to open stats at {}", self.path.display()))?;
+        writeln!(file, "{}", serde_json::to_string(stats)?)?;
+        Ok(())
+    }
+
+    /// Read every record, a missing store has none
+    ///
+    /// Lines that do not parse, such as one cut short by a crash, are skipped
+    /// so they cannot hide the rest of the history.
+    pub fn load(&self) -> Result<Vec<SessionStats>> {
+        if !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let content = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read stats from {}", self.path.display()))?;
+        let records = content.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .filter_map(|(number, line)| match serde_json::from_str(line) {
+                Ok(stats) => Some(stats),
+                Err(e) => {
+                    warn!("Skipping line {} of {}: {}", number + 1, self.path.display(), e);
+                    None
+                }
+            })
+            .collect();
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_and_load() {
+        let dir = std::env::temp_dir().join(format!("goose-stats-{}", uuid::Uuid::new_v4()));
+        let store = StatsStore::new(dir.join("stats.jsonl"));
+        assert!(store.load().unwrap().is_empty());
+
+        store.append(&SessionStats::new("first".to_string())).unwrap();
+        // A record cut short by a crash
+        OpenOptions::new().append(true).open(store.path()).unwrap()
+            .write_all(b"{\"session_id\": \"cut sh\n").unwrap();
+        store.append(&SessionStats::new("second".to_string())).unwrap();
+
+        let ids: Vec<_> = store.load().unwrap().into_iter().map(|stats| stats.session_id).collect();
+        assert_eq!(ids, vec!["first", "second"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
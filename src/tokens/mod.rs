@@ -0,0 +1,122 @@
+use tiktoken_rs::{cl100k_base_singleton, o200k_base_singleton, CoreBPE};
+
+use crate::models::catalog::{model_info, Tokenizer};
+use crate::models::Message;
+use crate::models::message::Content;
+use crate::toolkit::Tool;
+
+/// Tokens added by the chat format for every message
+const TOKENS_PER_MESSAGE: usize = 4;
+
+/// Tokens that prime the response after the last message
+const TOKENS_PER_REPLY: usize = 3;
+
+/// Providers bill images by size, this is a typical figure for a screenshot
+const TOKENS_PER_IMAGE: usize = 1000;
+
+/// Characters per token when there is no tokenizer, on the low side so the
+/// estimate errs towards too many tokens
+const CHARS_PER_TOKEN: f64 = 3.5;
+
+/// Counts the tokens a request will use for a given model
+///
+/// OpenAI models are counted exactly with their BPE encoding, other models
+/// are estimated from the length of the text.
+#[derive(Clone, Copy)]
+pub struct TokenCounter {
+    bpe: Option<&'static CoreBPE>,
+}
+
+impl TokenCounter {
+    pub fn for_model(model: &str) -> Self {
+        let bpe = match model_info(model).tokenizer {
+            Tokenizer::O200kBase => Some(o200k_base_singleton()),
+            Tokenizer::Cl100kBase => Some(cl100k_base_singleton()),
+            Tokenizer::Estimate => None,
+        };
+        Self { bpe }
+    }
+
+    /// Whether counts come from the model's own tokenizer rather than an estimate
+    pub fn is_exact(&self) -> bool {
+        self.bpe.is_some()
+    }
+
+    pub fn count_text(&self, text: &str) -> usize {
+        match self.bpe {
+            Some(bpe) => bpe.encode_with_special_tokens(text).len(),
+            None => (text.chars().count() as f64 / CHARS_PER_TOKEN).ceil() as usize,
+        }
+    }
+
+    pub fn count_message(&self, message: &Message) -> usize {
+        let content: usize = message.content.iter()
+            .map(|content| match content {
+                Content::Text { text } => self.count_text(text),
+                Content::Image { .. } => TOKENS_PER_IMAGE,
+                Content::ToolUse { id, name, parameters } => {
+                    self.count_text(id) + self.count_text(name) + self.count_text(&parameters.to_string())
+                }
+                Content::ToolResult { tool_use_id, output, .. } => {
+                    self.count_text(tool_use_id) + self.count_text(output)
+                }
+            })
+            .sum();
+        TOKENS_PER_MESSAGE + content
+    }
+
+    /// Count a conversation as it will be sent, including the reply priming
+    pub fn count_messages(&self, messages: &[Message]) -> usize {
+        messages.iter().map(|message| self.count_message(message)).sum::<usize>() + TOKENS_PER_REPLY
+    }
+
+    pub fn count_tools(&self, tools: &[Tool]) -> usize {
+        tools.iter()
+            .map(|tool| {
+                TOKENS_PER_MESSAGE
+                    + self.count_text(&tool.name)
+                    + self.count_text(&tool.description)
+                    + self.count_text(&tool.parameters.to_string())
+            })
+            .sum()
+    }
+}
+
+impl std::fmt::Debug for TokenCounter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenCounter")
+            .field("exact", &self.is_exact())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_count_text() {
+        let counter = TokenCounter::for_model("gpt-4o");
+        assert!(counter.is_exact());
+        assert_eq!(counter.count_text("hello world"), 2);
+
+        let counter = TokenCounter::for_model("gpt-4");
+        assert_eq!(counter.count_text("tiktoken is great!"), 6);
+
+        let counter = TokenCounter::for_model("claude-3-5-sonnet-latest");
+        assert!(!counter.is_exact());
+        assert_eq!(counter.count_text("1234567"), 2);
+    }
+
+    #[test]
+    fn test_count_messages_and_tools() {
+        let counter = TokenCounter::for_model("gpt-4o");
+        let messages = vec![Message::system("You are goose"), Message::user("hello world")];
+        let expected = counter.count_text("You are goose") + counter.count_text("hello world") + 2 * 4 + 3;
+        assert_eq!(counter.count_messages(&messages), expected);
+
+        let tool = Tool::new("bash", "Run a command", json!({"type": "object"}), vec![]);
+        assert!(counter.count_tools(&[tool]) > counter.count_text("Run a command"));
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt::{self, Debug};
+use std::sync::Arc;
 use anyhow::Result;
 use serde::{Serialize, Deserialize};
 use async_trait::async_trait;
@@ -62,9 +63,11 @@ pub trait Toolkit: Send + Sync + Debug {
     async fn process_tool(&self, tool_call: &Tool) -> Result<Message>;
 }
 
+/// The toolkits a toolkit was given for its requirements, by the names it
+/// refers to them with
 pub struct Requirements {
     toolkit: String,
-    requirements: std::collections::HashMap<String, Box<dyn Toolkit>>,
+    requirements: std::collections::HashMap<String, Arc<dyn Toolkit>>,
 }
 
 impl Requirements {
@@ -75,16 +78,50 @@ impl Requirements {
         }
     }
 
-    pub fn get(&self, requirement: &str) -> Option<&Box<dyn Toolkit>> {
+    pub fn insert(&mut self, requirement: &str, toolkit: Arc<dyn Toolkit>) {
+        self.requirements.insert(requirement.to_string(), toolkit);
+    }
+
+    pub fn get(&self, requirement: &str) -> Option<&Arc<dyn Toolkit>> {
         self.requirements.get(requirement)
     }
+
+    /// The toolkit for a requirement, to keep and call into later
+    pub fn require(&self, requirement: &str) -> Result<Arc<dyn Toolkit>> {
+        self.requirements.get(requirement).cloned().ok_or_else(|| {
+            anyhow::anyhow!(
+                "Toolkit {} requires {}, map it to one of the profile's toolkits under requires",
+                self.toolkit,
+                requirement
+            )
+        })
+    }
 }
 
 impl fmt::Debug for Requirements {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<_> = self.requirements.keys().collect();
+        names.sort();
         f.debug_struct("Requirements")
             .field("toolkit", &self.toolkit)
-            .field("requirements", &"<dyn Toolkit>")
+            .field("requirements", &names)
             .finish()
     }
-}
\ No newline at end of file
+}
+
+/// A toolkit shared with the toolkits that require it is used through the
+/// same instance
+#[async_trait]
+impl Toolkit for Arc<dyn Toolkit> {
+    fn system(&self) -> String {
+        self.as_ref().system()
+    }
+
+    fn tools(&self) -> Vec<Tool> {
+        self.as_ref().tools()
+    }
+
+    async fn process_tool(&self, tool_call: &Tool) -> Result<Message> {
+        self.as_ref().process_tool(tool_call).await
+    }
+}
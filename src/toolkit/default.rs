@@ -1,4 +1,3 @@
-use std::sync::Arc;
 use anyhow::Result;
 use async_trait::async_trait;
 use serde_json::json;
@@ -137,6 +136,12 @@ impl DefaultToolkit {
     }
 }
 
+impl Default for DefaultToolkit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[async_trait]
 impl Toolkit for DefaultToolkit {
     fn system(&self) -> String {
@@ -199,7 +204,7 @@ impl Toolkit for DefaultToolkit {
                 
                 if !output.stderr.is_empty() {
                     if !result.is_empty() {
-                        result.push_str("\n");
+                        result.push('\n');
                     }
                     result.push_str(&String::from_utf8_lossy(&output.stderr));
                 }
@@ -325,13 +330,6 @@ impl Toolkit for DefaultToolkit {
     }
 }
 
-/// Returns a list of default toolkits that should be automatically registered
-pub fn get_default_toolkits() -> Vec<Box<dyn Toolkit>> {
-    vec![
-        Box::new(DefaultToolkit::new())
-    ]
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
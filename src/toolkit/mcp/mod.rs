@@ -0,0 +1,266 @@
+mod server;
+mod transport;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::models::{McpHttpTransport, McpServerConfig, Message};
+use super::{Tool, Toolkit};
+pub use server::McpServer;
+use transport::{header_map, SseTransport, StdioTransport, StreamableHttpTransport, Transport};
+
+/// The protocol version goose asks servers for
+pub const PROTOCOL_VERSION: &str = "2025-03-26";
+
+/// Versions goose can talk to a server in, the server picks one when it
+/// does not support the requested version
+const SUPPORTED_VERSIONS: &[&str] = &[PROTOCOL_VERSION, "2024-11-05"];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolDefinition {
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default = "empty_schema")]
+    input_schema: Value,
+}
+
+fn empty_schema() -> Value {
+    json!({"type": "object", "properties": {}})
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolList {
+    tools: Vec<ToolDefinition>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ToolCallResult {
+    #[serde(default)]
+    content: Vec<Value>,
+    #[serde(default)]
+    is_error: bool,
+}
+
+impl From<ToolDefinition> for Tool {
+    fn from(definition: ToolDefinition) -> Self {
+        let required = definition.input_schema["required"].as_array()
+            .map(|required| required.iter().filter_map(|name| name.as_str().map(String::from)).collect())
+            .unwrap_or_default();
+        Tool::new(&definition.name, &definition.description, definition.input_schema, required)
+    }
+}
+
+/// The text of a tool result, other content is replaced by a note of its type
+fn content_text(content: &[Value]) -> String {
+    content.iter()
+        .map(|item| match item["type"].as_str() {
+            Some("text") => item["text"].as_str().unwrap_or_default().to_string(),
+            Some("resource") if item["resource"]["text"].is_string() => {
+                item["resource"]["text"].as_str().unwrap_or_default().to_string()
+            }
+            kind => format!("[{} content omitted]", kind.unwrap_or("unknown")),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Expand environment variables in configured values
+fn expand_values(values: &HashMap<String, String>) -> Result<HashMap<String, String>> {
+    values.iter()
+        .map(|(key, value)| {
+            let expanded = shellexpand::env(value).with_context(|| format!("Failed to expand {}", key))?;
+            Ok((key.clone(), expanded.into_owned()))
+        })
+        .collect()
+}
+
+/// A JSON-RPC session with an MCP server
+struct McpClient {
+    name: String,
+    transport: Box<dyn Transport>,
+    next_id: AtomicU64,
+    timeout: Duration,
+}
+
+impl McpClient {
+    async fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        let response = tokio::time::timeout(self.timeout, self.transport.request(request))
+            .await
+            .map_err(|_| anyhow!("MCP server {} did not answer {} within {}s", self.name, method, self.timeout.as_secs()))
+            .and_then(|response| response);
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                self.transport.forget(id);
+                return Err(e);
+            }
+        };
+
+        if let Some(error) = response.get("error") {
+            bail!("MCP server {} failed {}: {}", self.name, method, error["message"].as_str().unwrap_or("unknown error"));
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
+    }
+
+    async fn notify(&self, method: &str) -> Result<()> {
+        self.transport.notify(json!({"jsonrpc": "2.0", "method": method})).await
+    }
+
+    /// Agree on a protocol version, returning the server's instructions
+    async fn initialize(&self) -> Result<String> {
+        let result = self.request("initialize", json!({
+            "protocolVersion": PROTOCOL_VERSION,
+            "capabilities": {},
+            "clientInfo": {"name": "goose", "version": env!("CARGO_PKG_VERSION")}
+        })).await?;
+
+        let version = result["protocolVersion"].as_str().unwrap_or_default();
+        if !SUPPORTED_VERSIONS.contains(&version) {
+            bail!("MCP server {} uses protocol version {}, goose supports {}", self.name, version, SUPPORTED_VERSIONS.join(", "));
+        }
+        self.notify("notifications/initialized").await?;
+        Ok(result["instructions"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// Every tool of the server, across all pages of the list
+    async fn list_tools(&self) -> Result<Vec<Tool>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({"cursor": cursor}),
+                None => json!({}),
+            };
+            let page: ToolList = serde_json::from_value(self.request("tools/list", params).await?)
+                .with_context(|| format!("Invalid tool list from MCP server {}", self.name))?;
+            tools.extend(page.tools.into_iter().map(Tool::from));
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return Ok(tools),
+            }
+        }
+    }
+
+    async fn call_tool(&self, name: &str, arguments: &Value) -> Result<String> {
+        let result = self.request("tools/call", json!({"name": name, "arguments": arguments})).await?;
+        let result: ToolCallResult = serde_json::from_value(result)
+            .with_context(|| format!("Invalid result of {} from MCP server {}", name, self.name))?;
+        let text = content_text(&result.content);
+        if result.is_error {
+            bail!(text);
+        }
+        Ok(text)
+    }
+}
+
+/// The tools of a Model Context Protocol server, which goose either launches
+/// or connects to over HTTP
+///
+/// The tools are listed once when connecting, so servers that change their
+/// tools need a new session.
+pub struct McpToolkit {
+    client: McpClient,
+    instructions: String,
+    tools: Vec<Tool>,
+}
+
+impl McpToolkit {
+    /// Start or reach the server and list its tools
+    pub async fn connect(name: &str, config: &McpServerConfig) -> Result<Self> {
+        let timeout = Duration::from_secs(config.timeout_secs);
+        let transport: Box<dyn Transport> = match (&config.command, &config.url) {
+            (Some(command), _) => {
+                Box::new(StdioTransport::spawn(name, command, &config.args, &expand_values(&config.env)?)?)
+            }
+            (None, Some(url)) => {
+                let headers = header_map(&expand_values(&config.headers)?)?;
+                match config.transport {
+                    McpHttpTransport::StreamableHttp => Box::new(StreamableHttpTransport::new(url, headers)),
+                    McpHttpTransport::Sse => Box::new(
+                        tokio::time::timeout(timeout, SseTransport::connect(url, headers))
+                            .await
+                            .map_err(|_| anyhow!("{} did not open an event stream within {}s", url, timeout.as_secs()))??,
+                    ),
+                }
+            }
+            (None, None) => bail!("MCP server {} needs either a command or a url", name),
+        };
+
+        let client = McpClient { name: name.to_string(), transport, next_id: AtomicU64::new(1), timeout };
+        let instructions = client.initialize().await?;
+        let tools = client.list_tools().await?;
+        Ok(Self { client, instructions, tools })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.client.name
+    }
+}
+
+impl fmt::Debug for McpToolkit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let tools: Vec<&str> = self.tools.iter().map(|tool| tool.name.as_str()).collect();
+        f.debug_struct("McpToolkit")
+            .field("name", &self.client.name)
+            .field("tools", &tools)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl Toolkit for McpToolkit {
+    fn system(&self) -> String {
+        self.instructions.clone()
+    }
+
+    fn tools(&self) -> Vec<Tool> {
+        self.tools.clone()
+    }
+
+    async fn process_tool(&self, tool_call: &Tool) -> Result<Message> {
+        let text = self.client.call_tool(&tool_call.name, &tool_call.parameters).await?;
+        Ok(Message::assistant(&text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tool_from_definition() {
+        let definition: ToolDefinition = serde_json::from_value(json!({
+            "name": "search",
+            "inputSchema": {"type": "object", "properties": {"query": {"type": "string"}}, "required": ["query"]}
+        })).unwrap();
+        let tool = Tool::from(definition);
+        assert_eq!(tool.required, vec!["query"]);
+        assert_eq!(tool.parameters["properties"]["query"]["type"], "string");
+
+        let definition: ToolDefinition = serde_json::from_value(json!({"name": "now"})).unwrap();
+        assert_eq!(Tool::from(definition).parameters, empty_schema());
+    }
+
+    #[test]
+    fn test_content_text() {
+        let content = vec![
+            json!({"type": "text", "text": "first"}),
+            json!({"type": "image", "data": "", "mimeType": "image/png"}),
+            json!({"type": "resource", "resource": {"uri": "file:///a", "text": "second"}}),
+        ];
+        assert_eq!(content_text(&content), "first\n[image content omitted]\nsecond");
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+
+use crate::toolkit::{Tool, Toolkit};
+use super::{PROTOCOL_VERSION, SUPPORTED_VERSIONS};
+
+/// Offers toolkits to other MCP hosts, so they can call the tools goose
+/// gives its own agent
+///
+/// Requests are answered one at a time, in the order they arrive.
+pub struct McpServer {
+    toolkits: Vec<Arc<dyn Toolkit>>,
+}
+
+impl McpServer {
+    pub fn new(toolkits: Vec<Arc<dyn Toolkit>>) -> Self {
+        Self { toolkits }
+    }
+
+    /// Answer the messages read from a client, one per line, until it
+    /// closes its end
+    pub async fn serve<R, W>(&self, reader: R, mut writer: W) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut lines = BufReader::new(reader).lines();
+        while let Some(line) = lines.next_line().await.context("Failed to read from the MCP client")? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match serde_json::from_str(&line) {
+                Ok(message) => self.handle(message).await,
+                Err(e) => Some(error_response(Value::Null, -32700, &format!("Parse error: {}", e))),
+            };
+            if let Some(reply) = reply {
+                writer.write_all(format!("{}\n", reply).as_bytes()).await?;
+                writer.flush().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The reply to a message, or to each message of a batch, `None` when
+    /// nothing needs one
+    pub async fn handle(&self, message: Value) -> Option<Value> {
+        if let Value::Array(batch) = message {
+            let mut replies = Vec::new();
+            for message in batch {
+                replies.extend(Box::pin(self.handle(message)).await);
+            }
+            return (!replies.is_empty()).then_some(Value::Array(replies));
+        }
+
+        let method = message["method"].as_str().unwrap_or_default();
+        let Some(id) = message.get("id").cloned() else {
+            debug!("MCP notification {}", method);
+            return None;
+        };
+        if message.get("method").is_none() {
+            warn!("Ignoring an MCP response, the server sends no requests: {}", message);
+            return None;
+        }
+
+        let params = &message["params"];
+        let result = match method {
+            "initialize" => Ok(self.initialize(params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({"tools": self.tools().iter().map(tool_definition).collect::<Vec<_>>()})),
+            "tools/call" => self.call_tool(params).await,
+            _ => Err((-32601, format!("Method not found: {}", method))),
+        };
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err((code, message)) => error_response(id, code, &message),
+        })
+    }
+
+    fn initialize(&self, params: &Value) -> Value {
+        // Answer in the client's version when it is one goose speaks
+        let requested = params["protocolVersion"].as_str().unwrap_or_default();
+        let version = SUPPORTED_VERSIONS.iter().find(|version| **version == requested).unwrap_or(&PROTOCOL_VERSION);
+        let instructions: Vec<String> = self.toolkits.iter()
+            .map(|toolkit| toolkit.system())
+            .filter(|system| !system.is_empty())
+            .collect();
+        json!({
+            "protocolVersion": version,
+            "capabilities": {"tools": {}},
+            "serverInfo": {"name": "goose", "version": env!("CARGO_PKG_VERSION")},
+            "instructions": instructions.join("\n\n"),
+        })
+    }
+
+    fn tools(&self) -> Vec<Tool> {
+        self.toolkits.iter().flat_map(|toolkit| toolkit.tools()).collect()
+    }
+
+    /// Run a tool, its failures are reported in the result for the model to
+    /// see rather than as protocol errors
+    async fn call_tool(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let name = params["name"].as_str().unwrap_or_default();
+        let toolkit = self.toolkits.iter()
+            .find(|toolkit| toolkit.tools().iter().any(|tool| tool.name == name))
+            .ok_or_else(|| (-32602, format!("Unknown tool: {}", name)))?;
+        let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+
+        let tool = Tool::new(name, "", arguments, vec![]);
+        let (text, is_error) = match toolkit.process_tool(&tool).await {
+            Ok(message) => (message.text(), false),
+            Err(e) => (e.to_string(), true),
+        };
+        Ok(json!({"content": [{"type": "text", "text": text}], "isError": is_error}))
+    }
+}
+
+/// A tool as MCP lists it, its parameters are the input schema
+fn tool_definition(tool: &Tool) -> Value {
+    let mut schema = tool.parameters.clone();
+    if schema.get("required").is_none() && !tool.required.is_empty() {
+        schema["required"] = json!(tool.required);
+    }
+    json!({"name": tool.name, "description": tool.description, "inputSchema": schema})
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toolkit::plan::{PlanProgress, PlanToolkit, COMPLETE_TASK};
+    use std::sync::Mutex;
+
+    fn server() -> McpServer {
+        let plan = serde_yaml::from_str("kickoff: Go\ntasks: [First]").unwrap();
+        let toolkit = PlanToolkit::new(Arc::new(Mutex::new(PlanProgress::new(plan))));
+        McpServer::new(vec![Arc::new(toolkit)])
+    }
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params})
+    }
+
+    #[tokio::test]
+    async fn test_initialize_and_list_tools() {
+        let server = server();
+        let reply = server.handle(request(1, "initialize", json!({"protocolVersion": "2024-11-05"}))).await.unwrap();
+        assert_eq!(reply["result"]["protocolVersion"], "2024-11-05");
+        assert!(reply["result"]["instructions"].as_str().unwrap().starts_with("This session follows a plan"));
+
+        let reply = server.handle(request(2, "initialize", json!({"protocolVersion": "1999-01-01"}))).await.unwrap();
+        assert_eq!(reply["result"]["protocolVersion"], PROTOCOL_VERSION);
+        assert!(server.handle(json!({"jsonrpc": "2.0", "method": "notifications/initialized"})).await.is_none());
+
+        let reply = server.handle(request(3, "tools/list", json!({}))).await.unwrap();
+        assert_eq!(reply["id"], 3);
+        assert_eq!(reply["result"]["tools"][0]["name"], COMPLETE_TASK);
+        assert_eq!(reply["result"]["tools"][0]["inputSchema"]["required"], json!(["task"]));
+    }
+
+    #[tokio::test]
+    async fn test_call_tool() {
+        let server = server();
+        let call = |id, task| request(id, "tools/call", json!({"name": COMPLETE_TASK, "arguments": {"task": task}}));
+
+        let reply = server.handle(call(1, 2)).await.unwrap();
+        assert_eq!(reply["result"]["isError"], true);
+        assert_eq!(reply["result"]["content"][0]["text"], "There is no task 2, the plan has 1 tasks");
+
+        let reply = server.handle(call(2, 1)).await.unwrap();
+        assert_eq!(reply["result"]["isError"], false);
+        assert_eq!(reply["result"]["content"][0]["text"], "All 1 tasks of the plan are complete.");
+
+        let reply = server.handle(request(3, "tools/call", json!({"name": "bash"}))).await.unwrap();
+        assert_eq!(reply["error"]["code"], -32602);
+        let reply = server.handle(request(4, "resources/list", json!({}))).await.unwrap();
+        assert_eq!(reply["error"]["code"], -32601);
+    }
+
+    #[tokio::test]
+    async fn test_serve_lines() {
+        let input = format!("{}\nnot json\n\n{}\n", request(1, "ping", json!({})), json!([request(2, "ping", json!({}))]));
+        let mut output = Vec::new();
+        server().serve(input.as_bytes(), &mut output).await.unwrap();
+
+        let replies: Vec<Value> = String::from_utf8(output).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(replies.len(), 3);
+        assert_eq!(replies[0]["result"], json!({}));
+        assert_eq!(replies[1]["error"]["code"], -32700);
+        assert_eq!(replies[2][0]["id"], 2);
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use futures::StreamExt;
+use log::{debug, warn};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, CONTENT_TYPE};
+use reqwest::Url;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::exchange::stream::sse_events;
+
+/// Header the streamable HTTP transport identifies its session with
+const SESSION_HEADER: &str = "Mcp-Session-Id";
+
+/// Carries JSON-RPC messages between goose and an MCP server
+#[async_trait]
+pub(super) trait Transport: Send + Sync {
+    /// Send a request and wait for the response with the same id
+    async fn request(&self, request: Value) -> Result<Value>;
+
+    async fn notify(&self, notification: Value) -> Result<()>;
+
+    /// Stop waiting for the response to a request that failed or timed out
+    fn forget(&self, _id: u64) {}
+}
+
+/// The requests waiting for a response on a transport that receives
+/// messages separately from sending them
+struct Pending {
+    /// `None` once the server has gone away
+    waiting: Mutex<Option<HashMap<u64, oneshot::Sender<Value>>>>,
+}
+
+impl Pending {
+    fn new() -> Self {
+        Self { waiting: Mutex::new(Some(HashMap::new())) }
+    }
+
+    fn register(&self, request: &Value) -> Result<oneshot::Receiver<Value>> {
+        let id = request["id"].as_u64().ok_or_else(|| anyhow!("MCP requests need a numeric id"))?;
+        let (sender, receiver) = oneshot::channel();
+        match self.waiting.lock().unwrap().as_mut() {
+            Some(waiting) => waiting.insert(id, sender),
+            None => bail!("The MCP server closed the connection"),
+        };
+        Ok(receiver)
+    }
+
+    /// Handle a message from the server, returning the reply it needs
+    fn receive(&self, message: Value) -> Option<Value> {
+        if message.get("method").is_some() {
+            return server_reply(&message);
+        }
+        let id = message["id"].as_u64()?;
+        let sender = self.waiting.lock().unwrap().as_mut()?.remove(&id);
+        match sender {
+            Some(sender) => {
+                let _ = sender.send(message);
+            }
+            None => debug!("Dropping an MCP response nothing waits for: {}", message),
+        }
+        None
+    }
+
+    /// Stop waiting for a response, one that still arrives is dropped
+    fn forget(&self, id: u64) {
+        if let Some(waiting) = self.waiting.lock().unwrap().as_mut() {
+            waiting.remove(&id);
+        }
+    }
+
+    /// Fail every waiting request and any sent later
+    fn close(&self) {
+        self.waiting.lock().unwrap().take();
+    }
+}
+
+async fn response(receiver: oneshot::Receiver<Value>) -> Result<Value> {
+    receiver.await.map_err(|_| anyhow!("The MCP server closed the connection"))
+}
+
+/// The reply to a request or notification from the server, only pings are
+/// supported as goose offers no client capabilities
+fn server_reply(message: &Value) -> Option<Value> {
+    let id = message.get("id")?;
+    let method = message["method"].as_str().unwrap_or_default();
+    if method == "ping" {
+        return Some(json!({"jsonrpc": "2.0", "id": id, "result": {}}));
+    }
+    Some(json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": -32601, "message": format!("Method not found: {}", method)}
+    }))
+}
+
+/// A server goose runs as a child process, exchanging a message per line on
+/// its stdin and stdout
+pub(super) struct StdioTransport {
+    outgoing: mpsc::UnboundedSender<Value>,
+    pending: Arc<Pending>,
+    /// Killed when the transport is dropped
+    _child: Child,
+}
+
+impl StdioTransport {
+    pub fn spawn(name: &str, command: &str, args: &[String], env: &HashMap<String, String>) -> Result<Self> {
+        let mut child = Command::new(command)
+            .args(args)
+            .envs(env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to run {}", command))?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+
+        let (outgoing, mut receiver) = mpsc::unbounded_channel::<Value>();
+        tokio::spawn(async move {
+            while let Some(message) = receiver.recv().await {
+                let line = format!("{}\n", message);
+                if stdin.write_all(line.as_bytes()).await.is_err() || stdin.flush().await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let pending = Arc::new(Pending::new());
+        let (reader_pending, replies, server) = (Arc::clone(&pending), outgoing.clone(), name.to_string());
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str(&line) {
+                    Ok(message) => {
+                        if let Some(reply) = reader_pending.receive(message) {
+                            let _ = replies.send(reply);
+                        }
+                    }
+                    Err(_) => warn!("MCP server {} wrote a line that is not JSON-RPC: {}", server, line),
+                }
+            }
+            reader_pending.close();
+        });
+
+        let server = name.to_string();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                debug!("{}: {}", server, line);
+            }
+        });
+
+        Ok(Self { outgoing, pending, _child: child })
+    }
+}
+
+#[async_trait]
+impl Transport for StdioTransport {
+    async fn request(&self, request: Value) -> Result<Value> {
+        let receiver = self.pending.register(&request)?;
+        self.outgoing.send(request).map_err(|_| anyhow!("The MCP server closed the connection"))?;
+        response(receiver).await
+    }
+
+    async fn notify(&self, notification: Value) -> Result<()> {
+        self.outgoing.send(notification).map_err(|_| anyhow!("The MCP server closed the connection"))
+    }
+
+    fn forget(&self, id: u64) {
+        self.pending.forget(id);
+    }
+}
+
+/// Build the headers sent with every HTTP request to a server
+pub(super) fn header_map(headers: &HashMap<String, String>) -> Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("Invalid header name: {}", name))?;
+        let value = HeaderValue::from_str(value)
+            .with_context(|| format!("Invalid value for header {}", name))?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+async fn check_status(response: reqwest::Response, url: &str) -> Result<reqwest::Response> {
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        bail!("{} answered with {}: {}", url, status, body);
+    }
+    Ok(response)
+}
+
+/// A server reached over HTTP, answering each POSTed request with either a
+/// JSON response or an event stream ending in one
+pub(super) struct StreamableHttpTransport {
+    client: reqwest::Client,
+    url: String,
+    headers: HeaderMap,
+    /// Assigned by the server when it initializes
+    session_id: Mutex<Option<String>>,
+}
+
+impl StreamableHttpTransport {
+    pub fn new(url: &str, headers: HeaderMap) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            headers,
+            session_id: Mutex::new(None),
+        }
+    }
+
+    async fn post(&self, message: &Value) -> Result<reqwest::Response> {
+        let mut request = self.client.post(&self.url)
+            .headers(self.headers.clone())
+            .header(ACCEPT, "application/json, text/event-stream")
+            .json(message);
+        let session_id = self.session_id.lock().unwrap().clone();
+        if let Some(session_id) = session_id {
+            request = request.header(SESSION_HEADER, session_id);
+        }
+
+        let response = request.send().await.with_context(|| format!("Failed to reach {}", self.url))?;
+        let response = check_status(response, &self.url).await?;
+        if let Some(session_id) = response.headers().get(SESSION_HEADER).and_then(|value| value.to_str().ok()) {
+            *self.session_id.lock().unwrap() = Some(session_id.to_string());
+        }
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl Transport for StreamableHttpTransport {
+    async fn request(&self, request: Value) -> Result<Value> {
+        let response = self.post(&request).await?;
+        let is_stream = response.headers().get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|content_type| content_type.starts_with("text/event-stream"));
+        if !is_stream {
+            return response.json().await.with_context(|| format!("Invalid JSON-RPC response from {}", self.url));
+        }
+
+        let mut events = Box::pin(sse_events(response));
+        while let Some(event) = events.next().await {
+            let message: Value = serde_json::from_str(&event?.data)
+                .with_context(|| format!("Invalid JSON-RPC message from {}", self.url))?;
+            if message.get("method").is_some() {
+                if let Some(reply) = server_reply(&message) {
+                    self.post(&reply).await?;
+                }
+            } else if message["id"] == request["id"] {
+                return Ok(message);
+            }
+        }
+        bail!("{} ended its event stream without a response", self.url)
+    }
+
+    async fn notify(&self, notification: Value) -> Result<()> {
+        self.post(&notification).await.map(|_| ())
+    }
+}
+
+/// A server on the HTTP transport of the 2024-11-05 protocol, which answers
+/// on an event stream opened before anything is sent
+pub(super) struct SseTransport {
+    client: reqwest::Client,
+    /// Where messages are POSTed, as announced by the server
+    endpoint: Url,
+    headers: HeaderMap,
+    pending: Arc<Pending>,
+    reader: JoinHandle<()>,
+}
+
+impl SseTransport {
+    pub async fn connect(url: &str, headers: HeaderMap) -> Result<Self> {
+        let base = Url::parse(url).with_context(|| format!("Invalid MCP server url: {}", url))?;
+        let client = reqwest::Client::new();
+        let response = client.get(base.clone())
+            .headers(headers.clone())
+            .header(ACCEPT, "text/event-stream")
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach {}", url))?;
+        let mut events = Box::pin(sse_events(check_status(response, url).await?));
+
+        let endpoint = loop {
+            match events.next().await {
+                Some(event) => {
+                    let event = event?;
+                    if event.event.as_deref() == Some("endpoint") {
+                        break base.join(event.data.trim())
+                            .with_context(|| format!("Invalid endpoint from {}: {}", url, event.data))?;
+                    }
+                }
+                None => bail!("{} closed its event stream before sending the endpoint", url),
+            }
+        };
+
+        let pending = Arc::new(Pending::new());
+        let reader_pending = Arc::clone(&pending);
+        let (reader_client, reader_endpoint, reader_headers) = (client.clone(), endpoint.clone(), headers.clone());
+        let reader = tokio::spawn(async move {
+            while let Some(Ok(event)) = events.next().await {
+                if event.event.as_deref().is_some_and(|name| name != "message") {
+                    continue;
+                }
+                let Ok(message) = serde_json::from_str(&event.data) else {
+                    warn!("Invalid JSON-RPC message from MCP server: {}", event.data);
+                    continue;
+                };
+                if let Some(reply) = reader_pending.receive(message) {
+                    if let Err(e) = post_message(&reader_client, &reader_endpoint, &reader_headers, &reply).await {
+                        warn!("{:#}", e);
+                    }
+                }
+            }
+            reader_pending.close();
+        });
+
+        Ok(Self { client, endpoint, headers, pending, reader })
+    }
+}
+
+impl Drop for SseTransport {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+async fn post_message(client: &reqwest::Client, endpoint: &Url, headers: &HeaderMap, message: &Value) -> Result<()> {
+    let response = client.post(endpoint.clone())
+        .headers(headers.clone())
+        .json(message)
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach {}", endpoint))?;
+    check_status(response, endpoint.as_str()).await.map(|_| ())
+}
+
+#[async_trait]
+impl Transport for SseTransport {
+    async fn request(&self, request: Value) -> Result<Value> {
+        let receiver = self.pending.register(&request)?;
+        post_message(&self.client, &self.endpoint, &self.headers, &request).await?;
+        response(receiver).await
+    }
+
+    async fn notify(&self, notification: Value) -> Result<()> {
+        post_message(&self.client, &self.endpoint, &self.headers, &notification).await
+    }
+
+    fn forget(&self, id: u64) {
+        self.pending.forget(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicU64;
+    use std::time::Duration;
+    use super::*;
+    use super::super::McpClient;
+
+    /// Waits for responses like the stdio transport, but nothing answers
+    struct SilentTransport(Arc<Pending>);
+
+    #[async_trait]
+    impl Transport for SilentTransport {
+        async fn request(&self, request: Value) -> Result<Value> {
+            response(self.0.register(&request)?).await
+        }
+
+        async fn notify(&self, _notification: Value) -> Result<()> {
+            Ok(())
+        }
+
+        fn forget(&self, id: u64) {
+            self.0.forget(id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_request_stops_waiting() {
+        let pending = Arc::new(Pending::new());
+        let client = McpClient {
+            name: "silent".to_string(),
+            transport: Box::new(SilentTransport(Arc::clone(&pending))),
+            next_id: AtomicU64::new(1),
+            timeout: Duration::from_millis(10),
+        };
+
+        let error = client.request("tools/list", json!({})).await.unwrap_err();
+        assert!(error.to_string().starts_with("MCP server silent did not answer tools/list"), "{}", error);
+        assert!(pending.waiting.lock().unwrap().as_ref().unwrap().is_empty());
+    }
+}
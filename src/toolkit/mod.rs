@@ -1,7 +1,10 @@
 mod base;
 mod tools;
 pub mod default;
+pub mod mcp;
+pub mod plan;
+pub mod registry;
 
 pub use base::{ToolkitError, ToolkitResult, Toolkit, Requirements};
 pub use tools::Tool;
-pub use default::get_default_toolkits;
\ No newline at end of file
+pub use registry::{ToolkitInfo, ToolkitRegistry};
@@ -0,0 +1,183 @@
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::json;
+
+use crate::models::message::Content;
+use crate::models::plan::Plan;
+use crate::models::Message;
+use super::{Tool, Toolkit};
+
+/// Name of the tool the agent calls when it finishes a task
+pub const COMPLETE_TASK: &str = "complete_task";
+
+/// Which tasks of a plan are done
+#[derive(Debug, Clone)]
+pub struct PlanProgress {
+    plan: Plan,
+    done: Vec<bool>,
+}
+
+impl PlanProgress {
+    pub fn new(plan: Plan) -> Self {
+        let done = vec![false; plan.tasks.len()];
+        Self { plan, done }
+    }
+
+    /// Take the progress up to where a saved conversation left it, from
+    /// the tasks it completed without an error
+    pub fn restore(&mut self, messages: &[Message]) {
+        let succeeded: Vec<&str> = messages.iter()
+            .flat_map(|message| &message.content)
+            .filter_map(|content| match content {
+                Content::ToolResult { tool_use_id, is_error: false, .. } => Some(tool_use_id.as_str()),
+                _ => None,
+            })
+            .collect();
+        for content in messages.iter().flat_map(|message| &message.content) {
+            if let Content::ToolUse { id, name, parameters } = content {
+                if name == COMPLETE_TASK && succeeded.contains(&id.as_str()) {
+                    // Recorded calls were valid, so the task exists
+                    let _ = self.complete(parameters.get("task").and_then(|task| task.as_u64()).unwrap_or(0));
+                }
+            }
+        }
+    }
+
+    /// Mark a task, numbered from 1, as done
+    pub fn complete(&mut self, task: u64) -> Result<()> {
+        let index = (task as usize).checked_sub(1)
+            .filter(|index| *index < self.done.len())
+            .ok_or_else(|| anyhow!("There is no task {}, the plan has {} tasks", task, self.done.len()))?;
+        self.done[index] = true;
+        Ok(())
+    }
+
+    pub fn is_done(&self, task: usize) -> bool {
+        self.done.get(task).copied().unwrap_or(false)
+    }
+
+    pub fn completed(&self) -> usize {
+        self.done.iter().filter(|done| **done).count()
+    }
+
+    /// The tasks still to do, as the agent is told after completing one
+    pub fn summary(&self) -> String {
+        let remaining: Vec<String> = self.plan.tasks.iter()
+            .enumerate()
+            .filter(|(i, _)| !self.done[*i])
+            .map(|(i, task)| format!("{}. {}", i + 1, task.description))
+            .collect();
+        if remaining.is_empty() {
+            return format!("All {} tasks of the plan are complete.", self.done.len());
+        }
+        format!(
+            "{} of {} tasks complete, remaining:\n{}",
+            self.completed(),
+            self.done.len(),
+            remaining.join("\n"),
+        )
+    }
+}
+
+/// Lets the agent record its progress through a plan
+#[derive(Debug)]
+pub struct PlanToolkit {
+    progress: Arc<Mutex<PlanProgress>>,
+}
+
+impl PlanToolkit {
+    pub fn new(progress: Arc<Mutex<PlanProgress>>) -> Self {
+        Self { progress }
+    }
+}
+
+#[async_trait]
+impl Toolkit for PlanToolkit {
+    fn system(&self) -> String {
+        "This session follows a plan. When you finish one of its tasks and its checks pass, \
+        call complete_task with the task's number before moving on to the next one."
+            .to_string()
+    }
+
+    fn tools(&self) -> Vec<Tool> {
+        vec![Tool::new(
+            COMPLETE_TASK,
+            "Mark a task of the plan as done, once its acceptance checks pass.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "task": {
+                        "type": "integer",
+                        "description": "The number of the task in the plan, starting at 1."
+                    }
+                },
+                "required": ["task"]
+            }),
+            vec!["task".to_string()],
+        )]
+    }
+
+    async fn process_tool(&self, tool_call: &Tool) -> Result<Message> {
+        match tool_call.name.as_str() {
+            COMPLETE_TASK => {
+                let task = tool_call.parameters.get("task")
+                    .and_then(|task| task.as_u64())
+                    .ok_or_else(|| anyhow!("Missing or invalid task parameter"))?;
+                let mut progress = self.progress.lock().unwrap();
+                progress.complete(task)?;
+                Ok(Message::assistant(&progress.summary()))
+            }
+            _ => Err(anyhow!("Unknown tool: {}", tool_call.name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::Role;
+
+    fn progress() -> PlanProgress {
+        PlanProgress::new(serde_yaml::from_str("kickoff: Go\ntasks: [First, Second]").unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_complete_task() {
+        let progress = Arc::new(Mutex::new(progress()));
+        let toolkit = PlanToolkit::new(Arc::clone(&progress));
+        let call = |task| Tool::new(COMPLETE_TASK, "", json!({"task": task}), vec![]);
+
+        let reply = toolkit.process_tool(&call(2)).await.unwrap();
+        assert_eq!(reply.text(), "1 of 2 tasks complete, remaining:\n1. First");
+        assert!(toolkit.process_tool(&call(3)).await.is_err());
+        assert!(toolkit.process_tool(&call(0)).await.is_err());
+
+        let reply = toolkit.process_tool(&call(1)).await.unwrap();
+        assert_eq!(reply.text(), "All 2 tasks of the plan are complete.");
+        assert!(progress.lock().unwrap().is_done(0));
+    }
+
+    #[test]
+    fn test_restore_progress() {
+        let call = |id: &str, task| Content::ToolUse {
+            id: id.to_string(),
+            name: COMPLETE_TASK.to_string(),
+            parameters: json!({"task": task}),
+        };
+        let result = |id: &str, is_error| Content::ToolResult {
+            tool_use_id: id.to_string(),
+            output: String::new(),
+            is_error,
+        };
+        let messages = vec![
+            Message::new(Role::Assistant, vec![call("a", 1), call("b", 2)]),
+            Message::new(Role::User, vec![result("a", true), result("b", false)]),
+        ];
+
+        let mut progress = progress();
+        progress.restore(&messages);
+        assert!(!progress.is_done(0));
+        assert!(progress.is_done(1));
+    }
+}
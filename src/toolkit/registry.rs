@@ -0,0 +1,182 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use anyhow::{anyhow, bail, Context, Result};
+use log::warn;
+
+use crate::models::Profile;
+use super::default::DefaultToolkit;
+use super::{Requirements, Tool, Toolkit};
+
+/// Builds a toolkit from the toolkits it requires, each session gets its own
+/// instance
+pub type ToolkitConstructor = fn(&Requirements) -> Result<Box<dyn Toolkit>>;
+
+struct ToolkitEntry {
+    description: String,
+    requires: Vec<String>,
+    constructor: ToolkitConstructor,
+}
+
+/// What `goose toolkit list` shows about a toolkit
+#[derive(Debug, Clone)]
+pub struct ToolkitInfo {
+    pub name: String,
+    pub description: String,
+    pub requires: Vec<String>,
+    pub tools: Vec<Tool>,
+}
+
+/// The toolkits profiles can refer to by name
+pub struct ToolkitRegistry {
+    toolkits: BTreeMap<String, ToolkitEntry>,
+}
+
+impl Default for ToolkitRegistry {
+    /// A registry of the toolkits that ship with goose
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register(
+            "default",
+            "Core tools for running commands, editing files and fetching web content",
+            &[],
+            |_| Ok(Box::new(DefaultToolkit::new())),
+        );
+        registry
+    }
+}
+
+impl ToolkitRegistry {
+    /// An empty registry
+    pub fn new() -> Self {
+        Self { toolkits: BTreeMap::new() }
+    }
+
+    /// Make a toolkit available under a name, replacing any registered
+    /// under the same name
+    ///
+    /// `requires` names the requirements the constructor looks up, which
+    /// profiles map to their toolkits.
+    pub fn register(&mut self, name: &str, description: &str, requires: &[&str], constructor: ToolkitConstructor) {
+        let entry = ToolkitEntry {
+            description: description.to_string(),
+            requires: requires.iter().map(|requirement| requirement.to_string()).collect(),
+            constructor,
+        };
+        self.toolkits.insert(name.to_string(), entry);
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.toolkits.contains_key(name)
+    }
+
+    /// The registered names, sorted
+    pub fn names(&self) -> Vec<&str> {
+        self.toolkits.keys().map(String::as_str).collect()
+    }
+
+    fn entry(&self, name: &str) -> Result<&ToolkitEntry> {
+        self.toolkits.get(name).ok_or_else(|| {
+            anyhow!("Unknown toolkit: {}, the available toolkits are {}", name, self.names().join(", "))
+        })
+    }
+
+    /// Build a toolkit on its own, each requirement gets the registered
+    /// toolkit of the same name
+    pub fn create(&self, name: &str) -> Result<Arc<dyn Toolkit>> {
+        self.create_standalone(name, &mut Vec::new())
+    }
+
+    fn create_standalone(&self, name: &str, visiting: &mut Vec<String>) -> Result<Arc<dyn Toolkit>> {
+        if visiting.iter().any(|visited| visited == name) {
+            visiting.push(name.to_string());
+            bail!("Toolkit requirements form a cycle: {}", visiting.join(" -> "));
+        }
+        let entry = self.entry(name)?;
+
+        visiting.push(name.to_string());
+        let mut requirements = Requirements::new(name.to_string());
+        for requirement in &entry.requires {
+            requirements.insert(requirement, self.create_standalone(requirement, visiting)?);
+        }
+        visiting.pop();
+
+        let toolkit = (entry.constructor)(&requirements)
+            .with_context(|| format!("Failed to create toolkit {}", name))?;
+        Ok(Arc::from(toolkit))
+    }
+
+    /// Build the toolkits a profile lists, in its order
+    ///
+    /// Toolkits are constructed after the toolkits they require, which they
+    /// share the instance of with the session.
+    pub fn create_for_profile(&self, profile: &Profile) -> Result<Vec<Arc<dyn Toolkit>>> {
+        let mut built: HashMap<&str, Arc<dyn Toolkit>> = HashMap::new();
+        for spec in profile.toolkit_order()? {
+            let entry = self.entry(&spec.name)?;
+            let mut requirements = Requirements::new(spec.name.clone());
+            for (requirement, provider) in &spec.requires {
+                requirements.insert(requirement, Arc::clone(&built[provider.as_str()]));
+            }
+            for requirement in &entry.requires {
+                requirements.require(requirement)?;
+            }
+
+            let toolkit = (entry.constructor)(&requirements)
+                .with_context(|| format!("Failed to create toolkit {}", spec.name))?;
+            built.insert(&spec.name, Arc::from(toolkit));
+        }
+        Ok(profile.toolkits.iter().map(|spec| Arc::clone(&built[spec.name.as_str()])).collect())
+    }
+
+    /// Describe every registered toolkit and its tools
+    ///
+    /// A toolkit that cannot be built on its own, because no toolkit is
+    /// registered under the name of a requirement, is listed without tools.
+    pub fn list(&self) -> Vec<ToolkitInfo> {
+        self.toolkits.iter()
+            .map(|(name, entry)| ToolkitInfo {
+                name: name.clone(),
+                description: entry.description.clone(),
+                requires: entry.requires.clone(),
+                tools: match self.create(name) {
+                    Ok(toolkit) => toolkit.tools(),
+                    Err(e) => {
+                        warn!("{:#}", e);
+                        Vec::new()
+                    }
+                },
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_registry() {
+        let registry = ToolkitRegistry::default();
+        assert_eq!(registry.names(), vec!["default"]);
+
+        let toolkits = registry.list();
+        assert!(toolkits[0].tools.iter().any(|tool| tool.name == "bash"));
+    }
+
+    #[test]
+    fn test_create_for_profile() {
+        let registry = ToolkitRegistry::default();
+        let profile: Profile = serde_yaml::from_str(r#"
+provider: openai
+processor: gpt-4o
+accelerator: gpt-4o-mini
+moderator: passive
+toolkits:
+  - name: default
+"#).unwrap();
+        assert_eq!(registry.create_for_profile(&profile).unwrap().len(), 1);
+
+        let error = registry.create("github").unwrap_err().to_string();
+        assert_eq!(error, "Unknown toolkit: github, the available toolkits are default");
+    }
+}
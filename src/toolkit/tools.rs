@@ -22,7 +22,7 @@ impl Tool {
     pub fn validate_parameters(&self, params: &Value) -> bool {
         // Check that all required parameters are present
         for req in &self.required {
-            if !params.get(req).is_some() {
+            if params.get(req).is_none() {
                 return false;
             }
         }
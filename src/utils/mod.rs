@@ -2,4 +2,14 @@ pub mod file_utils;
 pub mod name_generator;
 pub mod session_file;
 
-pub use name_generator::generate_name;
\ No newline at end of file
+pub use name_generator::{generate_name, slugify};
+
+/// The start of `text` on a single line, at most `max_chars` long
+pub fn snippet(text: &str, max_chars: usize) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if line.chars().count() <= max_chars {
+        line
+    } else {
+        format!("{}...", line.chars().take(max_chars).collect::<String>())
+    }
+}
\ No newline at end of file
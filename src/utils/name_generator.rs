@@ -15,6 +15,26 @@ pub fn generate_name() -> String {
     format!("{}{}{}{}", letter1, number1, letter2, number2)
 }
 
+/// Longest name [`slugify`] produces
+const MAX_SLUG_CHARS: usize = 48;
+
+/// Turn free text into a name usable in a file name: lowercase words joined
+/// by hyphens, e.g. "Fix the CI build!" becomes fix-the-ci-build
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for word in text.split(|c: char| !c.is_ascii_alphanumeric()).filter(|word| !word.is_empty()) {
+        if slug.len() + word.len() + 1 > MAX_SLUG_CHARS && !slug.is_empty() {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word.to_ascii_lowercase());
+    }
+    slug.truncate(MAX_SLUG_CHARS);
+    slug
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -31,4 +51,12 @@ mod tests {
         let pattern = Regex::new(r"^[a-z][0-9][a-z][0-9]$").unwrap();
         assert!(pattern.is_match(&name));
     }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Fix the CI build!"), "fix-the-ci-build");
+        assert_eq!(slugify("  \"refactor--session/files\"\n"), "refactor-session-files");
+        assert_eq!(slugify("..."), "");
+        assert!(slugify(&"word ".repeat(20)).len() <= MAX_SLUG_CHARS);
+    }
 }
\ No newline at end of file
@@ -1,12 +1,108 @@
-use std::path::Path;
-use anyhow::Result;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use log::{info, warn};
 use serde::{Serialize, Deserialize};
 
-pub const SESSION_FILE_SUFFIX: &str = ".session.jsonl";
+use crate::exchange::MessageObserver;
+use crate::models::{Message, Plan, Profile, Usage};
+use crate::models::message::{Content, Role};
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Message {
-    // TODO: Define message structure based on exchange.Message
+/// Result given to tool calls that never got one, because the session ended
+/// while they ran
+pub const INTERRUPTED_TOOL_OUTPUT: &str = "The tool call was interrupted before it produced a result.";
+
+pub const SESSION_FILE_SUFFIX: &str = ".jsonl";
+
+/// Identifies the header record on the first line of a session file
+pub const SESSION_FORMAT: &str = "goose-session";
+pub const SESSION_FORMAT_VERSION: u32 = 1;
+
+/// The first line of a session file, describing the session so tools can
+/// inspect it without reading the messages that follow
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionHeader {
+    pub format: String,
+    pub version: u32,
+    pub created: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub working_dir: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub processor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accelerator: Option<String>,
+    pub goose_version: String,
+    /// The first prompt of the session
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The plan the session follows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<Plan>,
+}
+
+impl Default for SessionHeader {
+    fn default() -> Self {
+        Self {
+            format: SESSION_FORMAT.to_string(),
+            version: SESSION_FORMAT_VERSION,
+            created: Utc::now(),
+            working_dir: std::env::current_dir().ok().map(|dir| dir.display().to_string()),
+            profile: None,
+            provider: None,
+            processor: None,
+            accelerator: None,
+            goose_version: env!("CARGO_PKG_VERSION").to_string(),
+            description: None,
+            plan: None,
+        }
+    }
+}
+
+impl SessionHeader {
+    /// A header for a session starting now with the named profile
+    pub fn for_profile(profile_name: &str, profile: &Profile) -> Self {
+        Self {
+            profile: Some(profile_name.to_string()),
+            provider: Some(profile.provider.clone()),
+            processor: Some(profile.processor.clone()),
+            accelerator: Some(profile.accelerator.clone()),
+            ..Default::default()
+        }
+    }
+
+    /// Parse the first line of a session file, `None` if it is a message
+    /// because the file predates headers
+    fn parse(line: &str) -> Result<Option<Self>> {
+        let Ok(record) = serde_json::from_str::<serde_json::Value>(line) else {
+            return Ok(None);
+        };
+        if record.get("format").and_then(|format| format.as_str()) != Some(SESSION_FORMAT) {
+            return Ok(None);
+        }
+        let header: SessionHeader = serde_json::from_value(record)?;
+        if header.version > SESSION_FORMAT_VERSION {
+            bail!(
+                "The session was written in format version {} by goose {}, this version reads up to {}",
+                header.version,
+                header.goose_version,
+                SESSION_FORMAT_VERSION
+            );
+        }
+        Ok(Some(header))
+    }
+
+    fn describe(&mut self, message: &Message) {
+        if self.description.is_none() && message.is_user() && message.tool_result().is_empty() {
+            self.description = Some(message.text());
+        }
+    }
 }
 
 pub fn is_existing_session(path: &Path) -> bool {
@@ -17,7 +113,7 @@ pub fn is_empty_session(path: &Path) -> bool {
     path.is_file() && path.metadata().map(|m| m.len() == 0).unwrap_or(false)
 }
 
-pub fn read_or_create_file(file_path: &Path) -> Result<Vec<crate::models::Message>> {
+pub fn read_or_create_file(file_path: &Path) -> Result<Vec<Message>> {
     if file_path.exists() {
         read_from_file(file_path)
     } else {
@@ -26,14 +122,67 @@ pub fn read_or_create_file(file_path: &Path) -> Result<Vec<crate::models::Messag
     }
 }
 
-pub fn read_from_file(file_path: &Path) -> Result<Vec<crate::models::Message>> {
+/// Read the messages of a session, skipping its header
+pub fn read_from_file(file_path: &Path) -> Result<Vec<Message>> {
+    Ok(read_session(file_path)?.1)
+}
+
+/// Read the header and messages of a session, files written before headers
+/// existed have none
+///
+/// A last line that does not parse was cut short by a crash while it was
+/// written and is skipped, anywhere else it means the file is corrupt.
+pub fn read_session(file_path: &Path) -> Result<(Option<SessionHeader>, Vec<Message>)> {
     let content = std::fs::read_to_string(file_path)?;
-    let messages = content
+    let mut lines: Vec<&str> = content
         .lines()
         .filter(|line| !line.trim().is_empty())
-        .map(|line| serde_json::from_str(line))
-        .collect::<Result<Vec<_>, _>>()?;
-    Ok(messages)
+        .collect();
+
+    let header = match lines.first() {
+        Some(line) => SessionHeader::parse(line)
+            .with_context(|| format!("Failed to read the header of {}", file_path.display()))?,
+        None => None,
+    };
+    if header.is_some() {
+        lines.remove(0);
+    }
+
+    let mut messages = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        let line_number = i + 1 + header.is_some() as usize;
+        match serde_json::from_str(line) {
+            Ok(message) => messages.push(message),
+            Err(e) if i == lines.len() - 1 => {
+                warn!("Ignoring the incomplete last message in {}: {}", file_path.display(), e);
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to parse line {} of {}", line_number, file_path.display()));
+            }
+        }
+    }
+    Ok((header, messages))
+}
+
+/// Read only the header of a session
+pub fn read_header(file_path: &Path) -> Result<Option<SessionHeader>> {
+    let mut first_line = String::new();
+    BufReader::new(std::fs::File::open(file_path)?).read_line(&mut first_line)?;
+    if first_line.trim().is_empty() {
+        return Ok(None);
+    }
+    SessionHeader::parse(&first_line)
+        .with_context(|| format!("Failed to read the header of {}", file_path.display()))
+}
+
+/// Count the messages of a session without parsing them
+pub fn count_messages(file_path: &Path) -> Result<usize> {
+    let lines = BufReader::new(std::fs::File::open(file_path)?)
+        .lines()
+        .filter(|line| line.as_ref().map_or(true, |line| !line.trim().is_empty()))
+        .count();
+    let header = read_header(file_path)?.is_some() as usize;
+    Ok(lines.saturating_sub(header))
 }
 
 pub fn list_sorted_session_files(session_files_directory: &Path) -> Result<Vec<(String, std::path::PathBuf)>> {
@@ -68,17 +217,515 @@ pub fn session_file_exists(session_files_directory: &Path) -> bool {
         .unwrap_or(false)
 }
 
-use std::io::Write;
+/// What changes as a session goes on, kept in a file next to the session
+/// because the session file itself is only ever appended to
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SessionMetadata {
+    /// Tokens used over every run of the session
+    #[serde(default)]
+    pub usage: Usage,
+}
+
+/// Where the metadata of the session at `file_path` is kept
+pub fn metadata_path(file_path: &Path) -> PathBuf {
+    file_path.with_extension("meta.json")
+}
+
+/// Read the metadata of a session, empty if none was written yet
+pub fn read_metadata(file_path: &Path) -> Result<SessionMetadata> {
+    let path = metadata_path(file_path);
+    match std::fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse session metadata {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SessionMetadata::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Replace the metadata of a session, moving a temporary file into place so
+/// it is never left half written
+pub fn write_metadata(file_path: &Path, metadata: &SessionMetadata) -> Result<()> {
+    let path = metadata_path(file_path);
+    let temp_path = path.with_extension("json.tmp");
+    std::fs::write(&temp_path, serde_json::to_vec(metadata)?)?;
+    std::fs::rename(&temp_path, &path)
+        .with_context(|| format!("Failed to replace session metadata {}", path.display()))
+}
+
+/// Add the tokens of a turn to the totals of a session
+pub fn add_session_usage(file_path: &Path, usage: &Usage) -> Result<()> {
+    let mut metadata = read_metadata(file_path)?;
+    metadata.usage += *usage;
+    write_metadata(file_path, &metadata)
+}
+
+/// A session file as `session list` and `session clear` see it
+#[derive(Debug, Clone)]
+pub struct SessionSummary {
+    pub name: String,
+    pub path: PathBuf,
+    pub modified: DateTime<Utc>,
+    /// `None` for sessions written before headers existed
+    pub header: Option<SessionHeader>,
+    pub metadata: SessionMetadata,
+    pub message_count: usize,
+    /// The first prompt of the session
+    pub description: Option<String>,
+}
+
+impl SessionSummary {
+    pub fn profile(&self) -> Option<&str> {
+        self.header.as_ref().and_then(|header| header.profile.as_deref())
+    }
+}
+
+/// Summarize every session in the directory, most recently modified first
+///
+/// Only the headers are parsed, sessions that predate headers are described
+/// from their messages instead.
+pub fn list_sessions(sessions_dir: &Path) -> Result<Vec<SessionSummary>> {
+    let mut sessions = Vec::new();
+    for path in list_session_files(sessions_dir)? {
+        let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+        let modified: DateTime<Utc> = path.metadata()?.modified()?.into();
+        let metadata = read_metadata(&path).unwrap_or_else(|e| {
+            warn!("{:#}", e);
+            SessionMetadata::default()
+        });
+        let header = read_header(&path).unwrap_or_else(|e| {
+            warn!("{:#}", e);
+            None
+        });
+        let (message_count, description) = match &header {
+            Some(header) => (count_messages(&path)?, header.description.clone()),
+            None => {
+                let messages = read_from_file(&path).unwrap_or_default();
+                let description = messages.iter()
+                    .find(|message| message.is_user() && message.tool_result().is_empty())
+                    .map(|message| message.text());
+                (messages.len(), description)
+            }
+        };
+        sessions.push(SessionSummary { name, path, modified, header, metadata, message_count, description });
+    }
+    sessions.sort_by_key(|session| std::cmp::Reverse(session.modified));
+    Ok(sessions)
+}
+
+/// Pick the sessions to delete from a newest first list, keeping the `keep`
+/// most recent and, with `older_than`, any modified more recently than that
+pub fn sessions_to_clear(
+    sessions: Vec<SessionSummary>,
+    keep: usize,
+    older_than: Option<Duration>,
+    now: DateTime<Utc>,
+) -> Vec<SessionSummary> {
+    sessions.into_iter()
+        .skip(keep)
+        .filter(|session| older_than.is_none_or(|age| session.modified < now - age))
+        .collect()
+}
+
+/// Delete a session along with its metadata
+pub fn delete_session(session: &SessionSummary) -> Result<()> {
+    std::fs::remove_file(&session.path)
+        .with_context(|| format!("Failed to delete {}", session.path.display()))?;
+    match std::fs::remove_file(metadata_path(&session.path)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
 
+/// Parse an age such as `30d`, `2w`, `12h` or `45m`
+pub fn parse_age(age: &str) -> Result<Duration> {
+    let invalid = || anyhow!("Invalid age: {}, expected a number followed by m, h, d or w", age);
+    let unit_start = age.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (amount, unit) = age.split_at(unit_start);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    match unit {
+        "m" => Ok(Duration::minutes(amount)),
+        "h" => Ok(Duration::hours(amount)),
+        "d" => Ok(Duration::days(amount)),
+        "w" => Ok(Duration::weeks(amount)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Append messages to a session, each on its own line
+///
+/// The lines are written in a single call and synced to disk before
+/// returning, so a crash can cut off at most the last line.
 pub fn log_messages(file_path: &Path, messages: &[Message]) -> Result<()> {
-    let mut file = std::fs::OpenOptions::new()
+    append_records(file_path, None, messages)
+}
+
+/// Append the header, if given, and the messages in a single write that is
+/// synced to disk before returning
+fn append_records(file_path: &Path, header: Option<&SessionHeader>, messages: &[Message]) -> Result<()> {
+    let mut buffer = Vec::new();
+    if let Some(header) = header {
+        serde_json::to_writer(&mut buffer, header)?;
+        buffer.push(b'\n');
+    }
+    for message in messages {
+        serde_json::to_writer(&mut buffer, message)?;
+        buffer.push(b'\n');
+    }
+
+    let mut file = OpenOptions::new()
         .append(true)
         .create(true)
-        .open(file_path)?;
-    
-    for message in messages {
-        serde_json::to_writer(&mut file, message)?;
-        writeln!(file)?;
+        .open(file_path)
+        .with_context(|| format!("Failed to open session file {}", file_path.display()))?;
+    file.write_all(&buffer)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// Replace the messages of a session, keeping its header
+pub fn write_messages(file_path: &Path, messages: &[Message]) -> Result<()> {
+    let header = if file_path.exists() { read_header(file_path)? } else { None };
+    write_session(file_path, header.as_ref(), messages)
+}
+
+/// Write a session to a temporary file that is then moved into place, so
+/// the session is never left half written
+fn write_session(file_path: &Path, header: Option<&SessionHeader>, messages: &[Message]) -> Result<()> {
+    let temp_path = file_path.with_extension("jsonl.tmp");
+    std::fs::write(&temp_path, "")?;
+    append_records(&temp_path, header, messages)?;
+    std::fs::rename(&temp_path, file_path)
+        .with_context(|| format!("Failed to replace session file {}", file_path.display()))?;
+    Ok(())
+}
+
+/// Add a header to a session written before headers existed, describing it
+/// with `header` and dating it to when the file was last modified
+pub fn migrate_session(file_path: &Path, mut header: SessionHeader) -> Result<()> {
+    let messages = read_from_file(file_path)?;
+    if let Ok(modified) = file_path.metadata().and_then(|metadata| metadata.modified()) {
+        header.created = modified.into();
+    }
+    if let Some(message) = messages.first() {
+        header.describe(message);
+    }
+    write_session(file_path, Some(&header), &messages)?;
+    info!("Added a format version {} header to {}", SESSION_FORMAT_VERSION, file_path.display());
+    Ok(())
+}
+
+/// Give every tool call a result, so a history saved while tools were
+/// running can be sent to a provider again
+///
+/// Missing results are added as errors to the user message following the
+/// call, or to a new one when the call has none. Returns whether anything
+/// had to be repaired.
+pub fn repair_tool_calls(messages: &mut Vec<Message>) -> bool {
+    let mut repaired = false;
+    let mut i = 0;
+    while i < messages.len() {
+        if !messages[i].is_assistant() || !messages[i].has_tool_use() {
+            i += 1;
+            continue;
+        }
+
+        let call_ids: Vec<String> = messages[i].tool_use().iter()
+            .filter_map(|content| match content {
+                Content::ToolUse { id, .. } => Some(id.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let has_results = messages.get(i + 1)
+            .is_some_and(|next| next.is_user() && !next.tool_result().is_empty());
+        if !has_results {
+            messages.insert(i + 1, Message::new(Role::User, Vec::new()));
+        }
+
+        let results = &mut messages[i + 1].content;
+        for id in call_ids {
+            let answered = results.iter()
+                .any(|content| matches!(content, Content::ToolResult { tool_use_id, .. } if *tool_use_id == id));
+            if !answered {
+                results.push(Content::ToolResult {
+                    tool_use_id: id,
+                    output: INTERRUPTED_TOOL_OUTPUT.to_string(),
+                    is_error: true,
+                });
+                repaired = true;
+            }
+        }
+        i += 2;
+    }
+    repaired
+}
+
+/// The length of a session file, to return to with [`truncate_session`]
+pub fn session_len(file_path: &Path) -> Result<u64> {
+    match std::fs::metadata(file_path) {
+        Ok(metadata) => Ok(metadata.len()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e.into()),
     }
+}
+
+/// Drop everything written to a session file after it was `len` bytes long
+pub fn truncate_session(file_path: &Path, len: u64) -> Result<()> {
+    let file = OpenOptions::new().write(true).open(file_path)?;
+    file.set_len(len)?;
+    file.sync_data()?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Move a session file along with its metadata, refusing to replace another
+/// session
+pub fn rename_session(file_path: &Path, new_path: &Path) -> Result<()> {
+    if new_path.exists() {
+        bail!("A session already exists at {}", new_path.display());
+    }
+    std::fs::rename(file_path, new_path)
+        .with_context(|| format!("Failed to move {} to {}", file_path.display(), new_path.display()))?;
+    if metadata_path(file_path).exists() {
+        std::fs::rename(metadata_path(file_path), metadata_path(new_path))?;
+    }
+    Ok(())
+}
+
+/// Appends each message to a session file as the exchange adds it
+pub struct SessionLog {
+    path: Mutex<PathBuf>,
+    /// Written along with the first message whenever the file is empty, so
+    /// it can describe the session by its first prompt
+    header: Mutex<SessionHeader>,
+}
+
+impl SessionLog {
+    /// Log to the session file at `path`, first dropping a last line left
+    /// incomplete by a crash so new messages start on a line of their own
+    ///
+    /// `header` starts a new session, and is added to an existing one that
+    /// was written before headers existed.
+    pub fn open(path: &Path, header: SessionHeader) -> Result<Self> {
+        let mut content = if path.exists() { std::fs::read(path)? } else { Vec::new() };
+        if !content.is_empty() && !content.ends_with(b"\n") {
+            let complete = content.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1);
+            warn!("Dropping the incomplete last line of {}", path.display());
+            truncate_session(path, complete as u64)?;
+            content.truncate(complete);
+        }
+
+        if !content.is_empty() && read_header(path)?.is_none() {
+            migrate_session(path, header.clone())?;
+        }
+        Ok(Self { path: Mutex::new(path.to_path_buf()), header: Mutex::new(header) })
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.path.lock().unwrap().clone()
+    }
+
+    /// Move the session and its metadata to `new_path` and log there from now on
+    pub fn rename(&self, new_path: &Path) -> Result<()> {
+        let mut path = self.path.lock().unwrap();
+        rename_session(&path, new_path)?;
+        *path = new_path.to_path_buf();
+        Ok(())
+    }
+}
+
+impl MessageObserver for SessionLog {
+    fn on_message(&self, message: &Message) -> Result<()> {
+        let path = self.path.lock().unwrap();
+        // A failed first turn is truncated away along with the header
+        if session_len(&path)? > 0 {
+            return log_messages(&path, std::slice::from_ref(message));
+        }
+
+        let mut header = self.header.lock().unwrap();
+        header.description = None;
+        header.describe(message);
+        append_records(&path, Some(&header), std::slice::from_ref(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_session() -> PathBuf {
+        std::env::temp_dir().join(format!("goose-session-{}{}", uuid::Uuid::new_v4(), SESSION_FILE_SUFFIX))
+    }
+
+    #[test]
+    fn test_log_and_read_messages() {
+        let path = temp_session();
+        let messages = vec![Message::user("Hello"), Message::assistant("Hi!")];
+        let log = SessionLog::open(&path, SessionHeader::default()).unwrap();
+        for message in &messages {
+            log.on_message(message).unwrap();
+        }
+        let (header, read) = read_session(&path).unwrap();
+        assert_eq!(read, messages);
+        assert_eq!(header.unwrap().description.as_deref(), Some("Hello"));
+        assert_eq!(count_messages(&path).unwrap(), 2);
+
+        let len = session_len(&path).unwrap();
+        log.on_message(&Message::user("Never mind")).unwrap();
+        truncate_session(&path, len).unwrap();
+        assert_eq!(read_from_file(&path).unwrap().len(), 2);
+
+        // The header is written again if a failed first turn removed it
+        truncate_session(&path, 0).unwrap();
+        log.on_message(&Message::user("Start over")).unwrap();
+        assert_eq!(read_header(&path).unwrap().unwrap().description.as_deref(), Some("Start over"));
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_truncated_last_line() {
+        let path = temp_session();
+        let messages = vec![Message::user("Hello"), Message::assistant("Hi!")];
+        log_messages(&path, &messages[..1]).unwrap();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"role":"assistant","content":[{"type":"te"#).unwrap();
+
+        assert_eq!(read_from_file(&path).unwrap(), messages[..1]);
+
+        // Reopening drops the partial line so the next message parses
+        let log = SessionLog::open(&path, SessionHeader::default()).unwrap();
+        log.on_message(&messages[1]).unwrap();
+        assert_eq!(read_from_file(&path).unwrap(), messages);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_repair_tool_calls() {
+        let tool_use = |id: &str| Content::ToolUse {
+            id: id.to_string(),
+            name: "bash".to_string(),
+            parameters: serde_json::json!({"command": "ls"}),
+        };
+        let mut messages = vec![
+            Message::user("List files"),
+            Message::new(Role::Assistant, vec![tool_use("call_1"), tool_use("call_2")]),
+            Message::new(Role::User, vec![Content::ToolResult {
+                tool_use_id: "call_1".to_string(),
+                output: "Cargo.toml".to_string(),
+                is_error: false,
+            }]),
+            Message::new(Role::Assistant, vec![tool_use("call_3")]),
+        ];
+
+        assert!(repair_tool_calls(&mut messages));
+        assert_eq!(messages.len(), 5);
+        assert_eq!(messages[2].tool_result().len(), 2);
+        match messages[4].tool_result().as_slice() {
+            [Content::ToolResult { tool_use_id, is_error: true, .. }] => assert_eq!(tool_use_id, "call_3"),
+            other => panic!("Expected an error result, got {:?}", other),
+        }
+
+        // A repaired history needs no further repair
+        assert!(!repair_tool_calls(&mut messages));
+    }
+
+    #[test]
+    fn test_migrate_headerless_session() {
+        let path = temp_session();
+        let messages = vec![Message::user("Old question"), Message::assistant("Old answer")];
+        log_messages(&path, &messages).unwrap();
+        assert!(read_header(&path).unwrap().is_none());
+
+        let profile = Profile::new(
+            "openai".to_string(),
+            "gpt-4o".to_string(),
+            "gpt-4o-mini".to_string(),
+            "passive".to_string(),
+            vec![],
+        );
+        SessionLog::open(&path, SessionHeader::for_profile("work", &profile)).unwrap();
+
+        let (header, read) = read_session(&path).unwrap();
+        let header = header.unwrap();
+        assert_eq!(header.version, SESSION_FORMAT_VERSION);
+        assert_eq!(header.profile.as_deref(), Some("work"));
+        assert_eq!(header.processor.as_deref(), Some("gpt-4o"));
+        assert_eq!(header.description.as_deref(), Some("Old question"));
+        assert_eq!(read, messages);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_newer_format_is_an_error() {
+        let path = temp_session();
+        let header = SessionHeader { version: SESSION_FORMAT_VERSION + 1, ..Default::default() };
+        append_records(&path, Some(&header), &[Message::user("Hello")]).unwrap();
+
+        let error = format!("{:#}", read_from_file(&path).unwrap_err());
+        assert!(error.contains("format version 2"), "{}", error);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_line_is_an_error() {
+        let path = temp_session();
+        std::fs::write(&path, "not json\n").unwrap();
+        log_messages(&path, &[Message::user("Hello")]).unwrap();
+        assert!(read_from_file(&path).is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("30d").unwrap(), Duration::days(30));
+        assert_eq!(parse_age("2w").unwrap(), Duration::weeks(2));
+        assert_eq!(parse_age("12h").unwrap(), Duration::hours(12));
+        for invalid in ["30", "d", "30y", "-1d", ""] {
+            assert!(parse_age(invalid).is_err(), "{} should not parse", invalid);
+        }
+    }
+
+    #[test]
+    fn test_list_and_clear_sessions() {
+        let dir = std::env::temp_dir().join(format!("goose-sessions-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // One session written by an earlier version, without a header
+        let old = dir.join("old.jsonl");
+        log_messages(&old, &[Message::user("First question"), Message::assistant("An answer")]).unwrap();
+        let new = dir.join("new.jsonl");
+        let header = SessionHeader { profile: Some("work".to_string()), ..Default::default() };
+        SessionLog::open(&new, header).unwrap().on_message(&Message::user("Second question")).unwrap();
+        add_session_usage(&new, &Usage::new(100, 20)).unwrap();
+        add_session_usage(&new, &Usage::new(150, 30)).unwrap();
+        let now = Utc::now();
+        std::fs::File::options().append(true).open(&old).unwrap()
+            .set_modified((now - Duration::days(40)).into()).unwrap();
+
+        let sessions = list_sessions(&dir).unwrap();
+        assert_eq!(sessions.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["new", "old"]);
+        assert_eq!(sessions[0].profile(), Some("work"));
+        assert_eq!(sessions[0].metadata.usage.total(), 300);
+        assert_eq!(sessions[0].message_count, 1);
+        assert_eq!(sessions[0].description.as_deref(), Some("Second question"));
+        assert!(sessions[1].header.is_none());
+        assert_eq!(sessions[1].metadata, SessionMetadata::default());
+        assert_eq!(sessions[1].message_count, 2);
+        assert_eq!(sessions[1].description.as_deref(), Some("First question"));
+
+        assert!(sessions_to_clear(sessions.clone(), 2, None, now).is_empty());
+        assert_eq!(sessions_to_clear(sessions.clone(), 0, Some(Duration::days(30)), now).len(), 1);
+        assert_eq!(sessions_to_clear(sessions.clone(), 0, None, now).len(), 2);
+
+        for session in sessions_to_clear(sessions, 0, None, now) {
+            delete_session(&session).unwrap();
+        }
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+use anyhow::Result;
+use futures::StreamExt;
+use rust_goose::exchange::{
+    AnthropicOptions, AnthropicProvider, Content, Exchange, Message, MessageAssembler, Provider, StreamEvent,
+};
+use rust_goose::models::Usage;
+use rust_goose::toolkit::default::DefaultToolkit;
+use serde_json::{json, Value};
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+// Helper to create a provider pointed at the mock server
+fn create_test_provider(server: &MockServer) -> AnthropicProvider {
+    let options = AnthropicOptions {
+        model: "claude-3-5-sonnet-latest".to_string(),
+        host: server.uri(),
+        ..Default::default()
+    };
+    AnthropicProvider::with_api_key("test-key".to_string(), Some(options))
+}
+
+fn request_body(request: &wiremock::Request) -> Value {
+    serde_json::from_slice(&request.body).unwrap()
+}
+
+#[tokio::test]
+async fn test_anthropic_conversation() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .and(header("x-api-key", "test-key"))
+        .and(header("anthropic-version", "2023-06-01"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": "msg_1",
+            "type": "message",
+            "role": "assistant",
+            "content": [{"type": "text", "text": "Hello there!"}],
+            "stop_reason": "end_turn",
+            "usage": {
+                "input_tokens": 12,
+                "output_tokens": 4,
+                "cache_read_input_tokens": 100,
+                "cache_creation_input_tokens": 20
+            }
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let provider = create_test_provider(&server);
+    let response = provider.generate(&[Message::user("Hello!")], None).await?;
+
+    assert_eq!(response.text(), "Hello there!");
+    assert_eq!(provider.get_token_usage(), Usage {
+        input_tokens: 12,
+        output_tokens: 4,
+        cache_read_tokens: 100,
+        cache_write_tokens: 20,
+    });
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_anthropic_tool_loop() -> Result<()> {
+    let server = MockServer::start().await;
+
+    // Once the tool result has been sent back, answer with text
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .and(|request: &wiremock::Request| {
+            request_body(request)["messages"].as_array().map(|m| m.len()) == Some(3)
+        })
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "content": [{"type": "text", "text": "The command printed hello"}],
+            "usage": {"input_tokens": 40, "output_tokens": 6}
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .and(|request: &wiremock::Request| {
+            request_body(request)["messages"].as_array().map(|m| m.len()) == Some(1)
+        })
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "content": [
+                {"type": "text", "text": "Running it now."},
+                {"type": "tool_use", "id": "toolu_1", "name": "bash", "input": {"command": "echo hello"}}
+            ],
+            "stop_reason": "tool_use",
+            "usage": {"input_tokens": 30, "output_tokens": 10}
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut exchange = Exchange::new(Box::new(create_test_provider(&server))).await?;
+    exchange.add_toolkit(Box::new(DefaultToolkit::new()));
+    exchange.add_message(Message::user("Run echo hello")).await?;
+
+    let response = exchange.reply().await?;
+    assert_eq!(response.text(), "The command printed hello");
+    assert_eq!(exchange.get_token_usage().await.total(), 86);
+
+    // Inspect what was sent in the follow up request
+    let requests = server.received_requests().await.unwrap();
+    let body = request_body(requests.last().unwrap());
+    assert!(body["system"].as_str().unwrap().contains("Default toolkit"));
+    assert_eq!(body["tools"][0]["name"], "bash");
+    assert!(body["tools"][0]["input_schema"].is_object());
+    assert_eq!(body["messages"][1]["content"][1]["type"], "tool_use");
+    let tool_result = &body["messages"][2]["content"][0];
+    assert_eq!(tool_result["type"], "tool_result");
+    assert_eq!(tool_result["tool_use_id"], "toolu_1");
+    assert_eq!(tool_result["content"].as_str().unwrap().trim(), "hello");
+    assert_eq!(tool_result["is_error"], false);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_anthropic_error_handling() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(json!({
+            "type": "error",
+            "error": {"type": "authentication_error", "message": "invalid x-api-key"}
+        })))
+        .mount(&server)
+        .await;
+
+    let provider = create_test_provider(&server);
+    let result = provider.generate(&[Message::user("Hello")], None).await;
+
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("invalid x-api-key"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_anthropic_streaming() -> Result<()> {
+    let events = [
+        ("message_start", json!({"type": "message_start", "message": {"usage": {"input_tokens": 25, "output_tokens": 1}}})),
+        ("content_block_start", json!({"type": "content_block_start", "index": 0, "content_block": {"type": "text", "text": ""}})),
+        ("content_block_delta", json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": "Looking"}})),
+        ("content_block_delta", json!({"type": "content_block_delta", "index": 0, "delta": {"type": "text_delta", "text": " now"}})),
+        ("content_block_stop", json!({"type": "content_block_stop", "index": 0})),
+        ("content_block_start", json!({"type": "content_block_start", "index": 1, "content_block": {"type": "tool_use", "id": "toolu_1", "name": "bash", "input": {}}})),
+        ("content_block_delta", json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "{\"command\": "}})),
+        ("content_block_delta", json!({"type": "content_block_delta", "index": 1, "delta": {"type": "input_json_delta", "partial_json": "\"pwd\"}"}})),
+        ("content_block_stop", json!({"type": "content_block_stop", "index": 1})),
+        ("message_delta", json!({"type": "message_delta", "delta": {"stop_reason": "tool_use"}, "usage": {"output_tokens": 15}})),
+        ("message_stop", json!({"type": "message_stop"})),
+    ];
+    let body: String = events.iter()
+        .map(|(event, data)| format!("event: {}\ndata: {}\n\n", event, data))
+        .collect();
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/messages"))
+        .and(|request: &wiremock::Request| request_body(request)["stream"] == true)
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let provider = create_test_provider(&server);
+    let mut stream = provider.stream(&[Message::user("Where am I?")], None).await?;
+    let mut assembler = MessageAssembler::new();
+    let mut deltas = Vec::new();
+    while let Some(event) = stream.next().await {
+        let event = event?;
+        if let StreamEvent::Text(text) = &event {
+            deltas.push(text.clone());
+        }
+        assembler.push(&event);
+    }
+
+    assert_eq!(deltas, vec!["Looking", " now"]);
+    assert_eq!(assembler.token_usage(), Usage::new(25, 15));
+    let message = assembler.finish()?;
+    assert_eq!(message.text(), "Looking now");
+    match message.tool_use().as_slice() {
+        [Content::ToolUse { id, parameters, .. }] => {
+            assert_eq!(id, "toolu_1");
+            assert_eq!(parameters["command"], "pwd");
+        }
+        other => panic!("Expected a single tool use, got {:?}", other),
+    }
+
+    Ok(())
+}
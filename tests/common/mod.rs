@@ -0,0 +1,35 @@
+//! Helpers shared by the integration tests, each test crate uses some of them
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// The fake MCP server in `tests/support`, built the first time a test asks
+/// for it since cargo only builds examples for an unfiltered `cargo test`
+pub fn fake_mcp_server() -> String {
+    static SERVER: OnceLock<PathBuf> = OnceLock::new();
+    let server = SERVER.get_or_init(|| {
+        let mut build = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()));
+        build.args(["build", "--quiet", "--example", "fake-mcp-server"])
+            .current_dir(env!("CARGO_MANIFEST_DIR"));
+        if !cfg!(debug_assertions) {
+            build.arg("--release");
+        }
+        assert!(build.status().expect("cargo runs").success(), "Failed to build the fake MCP server");
+
+        // Test binaries live in the deps directory next to the examples one
+        let exe = std::env::current_exe().expect("the test binary has a path");
+        let profile_dir = exe.parent().and_then(|deps| deps.parent()).expect("tests run from the target directory");
+        profile_dir.join("examples").join(format!("fake-mcp-server{}", std::env::consts::EXE_SUFFIX))
+    });
+    server.display().to_string()
+}
+
+/// Whether OPENAI_API_KEY holds a key, tests that call the real API skip without one
+pub fn has_valid_api_key() -> bool {
+    match std::env::var("OPENAI_API_KEY") {
+        Ok(key) => !key.is_empty() && key != "invalid_key",
+        Err(_) => false,
+    }
+}
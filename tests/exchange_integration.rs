@@ -0,0 +1,332 @@
+use std::sync::{Arc, Mutex};
+use anyhow::Result;
+use rust_goose::exchange::{create_moderator, Exchange, Provider, ProviderError, StreamEvent, ToolRoundLimitExceeded};
+use rust_goose::models::message::{Content, Role};
+use rust_goose::models::{Message, Usage};
+use rust_goose::toolkit::{Tool, Toolkit};
+use serde_json::json;
+
+// Provider that replays a fixed script of responses and records what it was sent
+struct ScriptedProvider {
+    model: String,
+    responses: Mutex<Vec<Message>>,
+    requests: Arc<Mutex<Vec<Vec<Message>>>>,
+}
+
+type Requests = Arc<Mutex<Vec<Vec<Message>>>>;
+
+fn scripted_provider(model: &str, responses: Vec<Message>) -> (ScriptedProvider, Requests) {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let provider = ScriptedProvider {
+        model: model.to_string(),
+        responses: Mutex::new(responses),
+        requests: requests.clone(),
+    };
+    (provider, requests)
+}
+
+#[async_trait::async_trait]
+impl Provider for ScriptedProvider {
+    async fn initialize(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn generate(&self, messages: &[Message], _tools: Option<Vec<Tool>>) -> Result<Message> {
+        self.requests.lock().unwrap().push(messages.to_vec());
+        let mut responses = self.responses.lock().unwrap();
+        if responses.is_empty() {
+            anyhow::bail!("No scripted responses left");
+        }
+        Ok(responses.remove(0))
+    }
+
+    fn get_token_usage(&self) -> Usage {
+        Usage::new(6, 4)
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+}
+
+#[derive(Debug)]
+struct EchoToolkit;
+
+#[async_trait::async_trait]
+impl Toolkit for EchoToolkit {
+    fn tools(&self) -> Vec<Tool> {
+        vec![Tool::new(
+            "echo",
+            "Echo the given text",
+            json!({
+                "type": "object",
+                "properties": {"text": {"type": "string"}},
+                "required": ["text"]
+            }),
+            vec!["text".to_string()],
+        )]
+    }
+
+    async fn process_tool(&self, tool_call: &Tool) -> Result<Message> {
+        let text = tool_call.parameters["text"].as_str()
+            .ok_or_else(|| anyhow::anyhow!("Missing text parameter"))?;
+        Ok(Message::assistant(text))
+    }
+}
+
+fn tool_use(id: &str, name: &str, parameters: serde_json::Value) -> Message {
+    Message::new(
+        Role::Assistant,
+        vec![Content::ToolUse {
+            id: id.to_string(),
+            name: name.to_string(),
+            parameters,
+        }],
+    )
+}
+
+async fn scripted_exchange(responses: Vec<Message>) -> Result<(Exchange, Requests)> {
+    let (provider, requests) = scripted_provider("processor", responses);
+    let mut exchange = Exchange::new(Box::new(provider)).await?;
+    exchange.add_toolkit(Box::new(EchoToolkit));
+    Ok((exchange, requests))
+}
+
+#[tokio::test]
+async fn test_reply_runs_tools_until_final_answer() -> Result<()> {
+    let (exchange, requests) = scripted_exchange(vec![
+        tool_use("call_1", "echo", json!({"text": "hello"})),
+        Message::assistant("The tool said hello"),
+    ]).await?;
+
+    exchange.add_message(Message::user("Say hello with the echo tool")).await?;
+    let response = exchange.reply().await?;
+    assert_eq!(response.text(), "The tool said hello");
+
+    // user, assistant tool use, user tool result, assistant answer
+    let messages = exchange.get_messages().await;
+    assert_eq!(messages.len(), 4);
+    match messages[2].content.as_slice() {
+        [Content::ToolResult { tool_use_id, output, is_error }] => {
+            assert_eq!(tool_use_id, "call_1");
+            assert_eq!(output, "hello");
+            assert!(!is_error);
+        }
+        other => panic!("Expected a single tool result, got {:?}", other),
+    }
+
+    assert_eq!(exchange.get_token_usage().await.total(), 20);
+
+    // The second request must include the tool call and its result
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[1].len(), 3);
+    assert!(requests[1][1].has_tool_use());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reply_reports_tool_errors_to_the_model() -> Result<()> {
+    let (exchange, _) = scripted_exchange(vec![
+        tool_use("call_1", "echo", json!({})),
+        tool_use("call_2", "missing_tool", json!({})),
+        Message::assistant("Giving up"),
+    ]).await?;
+
+    exchange.add_message(Message::user("Break things")).await?;
+    let response = exchange.reply().await?;
+    assert_eq!(response.text(), "Giving up");
+
+    let messages = exchange.get_messages().await;
+    for index in [2, 4] {
+        match messages[index].content.as_slice() {
+            [Content::ToolResult { is_error, .. }] => assert!(is_error),
+            other => panic!("Expected an error tool result, got {:?}", other),
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reply_stops_after_the_maximum_tool_rounds() -> Result<()> {
+    let (mut exchange, requests) = scripted_exchange(
+        (1..=5).map(|i| tool_use(&format!("call_{}", i), "echo", json!({"text": "again"}))).collect(),
+    ).await?;
+    exchange.set_max_tool_rounds(2);
+
+    exchange.add_message(Message::user("Keep echoing")).await?;
+    let error = exchange.reply().await.unwrap_err();
+    assert_eq!(error.downcast_ref::<ToolRoundLimitExceeded>(), Some(&ToolRoundLimitExceeded { max_rounds: 2 }));
+
+    // Two rounds of tools ran, the third request's tool call was not
+    assert_eq!(requests.lock().unwrap().len(), 3);
+    assert_eq!(exchange.get_messages().await.iter().filter(|m| !m.tool_result().is_empty()).count(), 2);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reply_streaming_falls_back_to_generate() -> Result<()> {
+    let (exchange, _) = scripted_exchange(vec![
+        tool_use("call_1", "echo", json!({"text": "streamed"})),
+        Message::assistant("All done"),
+    ]).await?;
+
+    exchange.add_message(Message::user("Echo something")).await?;
+    let mut events = Vec::new();
+    let response = exchange.reply_streaming(|event| events.push(event.clone())).await?;
+
+    assert_eq!(response.text(), "All done");
+    assert_eq!(exchange.get_messages().await.len(), 4);
+    assert!(matches!(&events[0], StreamEvent::ToolCall { name: Some(name), .. } if name == "echo"));
+    assert!(events.contains(&StreamEvent::Text("All done".to_string())));
+    assert_eq!(exchange.get_token_usage().await.total(), 20);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_auxiliary_tasks_use_the_accelerator() -> Result<()> {
+    let long_output = "line of build output\n".repeat(1000);
+    let (mut exchange, processor_requests) = scripted_exchange(vec![
+        tool_use("call_1", "echo", json!({"text": long_output})),
+        Message::assistant("The build passed"),
+    ]).await?;
+    let (accelerator, accelerator_requests) = scripted_provider("accelerator", vec![
+        Message::assistant("build output, all passing"),
+        Message::assistant("The user asked for a build and it passed."),
+    ]);
+    exchange.set_accelerator(Box::new(accelerator)).await?;
+
+    exchange.add_message(Message::user("Run the build")).await?;
+    exchange.reply().await?;
+
+    // The long tool output was condensed before the processor saw it
+    let processor_requests = processor_requests.lock().unwrap().clone();
+    let tool_results = processor_requests[1].last().unwrap().tool_result();
+    assert!(matches!(tool_results[0], Content::ToolResult { output, .. } if output == "build output, all passing"));
+
+    let summary = exchange.summarize(&exchange.get_messages().await).await?;
+    assert_eq!(summary, "The user asked for a build and it passed.");
+    let accelerator_requests = accelerator_requests.lock().unwrap().clone();
+    assert!(accelerator_requests[1][0].is_system());
+    assert!(accelerator_requests[1][1].text().contains("user: Run the build"));
+
+    let usage = exchange.get_model_usage().await;
+    assert_eq!(usage["processor"], Usage::new(12, 8));
+    assert_eq!(usage["accelerator"], Usage::new(12, 8));
+    assert_eq!(exchange.get_token_usage().await.total(), 40);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_auxiliary_tasks_fall_back_to_the_processor() -> Result<()> {
+    let (exchange, _) = scripted_exchange(vec![Message::assistant("A short summary")]).await?;
+
+    let summary = exchange.summarize(&[Message::user("Hello")]).await?;
+    assert_eq!(summary, "A short summary");
+    assert_eq!(exchange.get_model_usage().await["processor"].total(), 10);
+    // Auxiliary responses are not added to the history
+    assert!(exchange.get_messages().await.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sessions_are_named_by_the_accelerator() -> Result<()> {
+    let (mut exchange, processor_requests) = scripted_exchange(vec![]).await?;
+    let (accelerator, accelerator_requests) = scripted_provider("accelerator", vec![
+        Message::assistant("Fix the failing CI build!"),
+    ]);
+    exchange.set_accelerator(Box::new(accelerator)).await?;
+
+    let name = exchange.name_session("The build on main fails since yesterday, can you look?").await?;
+    assert_eq!(name, "fix-the-failing-ci-build");
+    assert!(accelerator_requests.lock().unwrap()[0][1].text().contains("The build on main fails"));
+    assert!(processor_requests.lock().unwrap().is_empty());
+    assert_eq!(exchange.get_model_usage().await["accelerator"].total(), 10);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_summarize_moderator_compresses_old_turns() -> Result<()> {
+    let (mut exchange, requests) = scripted_exchange(vec![Message::assistant("Sure, continuing")]).await?;
+    let (accelerator, accelerator_requests) = scripted_provider("accelerator", vec![
+        Message::assistant("The user set up a Rust project."),
+    ]);
+    exchange.set_accelerator(Box::new(accelerator)).await?;
+    exchange.set_moderator(create_moderator("summarize")?);
+    exchange.set_context_limit(4000);
+
+    let long_text = "details ".repeat(1000);
+    for message in [
+        Message::user(&long_text),
+        Message::assistant(&long_text),
+        Message::user("Now add a test"),
+    ] {
+        exchange.add_message(message).await?;
+    }
+    exchange.reply().await?;
+
+    // The first turn went to the accelerator and was replaced by its summary
+    let accelerator_requests = accelerator_requests.lock().unwrap().clone();
+    assert_eq!(accelerator_requests.len(), 1);
+    assert!(accelerator_requests[0][1].text().contains("details"));
+
+    let sent = requests.lock().unwrap()[0].clone();
+    assert_eq!(sent.len(), 1);
+    assert!(sent[0].text().starts_with("Summary of the conversation so far:\nThe user set up a Rust project."));
+    assert!(sent[0].text().ends_with("Now add a test"));
+
+    let history = exchange.get_messages().await;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1].text(), "Sure, continuing");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_truncate_moderator_drops_old_turns() -> Result<()> {
+    let (mut exchange, requests) = scripted_exchange(vec![Message::assistant("Done")]).await?;
+    exchange.set_moderator(create_moderator("truncate")?);
+    exchange.set_context_limit(2000);
+
+    let long_text = "details ".repeat(1000);
+    exchange.add_message(Message::user(&long_text)).await?;
+    exchange.add_message(Message::assistant("Noted")).await?;
+    exchange.add_message(Message::user("What next?")).await?;
+    exchange.reply().await?;
+
+    let sent = requests.lock().unwrap()[0].clone();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].text(), "What next?");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_request_over_the_context_window_is_not_sent() -> Result<()> {
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let provider = ScriptedProvider {
+        model: "gpt-4".to_string(),
+        responses: Mutex::new(vec![Message::assistant("unreachable")]),
+        requests: requests.clone(),
+    };
+    let exchange = Exchange::new(Box::new(provider)).await?;
+
+    // gpt-4 has an 8k context window in the catalog
+    exchange.add_message(Message::user(&"lorem ipsum ".repeat(10_000))).await?;
+    let error = exchange.reply().await.unwrap_err();
+
+    match error.downcast_ref::<ProviderError>() {
+        Some(ProviderError::ContextLengthExceeded(message)) => assert!(message.contains("context window of 8192")),
+        other => panic!("Expected a context length error, got {:?}", other),
+    }
+    assert!(requests.lock().unwrap().is_empty());
+
+    Ok(())
+}
@@ -0,0 +1,219 @@
+mod common;
+
+use std::collections::HashMap;
+use anyhow::Result;
+use rust_goose::models::{McpHttpTransport, McpServerConfig};
+use rust_goose::toolkit::mcp::McpToolkit;
+use rust_goose::toolkit::{Tool, Toolkit};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use wiremock::matchers::{body_partial_json, header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn fake_server() -> McpServerConfig {
+    McpServerConfig {
+        command: Some(common::fake_mcp_server()),
+        timeout_secs: 10,
+        ..Default::default()
+    }
+}
+
+fn call(name: &str, parameters: Value) -> Tool {
+    Tool::new(name, "", parameters, vec![])
+}
+
+#[tokio::test]
+async fn test_stdio_server_tools() -> Result<()> {
+    std::env::set_var("GOOSE_MCP_TEST_TOKEN", "secret");
+    let mut config = fake_server();
+    config.env = HashMap::from([("TOKEN".to_string(), "${GOOSE_MCP_TEST_TOKEN}".to_string())]);
+
+    let toolkit = McpToolkit::connect("fake", &config).await?;
+    assert_eq!(toolkit.system(), "Use echo to repeat text back.");
+
+    // Both pages of the tool list, with the schemas as parameters
+    let tools = toolkit.tools();
+    let names: Vec<_> = tools.iter().map(|tool| tool.name.as_str()).collect();
+    assert_eq!(names, vec!["echo", "fail", "env"]);
+    assert_eq!(tools[0].required, vec!["text"]);
+    assert_eq!(tools[0].parameters["properties"]["text"]["type"], "string");
+
+    // Each call answers the server's ping before getting its result
+    let reply = toolkit.process_tool(&call("echo", json!({"text": "Hello"}))).await?;
+    assert_eq!(reply.text(), "Hello");
+    let reply = toolkit.process_tool(&call("env", json!({"name": "TOKEN"}))).await?;
+    assert_eq!(reply.text(), "secret");
+
+    let error = toolkit.process_tool(&call("fail", json!({}))).await.unwrap_err();
+    assert_eq!(error.to_string(), "The tool failed on purpose");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stdio_server_that_fails_to_start() {
+    let mut config = fake_server();
+    config.command = Some("goose-no-such-mcp-server".to_string());
+    assert!(McpToolkit::connect("missing", &config).await.is_err());
+
+    // A program that exits without answering
+    config.command = Some("true".to_string());
+    let error = McpToolkit::connect("silent", &config).await.unwrap_err();
+    assert_eq!(error.to_string(), "The MCP server closed the connection");
+}
+
+#[tokio::test]
+async fn test_streamable_http_server_tools() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/mcp"))
+        .and(body_partial_json(json!({"method": "initialize"})))
+        .and(header("authorization", "Bearer secret"))
+        .respond_with(ResponseTemplate::new(200)
+            .insert_header("Mcp-Session-Id", "session-1")
+            .set_body_json(json!({"jsonrpc": "2.0", "id": 1, "result": {
+                "protocolVersion": "2025-03-26",
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "remote", "version": "1.0.0"}
+            }})))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({"method": "notifications/initialized"})))
+        .and(header("mcp-session-id", "session-1"))
+        .respond_with(ResponseTemplate::new(202))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({"method": "tools/list"})))
+        .and(header("mcp-session-id", "session-1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"jsonrpc": "2.0", "id": 2, "result": {
+            "tools": [{"name": "lookup", "description": "Look something up", "inputSchema": {"type": "object"}}]
+        }})))
+        .mount(&server)
+        .await;
+    // The call is answered on an event stream, after a ping from the server
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({"method": "tools/call", "params": {"name": "lookup"}})))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            format!(
+                "event: message\ndata: {}\n\nevent: message\ndata: {}\n\n",
+                json!({"jsonrpc": "2.0", "id": "ping-1", "method": "ping"}),
+                json!({"jsonrpc": "2.0", "id": 3, "result": {"content": [{"type": "text", "text": "Found it"}]}}),
+            ),
+            "text/event-stream",
+        ))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(json!({"id": "ping-1", "result": {}})))
+        .respond_with(ResponseTemplate::new(202))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    std::env::set_var("GOOSE_MCP_TEST_HTTP_TOKEN", "secret");
+    let config = McpServerConfig {
+        url: Some(format!("{}/mcp", server.uri())),
+        headers: HashMap::from([("Authorization".to_string(), "Bearer $GOOSE_MCP_TEST_HTTP_TOKEN".to_string())]),
+        timeout_secs: 10,
+        ..Default::default()
+    };
+    let toolkit = McpToolkit::connect("remote", &config).await?;
+    assert_eq!(toolkit.tools()[0].name, "lookup");
+    assert_eq!(toolkit.system(), "");
+
+    let reply = toolkit.process_tool(&call("lookup", json!({}))).await?;
+    assert_eq!(reply.text(), "Found it");
+    Ok(())
+}
+
+/// Read an HTTP request, returning its first line and body
+async fn read_request(stream: &mut tokio::net::TcpStream) -> Result<(String, String)> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    let mut content_length = 0;
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).await?;
+        if line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse()?;
+            }
+        }
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+    Ok((request_line, String::from_utf8(body)?))
+}
+
+/// Serve MCP on the HTTP transport of the 2024-11-05 protocol, answering
+/// every POSTed request on the event stream
+async fn serve_legacy_sse(listener: TcpListener) -> Result<()> {
+    let (mut events, _) = listener.accept().await?;
+    let (request_line, _) = read_request(&mut events).await?;
+    assert!(request_line.starts_with("GET /sse "));
+    events.write_all(b"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\nconnection: close\r\n\r\n").await?;
+    events.write_all(b"event: endpoint\ndata: /messages?session=1\n\n").await?;
+
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let (request_line, body) = read_request(&mut stream).await?;
+        assert!(request_line.starts_with("POST /messages?session=1 "));
+        stream.write_all(b"HTTP/1.1 202 Accepted\r\ncontent-length: 0\r\nconnection: close\r\n\r\n").await?;
+
+        let request: Value = serde_json::from_str(&body)?;
+        let result = match request["method"].as_str() {
+            Some("initialize") => json!({"protocolVersion": "2024-11-05", "capabilities": {}, "instructions": "Legacy"}),
+            Some("tools/list") => json!({"tools": [{"name": "time", "inputSchema": {"type": "object"}}]}),
+            Some("tools/call") => json!({"content": [{"type": "text", "text": "noon"}]}),
+            _ => continue,
+        };
+        let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": result});
+        events.write_all(format!("event: message\ndata: {}\n\n", response).as_bytes()).await?;
+    }
+}
+
+#[tokio::test]
+async fn test_legacy_sse_server_tools() -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}/sse", listener.local_addr()?);
+    let server = tokio::spawn(serve_legacy_sse(listener));
+
+    let config = McpServerConfig {
+        url: Some(url),
+        transport: McpHttpTransport::Sse,
+        timeout_secs: 10,
+        ..Default::default()
+    };
+    let toolkit = McpToolkit::connect("legacy", &config).await?;
+    assert_eq!(toolkit.system(), "Legacy");
+    assert_eq!(toolkit.tools()[0].name, "time");
+    assert_eq!(toolkit.process_tool(&call("time", json!({}))).await?.text(), "noon");
+
+    server.abort();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_goose_serves_its_toolkits() -> Result<()> {
+    let config = McpServerConfig {
+        command: Some(env!("CARGO_BIN_EXE_rust-goose").to_string()),
+        args: vec!["mcp".to_string(), "serve".to_string()],
+        timeout_secs: 30,
+        ..Default::default()
+    };
+    let toolkit = McpToolkit::connect("goose", &config).await?;
+    let names: Vec<_> = toolkit.tools().into_iter().map(|tool| tool.name).collect();
+    assert!(names.contains(&"bash".to_string()) && names.contains(&"text_editor".to_string()), "{:?}", names);
+
+    let reply = toolkit.process_tool(&call("bash", json!({"command": "echo served"}))).await?;
+    assert!(reply.text().contains("served"), "{}", reply.text());
+    assert!(toolkit.process_tool(&call("bash", json!({}))).await.is_err());
+    Ok(())
+}
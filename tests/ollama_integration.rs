@@ -0,0 +1,124 @@
+use anyhow::Result;
+use rust_goose::exchange::{Exchange, Message, OllamaOptions, OllamaProvider, Provider};
+use rust_goose::models::Usage;
+use rust_goose::toolkit::default::DefaultToolkit;
+use serde_json::{json, Value};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+// Helper to create a provider pointed at the stub server
+fn create_test_provider(server: &MockServer) -> Result<OllamaProvider> {
+    let options = OllamaOptions {
+        model: "llama3.1".to_string(),
+        host: server.uri(),
+        ..Default::default()
+    };
+    OllamaProvider::new(Some(options))
+}
+
+fn request_body(request: &wiremock::Request) -> Value {
+    serde_json::from_slice(&request.body).unwrap()
+}
+
+#[tokio::test]
+async fn test_ollama_conversation() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "model": "llama3.1",
+            "message": {"role": "assistant", "content": "Hi from a local model"},
+            "done": true,
+            "prompt_eval_count": 20,
+            "eval_count": 5
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let provider = create_test_provider(&server)?;
+    let response = provider.generate(&[Message::user("Hello!")], None).await?;
+
+    assert_eq!(response.text(), "Hi from a local model");
+    assert_eq!(provider.get_token_usage(), Usage::new(20, 5));
+
+    let requests = server.received_requests().await.unwrap();
+    let body = request_body(&requests[0]);
+    assert_eq!(body["model"], "llama3.1");
+    assert_eq!(body["stream"], false);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ollama_tool_loop() -> Result<()> {
+    let server = MockServer::start().await;
+
+    // Once the tool result has been sent back, answer with text
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .and(|request: &wiremock::Request| {
+            request_body(request)["messages"].as_array()
+                .map(|m| m.iter().any(|m| m["role"] == "tool"))
+                .unwrap_or(false)
+        })
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "message": {"role": "assistant", "content": "Done"},
+            "done": true
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "message": {
+                "role": "assistant",
+                "content": "",
+                "tool_calls": [{"function": {"name": "bash", "arguments": {"command": "echo offline"}}}]
+            },
+            "done": true
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let mut exchange = Exchange::new(Box::new(create_test_provider(&server)?)).await?;
+    exchange.add_toolkit(Box::new(DefaultToolkit::new()));
+    exchange.add_message(Message::user("Run echo offline")).await?;
+
+    let response = exchange.reply().await?;
+    assert_eq!(response.text(), "Done");
+
+    let requests = server.received_requests().await.unwrap();
+    let body = request_body(requests.last().unwrap());
+    assert_eq!(body["tools"][0]["function"]["name"], "bash");
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages[0]["role"], "system");
+    assert_eq!(messages[2]["tool_calls"][0]["function"]["arguments"]["command"], "echo offline");
+    assert_eq!(messages[3]["role"], "tool");
+    assert_eq!(messages[3]["content"].as_str().unwrap().trim(), "offline");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_ollama_error_handling() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/chat"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+            "error": "model \"missing\" not found, try pulling it first"
+        })))
+        .mount(&server)
+        .await;
+
+    let provider = create_test_provider(&server)?;
+    let result = provider.generate(&[Message::user("Hello")], None).await;
+
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("not found"));
+
+    Ok(())
+}
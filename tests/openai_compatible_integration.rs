@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use anyhow::Result;
+use rust_goose::exchange::{create_provider, Content, Message, OpenAIOptions, OpenAIProvider, Provider, StreamEvent};
+use rust_goose::models::{EndpointConfig, Profile, Usage};
+use serde_json::json;
+use wiremock::matchers::{header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn chat_completion(text: &str) -> serde_json::Value {
+    json!({
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "created": 1700000000,
+        "model": "served-model",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": text},
+            "finish_reason": "stop"
+        }],
+        "usage": {"prompt_tokens": 9, "completion_tokens": 3, "total_tokens": 12}
+    })
+}
+
+fn has_header(request: &wiremock::Request, name: &str) -> bool {
+    request.headers.iter().any(|(header, _)| header.as_str().eq_ignore_ascii_case(name))
+}
+
+#[tokio::test]
+async fn test_compatible_endpoint_headers_and_query() -> Result<()> {
+    std::env::set_var("GOOSE_TEST_COMPATIBLE_KEY", "secret-key");
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(header("authorization", "Bearer secret-key"))
+        .and(header("openai-organization", "org-goose"))
+        .and(header("x-title", "goose"))
+        .and(query_param("api-version", "2024-02-01"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(chat_completion("Hello from a proxy")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let endpoint = EndpointConfig {
+        base_url: Some(format!("{}/v1/", server.uri())),
+        api_key_env: Some("GOOSE_TEST_COMPATIBLE_KEY".to_string()),
+        organization: Some("org-goose".to_string()),
+        api_version: Some("2024-02-01".to_string()),
+        headers: HashMap::from([("X-Title".to_string(), "goose".to_string())]),
+        ..Default::default()
+    };
+    let options = OpenAIOptions {
+        model: "served-model".to_string(),
+        ..Default::default()
+    };
+    let provider = OpenAIProvider::from_endpoint(&endpoint, Some(options))?;
+
+    let response = provider.generate(&[Message::user("Hello!")], None).await?;
+    assert_eq!(response.text(), "Hello from a proxy");
+    assert_eq!(provider.get_token_usage(), Usage::new(9, 3));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_usage_is_not_carried_over_between_requests() -> Result<()> {
+    let server = MockServer::start().await;
+    let mut without_usage = chat_completion("Hello again");
+    without_usage.as_object_mut().unwrap().remove("usage");
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(chat_completion("Hello")))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(without_usage))
+        .mount(&server)
+        .await;
+
+    let endpoint = EndpointConfig {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..Default::default()
+    };
+    let provider = OpenAIProvider::from_endpoint(&endpoint, None)?;
+
+    provider.generate(&[Message::user("Hello!")], None).await?;
+    assert_eq!(provider.get_token_usage(), Usage::new(9, 3));
+
+    let response = provider.generate(&[Message::user("Hello!")], None).await?;
+    assert_eq!(response.text(), "Hello again");
+    assert_eq!(provider.get_token_usage(), Usage::default());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_azure_style_api_key_header() -> Result<()> {
+    std::env::set_var("GOOSE_TEST_AZURE_KEY", "azure-key");
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/openai/deployments/gpt4/chat/completions"))
+        .and(header("api-key", "azure-key"))
+        .and(query_param("api-version", "2024-02-01"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(chat_completion("Hello from Azure")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let endpoint = EndpointConfig {
+        base_url: Some(format!("{}/openai/deployments/gpt4", server.uri())),
+        api_key_env: Some("GOOSE_TEST_AZURE_KEY".to_string()),
+        api_key_header: Some("api-key".to_string()),
+        api_version: Some("2024-02-01".to_string()),
+        ..Default::default()
+    };
+    let provider = OpenAIProvider::from_endpoint(&endpoint, None)?;
+
+    let response = provider.generate(&[Message::user("Hello!")], None).await?;
+    assert_eq!(response.text(), "Hello from Azure");
+
+    let requests = server.received_requests().await.unwrap();
+    assert!(!has_header(&requests[0], "authorization"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_create_provider_from_profile_endpoints() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(chat_completion("Hello from vLLM")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let yaml = format!(r#"
+provider: local-vllm
+processor: served-model
+accelerator: served-model
+moderator: passive
+toolkits: []
+endpoints:
+  local-vllm:
+    base_url: {}/v1
+  groq:
+    base_url: https://api.groq.com/openai/v1
+    api_key_env: GROQ_API_KEY
+"#, server.uri());
+    let profile: Profile = serde_yaml::from_str(&yaml)?;
+    assert_eq!(profile.endpoints.len(), 2);
+
+    let provider = create_provider(&profile.provider, &profile.processor, &profile.endpoints)?;
+    let response = provider.generate(&[Message::user("Hello!")], None).await?;
+    assert_eq!(response.text(), "Hello from vLLM");
+
+    // No API key is sent when the endpoint does not name one
+    let requests = server.received_requests().await.unwrap();
+    assert!(!has_header(&requests[0], "authorization"));
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body)?;
+    assert_eq!(body["model"], "served-model");
+
+    // Endpoints that name a missing API key variable fail clearly
+    std::env::remove_var("GROQ_API_KEY");
+    let error = create_provider("groq", &profile.processor, &profile.endpoints).err().unwrap();
+    assert!(error.to_string().contains("GROQ_API_KEY"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_streaming_text_and_tool_calls() -> Result<()> {
+    let chunks = [
+        json!({"choices": [{"index": 0, "delta": {"role": "assistant", "content": "Let me "}}]}),
+        json!({"choices": [{"index": 0, "delta": {"content": "check."}}]}),
+        json!({"choices": [{"index": 0, "delta": {"tool_calls": [
+            {"index": 0, "id": "call_1", "type": "function", "function": {"name": "bash", "arguments": ""}}
+        ]}}]}),
+        json!({"choices": [{"index": 0, "delta": {"tool_calls": [
+            {"index": 0, "function": {"arguments": "{\"command\":"}}
+        ]}}]}),
+        json!({"choices": [{"index": 0, "delta": {"tool_calls": [
+            {"index": 0, "function": {"arguments": " \"ls\"}"}}
+        ]}, "finish_reason": "tool_calls"}]}),
+        json!({"choices": [], "usage": {
+            "prompt_tokens": 20,
+            "completion_tokens": 10,
+            "total_tokens": 30,
+            "prompt_tokens_details": {"cached_tokens": 8}
+        }}),
+    ];
+    let mut body: String = chunks.iter().map(|chunk| format!("data: {}\n\n", chunk)).collect();
+    body.push_str("data: [DONE]\n\n");
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(|request: &wiremock::Request| {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            body["stream"] == true && body["stream_options"]["include_usage"] == true
+        })
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "text/event-stream"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let endpoint = EndpointConfig {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..Default::default()
+    };
+    let provider = OpenAIProvider::from_endpoint(&endpoint, None)?;
+    let exchange = rust_goose::exchange::Exchange::new(Box::new(provider)).await?;
+
+    let mut text = String::new();
+    let response = exchange
+        .generate_streaming(&[Message::user("List files")], None, &mut |event| {
+            if let StreamEvent::Text(delta) = event {
+                text.push_str(delta);
+            }
+        })
+        .await?;
+
+    assert_eq!(text, "Let me check.");
+    assert_eq!(response.text(), "Let me check.");
+    match response.tool_use().as_slice() {
+        [Content::ToolUse { id, name, parameters }] => {
+            assert_eq!(id, "call_1");
+            assert_eq!(name, "bash");
+            assert_eq!(parameters["command"], "ls");
+        }
+        other => panic!("Expected a single tool use, got {:?}", other),
+    }
+    // Cached prompt tokens are billed separately from the rest of the input
+    assert_eq!(exchange.get_token_usage().await, Usage {
+        cache_read_tokens: 8,
+        ..Usage::new(12, 10)
+    });
+
+    Ok(())
+}
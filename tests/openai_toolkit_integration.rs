@@ -1,3 +1,5 @@
+mod common;
+
 use anyhow::Result;
 use dotenv::dotenv;
 use rust_goose::{
@@ -50,6 +52,12 @@ async fn create_test_provider() -> Result<OpenAIProvider> {
 
 #[tokio::test]
 async fn test_openai_toolkit_basic() -> Result<()> {
+    dotenv().ok();
+    if !common::has_valid_api_key() {
+        println!("Skipping test_openai_toolkit_basic - no valid API key found");
+        return Ok(());
+    }
+
     // Create a test tool using bash which is supported
     let tool = Tool::new(
         "bash",
@@ -81,13 +89,19 @@ async fn test_openai_toolkit_basic() -> Result<()> {
     let response = provider.generate(&messages, Some(toolkit.tools())).await?;
     
     // Response should either be a tool call or contain content
-    assert!(!response.text().is_empty());
+    assert!(!response.text().is_empty() || response.has_tool_use());
     
     Ok(())
 }
 
 #[tokio::test]
 async fn test_openai_toolkit_multiple_tools() -> Result<()> {
+    dotenv().ok();
+    if !common::has_valid_api_key() {
+        println!("Skipping test_openai_toolkit_multiple_tools - no valid API key found");
+        return Ok(());
+    }
+
     // Create multiple test tools
     let tools = vec![
         Tool::new(
@@ -136,13 +150,19 @@ async fn test_openai_toolkit_multiple_tools() -> Result<()> {
     ];
     
     let response = provider.generate(&messages, Some(toolkit.tools())).await?;
-    assert!(!response.text().is_empty());
+    assert!(!response.text().is_empty() || response.has_tool_use());
     
     Ok(())
 }
 
 #[tokio::test]
 async fn test_openai_toolkit_error_handling() -> Result<()> {
+    dotenv().ok();
+    if !common::has_valid_api_key() {
+        println!("Skipping test_openai_toolkit_error_handling - no valid API key found");
+        return Ok(());
+    }
+
     // Create a tool with required parameters
     let tool = Tool::new(
         "validate",
@@ -178,8 +198,8 @@ async fn test_openai_toolkit_error_handling() -> Result<()> {
     
     let response = provider.generate(&messages, Some(toolkit.tools())).await?;
     
-    // Response should indicate parameter validation or contain an error message
-    assert!(!response.text().is_empty());
+    // Response should indicate parameter validation, contain an error message or call the tool
+    assert!(!response.text().is_empty() || response.has_tool_use());
     
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,173 @@
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use rust_goose::exchange::{Message, OpenAIProvider, Provider, ProviderError, RetryProvider};
+use rust_goose::models::{EndpointConfig, Profile, RetryConfig, Usage};
+use serde_json::json;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn fast_retries() -> RetryConfig {
+    RetryConfig {
+        max_retries: 3,
+        initial_delay_ms: 1,
+        max_delay_ms: 10,
+        backoff_multiplier: 2.0,
+    }
+}
+
+fn create_test_provider(server: &MockServer, config: RetryConfig) -> Result<RetryProvider> {
+    let endpoint = EndpointConfig {
+        base_url: Some(format!("{}/v1", server.uri())),
+        ..Default::default()
+    };
+    let provider = OpenAIProvider::from_endpoint(&endpoint, None)?;
+    Ok(RetryProvider::new(Box::new(provider), config))
+}
+
+fn api_error(message: &str, code: &str) -> serde_json::Value {
+    json!({"error": {"message": message, "type": "invalid_request_error", "code": code}})
+}
+
+#[tokio::test]
+async fn test_rate_limit_is_retried_after_requested_delay() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(429)
+            .insert_header("retry-after-ms", "200")
+            .set_body_json(api_error("Rate limit reached", "rate_limit_exceeded")))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": "gpt-4",
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": "Made it"}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7}
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let config = RetryConfig { max_delay_ms: 1000, ..fast_retries() };
+    let provider = create_test_provider(&server, config)?;
+    let start = Instant::now();
+    let response = provider.generate(&[Message::user("Hello")], None).await?;
+
+    assert_eq!(response.text(), "Made it");
+    assert_eq!(provider.get_token_usage(), Usage::new(5, 2));
+    // The header asks for longer than the backoff would wait
+    assert!(start.elapsed() >= Duration::from_millis(200));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rate_limit_longer_than_the_maximum_delay_is_not_retried() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(429)
+            .insert_header("retry-after", "86400")
+            .set_body_json(api_error("Daily limit reached", "rate_limit_exceeded")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let provider = create_test_provider(&server, fast_retries())?;
+    let start = Instant::now();
+    let error = provider.generate(&[Message::user("Hello")], None).await.unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<ProviderError>(),
+        Some(ProviderError::RateLimited { retry_after: Some(delay), .. }) if *delay == Duration::from_secs(86400)
+    ));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_server_errors_exhaust_retries() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(503).set_body_string("upstream unavailable"))
+        .expect(4)
+        .mount(&server)
+        .await;
+
+    let provider = create_test_provider(&server, fast_retries())?;
+    let error = provider.generate(&[Message::user("Hello")], None).await.unwrap_err();
+
+    match error.downcast_ref::<ProviderError>() {
+        Some(ProviderError::Server { status, message }) => {
+            assert_eq!(*status, 503);
+            assert_eq!(message, "upstream unavailable");
+        }
+        other => panic!("Expected a server error, got {:?}", other),
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_auth_and_context_errors_are_not_retried() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(401).set_body_json(api_error("Incorrect API key provided", "invalid_api_key")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let provider = create_test_provider(&server, fast_retries())?;
+    let error = provider.stream(&[Message::user("Hello")], None).await.err().unwrap();
+    assert!(matches!(error.downcast_ref::<ProviderError>(), Some(ProviderError::Auth(_))));
+    assert!(error.to_string().contains("Incorrect API key provided"));
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(api_error(
+            "This model's maximum context length is 8192 tokens",
+            "context_length_exceeded",
+        )))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let provider = create_test_provider(&server, fast_retries())?;
+    let error = provider.generate(&[Message::user("Hello")], None).await.unwrap_err();
+    assert!(matches!(error.downcast_ref::<ProviderError>(), Some(ProviderError::ContextLengthExceeded(_))));
+
+    Ok(())
+}
+
+#[test]
+fn test_retry_config_in_profile() -> Result<()> {
+    let profile: Profile = serde_yaml::from_str(r#"
+provider: openai
+processor: gpt-4o
+accelerator: gpt-4o-mini
+moderator: passive
+toolkits: []
+retry:
+  max_retries: 2
+  initial_delay_ms: 500
+"#)?;
+
+    assert_eq!(profile.retry.max_retries, 2);
+    assert_eq!(profile.retry.initial_delay_ms, 500);
+    assert_eq!(profile.retry.max_delay_ms, RetryConfig::default().max_delay_ms);
+
+    // Defaults are left out when the profile is written back
+    let defaults = Profile::new("openai".into(), "gpt-4o".into(), "gpt-4o-mini".into(), "passive".into(), vec![]);
+    assert!(!serde_yaml::to_string(&defaults)?.contains("retry"));
+
+    Ok(())
+}
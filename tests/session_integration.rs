@@ -1,3 +1,5 @@
+mod common;
+
 use std::sync::Once;
 use anyhow::Result;
 use rust_goose::cli::session::Session;
@@ -16,6 +18,11 @@ fn setup() {
 async fn test_session_start_basic() -> Result<()> {
     setup();
 
+    if !common::has_valid_api_key() {
+        println!("Skipping test_session_start_basic - no valid API key found");
+        return Ok(());
+    }
+
     // Create a new session with default settings
     let mut session = Session::new(
         Some("test_session".to_string()),
@@ -41,8 +48,13 @@ async fn test_session_start_basic() -> Result<()> {
 async fn test_session_start_with_profile() -> Result<()> {
     setup();
 
+    if !common::has_valid_api_key() {
+        println!("Skipping test_session_start_with_profile - no valid API key found");
+        return Ok(());
+    }
+
     // Create a new session with a specific profile
-    let mut session = Session::new(
+    let session = Session::new(
         Some("test_session_profile".to_string()),
         Some("default".to_string()),
         None,
@@ -60,6 +72,11 @@ async fn test_session_start_with_profile() -> Result<()> {
 async fn test_session_interruption() -> Result<()> {
     setup();
 
+    if !common::has_valid_api_key() {
+        println!("Skipping test_session_interruption - no valid API key found");
+        return Ok(());
+    }
+
     let mut session = Session::new(
         Some("test_session_interrupt".to_string()),
         None,
@@ -82,4 +99,920 @@ async fn test_session_interruption() -> Result<()> {
     assert!(!session.is_interrupted());
 
     Ok(())
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_session_uses_profile_provider_and_model() -> Result<()> {
+    use rust_goose::models::Profile;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(|request: &wiremock::Request| {
+            let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            body["model"] == "profile-model"
+        })
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            format!(
+                "data: {}\n\ndata: {}\n\ndata: [DONE]\n\n",
+                json!({"choices": [{"index": 0, "delta": {"content": "Hi from the profile"}}]}),
+                json!({"choices": [], "usage": {"prompt_tokens": 4, "completion_tokens": 4, "total_tokens": 8}}),
+            ),
+            "text/event-stream",
+        ))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let profile: Profile = serde_yaml::from_str(&format!(r#"
+provider: local
+processor: profile-model
+accelerator: profile-model
+moderator: passive
+toolkits:
+  - name: default
+endpoints:
+  local:
+    base_url: {}/v1
+"#, server.uri()))?;
+
+    let mut session = Session::with_profile(
+        Some(format!("test_session_profile_{}", uuid::Uuid::new_v4())),
+        "local".to_string(),
+        profile,
+        None,
+        None,
+        false,
+    ).await?;
+    let tools = session.exchange.as_ref().unwrap().tools();
+    assert!(tools.iter().any(|tool| tool.name == "bash"));
+
+    session.process_message(Message::user("Hello!")).await?;
+    assert_eq!(session.messages.last().unwrap().text(), "Hi from the profile");
+    assert_eq!(session.get_stats().total_tokens, 8);
+
+    std::fs::remove_file(&session.session_file_path).ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_session_logs_messages_as_they_are_added() -> Result<()> {
+    use rust_goose::models::Profile;
+    use rust_goose::utils::session_file::{count_messages, metadata_path, read_from_file, read_header};
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            format!(
+                "data: {}\n\ndata: [DONE]\n\n",
+                json!({"choices": [{"index": 0, "delta": {"content": "Logged"}}]}),
+            ),
+            "text/event-stream",
+        ))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({"error": {"message": "bad request"}})))
+        .mount(&server)
+        .await;
+
+    let profile: Profile = serde_yaml::from_str(&format!(r#"
+provider: local
+processor: profile-model
+accelerator: none
+moderator: passive
+toolkits: []
+endpoints:
+  local:
+    base_url: {}/v1
+"#, server.uri()))?;
+
+    let mut session = Session::with_profile(
+        Some(format!("test_session_log_{}", uuid::Uuid::new_v4())),
+        "local".to_string(),
+        profile,
+        None,
+        None,
+        false,
+    ).await?;
+
+    session.process_message(Message::user("Hello!")).await?;
+    let logged = read_from_file(&session.session_file_path)?;
+    assert_eq!(logged, session.messages);
+    assert_eq!(logged.iter().map(|m| m.text()).collect::<Vec<_>>(), vec!["Hello!", "Logged"]);
+
+    // A failed turn is dropped from the file along with the history
+    assert!(session.process_message(Message::user("Again")).await.is_err());
+    assert_eq!(read_from_file(&session.session_file_path)?, logged);
+
+    let header = read_header(&session.session_file_path)?.unwrap();
+    assert_eq!(header.profile.as_deref(), Some("local"));
+    assert_eq!(header.processor.as_deref(), Some("profile-model"));
+    assert_eq!(header.description.as_deref(), Some("Hello!"));
+    assert_eq!(count_messages(&session.session_file_path)?, 2);
+
+    std::fs::remove_file(&session.session_file_path).ok();
+    std::fs::remove_file(metadata_path(&session.session_file_path)).ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_single_pass_reports_how_the_run_ended() -> Result<()> {
+    use rust_goose::cli::session::RunOutcome;
+    use rust_goose::models::Profile;
+    use rust_goose::stats::StatsStore;
+    use rust_goose::utils::session_file::read_from_file;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            format!(
+                "data: {}\n\ndata: [DONE]\n\n",
+                json!({"choices": [{"index": 0, "delta": {"content": "Done"}}]}),
+            ),
+            "text/event-stream",
+        ))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({"error": {"message": "bad request"}})))
+        .mount(&server)
+        .await;
+
+    let profile: Profile = serde_yaml::from_str(&format!(r#"
+provider: local
+processor: profile-model
+accelerator: none
+moderator: passive
+toolkits: []
+endpoints:
+  local:
+    base_url: {}/v1
+"#, server.uri()))?;
+
+    let mut session = Session::with_profile(
+        Some(format!("test_session_run_{}", uuid::Uuid::new_v4())),
+        "local".to_string(),
+        profile,
+        None,
+        None,
+        false,
+    ).await?;
+    let stats_dir = std::env::temp_dir().join(format!("goose-run-stats-{}", uuid::Uuid::new_v4()));
+    session.stats_store = StatsStore::new(stats_dir.join("stats.jsonl"));
+
+    assert_eq!(session.single_pass("Do the task").await?, RunOutcome::Completed);
+    let texts: Vec<_> = read_from_file(&session.session_file_path)?.iter().map(|m| m.text()).collect();
+    assert_eq!(texts, vec!["Do the task", "Done"]);
+
+    assert_eq!(session.single_pass("Do it again").await?, RunOutcome::Failed);
+    assert_eq!(read_from_file(&session.session_file_path)?.len(), 2);
+    assert_eq!(session.single_pass("  \n").await?, RunOutcome::InvalidInput);
+
+    // Every completed or failed run is in the stats
+    let saved = session.stats_store.load()?;
+    assert_eq!(saved.len(), 2);
+    assert_eq!(saved[0].total_messages, 1);
+    assert_eq!(saved[1].session_id, session.name);
+
+    std::fs::remove_file(&session.session_file_path).ok();
+    std::fs::remove_dir_all(stats_dir).ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_single_pass_streams_json_events() -> Result<()> {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use rust_goose::cli::output::JsonRenderer;
+    use rust_goose::models::Profile;
+    use rust_goose::stats::StatsStore;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let sse = |chunks: &[serde_json::Value]| {
+        let mut body: String = chunks.iter().map(|chunk| format!("data: {}\n\n", chunk)).collect();
+        body.push_str("data: [DONE]\n\n");
+        ResponseTemplate::new(200).set_body_raw(body, "text/event-stream")
+    };
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(sse(&[
+            json!({"choices": [{"index": 0, "delta": {"tool_calls": [
+                {"index": 0, "id": "call_1", "type": "function", "function": {"name": "bash", "arguments": "{\"command\": \"ls\"}"}}
+            ]}}]}),
+            json!({"choices": [], "usage": {"prompt_tokens": 20, "completion_tokens": 10, "total_tokens": 30}}),
+        ]))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(sse(&[json!({"choices": [{"index": 0, "delta": {"content": "All done"}}]})]))
+        .mount(&server)
+        .await;
+
+    let profile: Profile = serde_yaml::from_str(&format!(r#"
+provider: local
+processor: profile-model
+accelerator: none
+moderator: passive
+toolkits: []
+endpoints:
+  local:
+    base_url: {}/v1
+"#, server.uri()))?;
+
+    let mut session = Session::with_profile(
+        Some(format!("test_session_json_{}", uuid::Uuid::new_v4())),
+        "local".to_string(),
+        profile,
+        None,
+        None,
+        false,
+    ).await?;
+    let stats_dir = std::env::temp_dir().join(format!("goose-json-stats-{}", uuid::Uuid::new_v4()));
+    session.stats_store = StatsStore::new(stats_dir.join("stats.jsonl"));
+    let buffer = Buffer::default();
+    *session.renderer.lock().unwrap() = Box::new(JsonRenderer::new(Box::new(buffer.clone()), true));
+
+    session.single_pass("List the files").await?;
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone())?;
+    let events: Vec<serde_json::Value> = output.lines().map(serde_json::from_str).collect::<Result<_, _>>()?;
+    let types: Vec<&str> = events.iter().map(|event| event["type"].as_str().unwrap()).collect();
+    assert_eq!(types, vec![
+        "session_start", "user_message", "tool_call", "tool_result", "assistant_text", "usage", "result",
+    ]);
+    assert_eq!(events[2]["parameters"]["command"], "ls");
+    // No toolkit provides bash in this profile
+    assert_eq!(events[3]["is_error"], true);
+    assert_eq!(events[5]["model"], "profile-model");
+    assert_eq!(events[5]["input_tokens"], 20);
+    assert_eq!(events[6]["outcome"], "completed");
+    assert_eq!(events[6]["exit_code"], 0);
+    assert_eq!(events[6]["text"], "All done");
+    assert_eq!(events[6]["tool_calls"], 1);
+
+    std::fs::remove_file(&session.session_file_path).ok();
+    std::fs::remove_dir_all(stats_dir).ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_new_session_is_named_after_its_first_prompt() -> Result<()> {
+    use rust_goose::models::Profile;
+    use rust_goose::stats::StatsStore;
+    use rust_goose::utils::session_file::{metadata_path, read_from_file};
+    use serde_json::json;
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(json!({"model": "profile-model"})))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            format!(
+                "data: {}\n\ndata: [DONE]\n\n",
+                json!({"choices": [{"index": 0, "delta": {"content": "Done"}}]}),
+            ),
+            "text/event-stream",
+        ))
+        .mount(&server)
+        .await;
+    let suffix = uuid::Uuid::new_v4().simple().to_string();
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .and(body_partial_json(json!({"model": "naming-model"})))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": "naming-model",
+            "choices": [{"index": 0, "message": {"role": "assistant", "content": format!("Tidy build {}", suffix)}, "finish_reason": "stop"}],
+            "usage": {"prompt_tokens": 30, "completion_tokens": 4, "total_tokens": 34}
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let profile: Profile = serde_yaml::from_str(&format!(r#"
+provider: local
+processor: profile-model
+accelerator: naming-model
+moderator: passive
+toolkits: []
+endpoints:
+  local:
+    base_url: {}/v1
+"#, server.uri()))?;
+
+    let mut session = Session::with_profile(None, "local".to_string(), profile, None, None, false).await?;
+    let stats_dir = std::env::temp_dir().join(format!("goose-naming-stats-{}", uuid::Uuid::new_v4()));
+    session.stats_store = StatsStore::new(stats_dir.join("stats.jsonl"));
+    let random_path = session.session_file_path.clone();
+
+    session.single_pass("Tidy up the build scripts").await?;
+    assert_eq!(session.name, format!("tidy-build-{}", suffix));
+    assert!(session.session_file_path.ends_with(format!("tidy-build-{}.jsonl", suffix)));
+    assert!(!random_path.exists());
+    assert_eq!(read_from_file(&session.session_file_path)?.len(), 2);
+    assert_eq!(session.stats_store.load()?[0].session_id, session.name);
+
+    // Later turns keep the name and log to the renamed file
+    session.single_pass("Thanks").await?;
+    assert_eq!(session.name, format!("tidy-build-{}", suffix));
+    assert_eq!(read_from_file(&session.session_file_path)?.len(), 4);
+
+    std::fs::remove_file(&session.session_file_path).ok();
+    std::fs::remove_file(metadata_path(&session.session_file_path)).ok();
+    std::fs::remove_dir_all(stats_dir).ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_plan_progress_is_tracked_and_resumed() -> Result<()> {
+    use rust_goose::models::{Plan, Profile};
+    use rust_goose::utils::session_file::read_header;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            format!(
+                "data: {}\n\ndata: [DONE]\n\n",
+                json!({"choices": [{"index": 0, "delta": {"tool_calls": [
+                    {"index": 0, "id": "call_1", "type": "function", "function": {"name": "complete_task", "arguments": "{\"task\": 1}"}}
+                ]}}]}),
+            ),
+            "text/event-stream",
+        ))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            format!(
+                "data: {}\n\ndata: [DONE]\n\n",
+                json!({"choices": [{"index": 0, "delta": {"content": "The first task is done"}}]}),
+            ),
+            "text/event-stream",
+        ))
+        .mount(&server)
+        .await;
+
+    let profile: Profile = serde_yaml::from_str(&format!(r#"
+provider: local
+processor: profile-model
+accelerator: none
+moderator: passive
+toolkits: []
+endpoints:
+  local:
+    base_url: {}/v1
+"#, server.uri()))?;
+    let plan: Plan = serde_yaml::from_str("kickoff: Tidy up\ntasks: [Remove dead code, Update the docs]")?;
+
+    let name = format!("test_session_plan_{}", uuid::Uuid::new_v4());
+    let mut session = Session::with_profile(
+        Some(name.clone()),
+        "local".to_string(),
+        profile.clone(),
+        Some(plan.clone()),
+        None,
+        false,
+    ).await?;
+    session.process_message(Message::user(&plan.kickoff_message())).await?;
+    assert_eq!(session.plan_progress.as_ref().unwrap().lock().unwrap().completed(), 1);
+    assert_eq!(read_header(&session.session_file_path)?.unwrap().plan, Some(plan.clone()));
+
+    // The plan comes back with the session, along with its progress
+    let resumed = Session::with_profile(Some(name.clone()), "local".to_string(), profile.clone(), None, None, false).await?;
+    let progress = resumed.plan_progress.as_ref().unwrap().lock().unwrap().clone();
+    assert!(progress.is_done(0));
+    assert!(!progress.is_done(1));
+    assert!(resumed.exchange.as_ref().unwrap().tools().iter().any(|tool| tool.name == "complete_task"));
+
+    // but a plan cannot be added to a session that already started
+    assert!(Session::with_profile(Some(name), "local".to_string(), profile, Some(plan), None, false).await.is_err());
+
+    std::fs::remove_file(&session.session_file_path).ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_plan_progress_is_rolled_back_with_a_failed_turn() -> Result<()> {
+    use rust_goose::models::{Plan, Profile};
+    use rust_goose::utils::session_file::read_from_file;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // The task is completed, then the request after it fails
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            format!(
+                "data: {}\n\ndata: [DONE]\n\n",
+                json!({"choices": [{"index": 0, "delta": {"tool_calls": [
+                    {"index": 0, "id": "call_1", "type": "function", "function": {"name": "complete_task", "arguments": "{\"task\": 1}"}}
+                ]}}]}),
+            ),
+            "text/event-stream",
+        ))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({"error": {"message": "bad request"}})))
+        .mount(&server)
+        .await;
+
+    let profile: Profile = serde_yaml::from_str(&format!(r#"
+provider: local
+processor: profile-model
+accelerator: none
+moderator: passive
+toolkits: []
+endpoints:
+  local:
+    base_url: {}/v1
+"#, server.uri()))?;
+    let plan: Plan = serde_yaml::from_str("kickoff: Tidy up\ntasks: [Remove dead code, Update the docs]")?;
+
+    let name = format!("test_session_plan_rollback_{}", uuid::Uuid::new_v4());
+    let mut session = Session::with_profile(Some(name), "local".to_string(), profile, Some(plan.clone()), None, false).await?;
+    assert!(session.process_message(Message::user(&plan.kickoff_message())).await.is_err());
+
+    // The progress agrees with the history the turn was rolled back to
+    assert!(session.messages.is_empty());
+    assert!(read_from_file(&session.session_file_path)?.is_empty());
+    assert_eq!(session.plan_progress.as_ref().unwrap().lock().unwrap().completed(), 0);
+
+    std::fs::remove_file(&session.session_file_path).ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_session_uses_mcp_server_tools() -> Result<()> {
+    use rust_goose::models::Profile;
+    use rust_goose::utils::session_file::read_from_file;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            format!(
+                "data: {}\n\ndata: [DONE]\n\n",
+                json!({"choices": [{"index": 0, "delta": {"tool_calls": [
+                    {"index": 0, "id": "call_1", "type": "function", "function": {"name": "echo", "arguments": "{\"text\": \"from mcp\"}"}}
+                ]}}]}),
+            ),
+            "text/event-stream",
+        ))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            format!(
+                "data: {}\n\ndata: [DONE]\n\n",
+                json!({"choices": [{"index": 0, "delta": {"content": "It echoed"}}]}),
+            ),
+            "text/event-stream",
+        ))
+        .mount(&server)
+        .await;
+
+    let profile: Profile = serde_yaml::from_str(&format!(r#"
+provider: local
+processor: profile-model
+accelerator: none
+moderator: passive
+toolkits: []
+endpoints:
+  local:
+    base_url: {}/v1
+mcp_servers:
+  fake:
+    command: {}
+"#, server.uri(), common::fake_mcp_server()))?;
+
+    let mut session = Session::with_profile(
+        Some(format!("test_session_mcp_{}", uuid::Uuid::new_v4())),
+        "local".to_string(),
+        profile,
+        None,
+        None,
+        false,
+    ).await?;
+    assert!(session.exchange.as_ref().unwrap().tools().iter().any(|tool| tool.name == "echo"));
+
+    session.process_message(Message::user("Echo something")).await?;
+    let messages = read_from_file(&session.session_file_path)?;
+    let echoed = messages.iter().flat_map(|message| message.tool_result()).any(|result| matches!(
+        result,
+        rust_goose::exchange::Content::ToolResult { output, is_error: false, .. } if output == "from mcp"
+    ));
+    assert!(echoed, "{:?}", messages);
+
+    std::fs::remove_file(&session.session_file_path).ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_session_rejects_clashing_tool_names() -> Result<()> {
+    let profile: rust_goose::models::Profile = serde_yaml::from_str(&format!(r#"
+provider: local
+processor: profile-model
+accelerator: none
+moderator: passive
+toolkits: []
+endpoints:
+  local:
+    base_url: http://127.0.0.1:9/v1
+mcp_servers:
+  first:
+    command: {server}
+  second:
+    command: {server}
+"#, server = common::fake_mcp_server()))?;
+
+    // Both servers offer the same tools
+    let name = format!("test_session_clash_{}", uuid::Uuid::new_v4());
+    let result = Session::with_profile(Some(name.clone()), "local".to_string(), profile, None, None, false).await;
+    let error = result.err().unwrap().to_string();
+    assert_eq!(
+        error,
+        "The tool echo is provided by both MCP server first and MCP server second, remove one of them from the profile"
+    );
+
+    std::fs::remove_file(rust_goose::cli::config::session_path(&name)).ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_resumed_session_restores_and_repairs_history() -> Result<()> {
+    use rust_goose::cli::config::session_path;
+    use rust_goose::exchange::{Content, Role};
+    use rust_goose::models::Profile;
+    use rust_goose::utils::session_file::{log_messages, read_from_file, read_header};
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // A session that crashed while running a tool
+    let name = format!("test_session_resume_{}", uuid::Uuid::new_v4());
+    log_messages(&session_path(&name), &[
+        Message::user("What is in this directory?"),
+        Message::new(Role::Assistant, vec![Content::ToolUse {
+            id: "call_1".to_string(),
+            name: "bash".to_string(),
+            parameters: json!({"command": "ls"}),
+        }]),
+    ])?;
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            format!(
+                "data: {}\n\ndata: [DONE]\n\n",
+                json!({"choices": [{"index": 0, "delta": {"content": "Let me try again"}}]}),
+            ),
+            "text/event-stream",
+        ))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let profile: Profile = serde_yaml::from_str(&format!(r#"
+provider: local
+processor: profile-model
+accelerator: none
+moderator: passive
+toolkits: []
+endpoints:
+  local:
+    base_url: {}/v1
+"#, server.uri()))?;
+    let mut session = Session::with_profile(Some(name), "local".to_string(), profile, None, None, false).await?;
+
+    // The interrupted call got an error result, in the history and the file
+    assert_eq!(session.messages.len(), 3);
+    assert!(matches!(session.messages[2].tool_result()[..], [Content::ToolResult { is_error: true, .. }]));
+    assert_eq!(read_from_file(&session.session_file_path)?, session.messages);
+    // and the file written before headers existed was given one
+    let header = read_header(&session.session_file_path)?.unwrap();
+    assert_eq!(header.description.as_deref(), Some("What is in this directory?"));
+
+    session.process_message(Message::user("Did it work?")).await?;
+    let requests = server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[0].body)?;
+    assert_eq!(body["messages"][0]["content"][0]["text"], "What is in this directory?");
+    assert_eq!(body["messages"][2]["role"], "tool");
+    assert_eq!(read_from_file(&session.session_file_path)?.len(), 5);
+
+    std::fs::remove_file(&session.session_file_path).ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_resumed_single_pass_renders_the_recap_as_json() -> Result<()> {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use rust_goose::cli::config::session_path;
+    use rust_goose::cli::output::JsonRenderer;
+    use rust_goose::cli::session::RunOutcome;
+    use rust_goose::models::Profile;
+    use rust_goose::utils::session_file::log_messages;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let name = format!("test_session_recap_{}", uuid::Uuid::new_v4());
+    log_messages(&session_path(&name), &[Message::user("What is in this directory?"), Message::assistant("Two files")])?;
+
+    // An empty message ends the run before anything is sent
+    let profile: Profile = serde_yaml::from_str(r#"
+provider: local
+processor: profile-model
+accelerator: none
+moderator: passive
+toolkits: []
+endpoints:
+  local:
+    base_url: http://127.0.0.1:9/v1
+"#)?;
+    let mut session = Session::with_profile(Some(name), "local".to_string(), profile, None, None, false).await?;
+    let buffer = Buffer::default();
+    session.set_renderer(Box::new(JsonRenderer::new(Box::new(buffer.clone()), false)));
+
+    assert_eq!(session.single_pass("").await?, RunOutcome::InvalidInput);
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone())?;
+    // Everything is written as one JSON document, the recap included
+    let events: Vec<serde_json::Value> = serde_json::from_str(&output)?;
+    let types: Vec<&str> = events.iter().map(|event| event["type"].as_str().unwrap()).collect();
+    assert_eq!(types, vec!["session_start", "recap", "error", "result"]);
+    assert_eq!(events[1]["message_count"], 2);
+    assert_eq!(events[1]["last_prompt"], "What is in this directory?");
+    assert_eq!(events[1]["last_reply"], "Two files");
+
+    std::fs::remove_file(&session.session_file_path).ok();
+    Ok(())
+}
+
+#[test]
+fn test_run_reports_setup_errors_as_json() -> Result<()> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    // A profile that fails validation, so the session cannot be created
+    let home = std::env::temp_dir().join(format!("goose-run-home-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(home.join(".config/goose"))?;
+    std::fs::write(home.join(".config/goose/profiles.yaml"), r#"
+broken:
+  provider: openai
+  processor: gpt-4o
+  accelerator: gpt-4o-mini
+  moderator: passive
+  toolkits:
+    - name: github
+      requires:
+        shell: default
+"#)?;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_rust-goose"))
+        .args(["run", "--profile", "broken", "--output-format", "stream-json"])
+        .env("HOME", &home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(b"Hello")?;
+    let output = child.wait_with_output()?;
+
+    assert_eq!(output.status.code(), Some(1));
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stdout)?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<Result<_, _>>()?;
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["type"], "error");
+    assert!(events[0]["message"].as_str().unwrap().contains("Profile 'broken' is invalid"));
+    assert_eq!(events[1]["type"], "result");
+    assert_eq!(events[1]["outcome"], "failed");
+    assert_eq!(events[1]["exit_code"], 1);
+    assert!(events[1]["session"].is_null());
+
+    std::fs::remove_dir_all(home).ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_failed_turn_is_rolled_back_after_moderation() -> Result<()> {
+    use rust_goose::cli::config::session_path;
+    use rust_goose::models::Profile;
+    use rust_goose::utils::session_file::{log_messages, read_from_file};
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // More history than fits in gpt-4's 8k context window
+    let name = format!("test_session_moderated_{}", uuid::Uuid::new_v4());
+    let long_text = "details ".repeat(2500);
+    let history = vec![
+        Message::user(&long_text),
+        Message::assistant(&long_text),
+        Message::user(&long_text),
+        Message::assistant("Noted"),
+    ];
+    log_messages(&session_path(&name), &history)?;
+
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({"error": {"message": "bad request"}})))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(
+            format!(
+                "data: {}\n\ndata: [DONE]\n\n",
+                json!({"choices": [{"index": 0, "delta": {"content": "Fine"}}]}),
+            ),
+            "text/event-stream",
+        ))
+        .mount(&server)
+        .await;
+
+    let profile: Profile = serde_yaml::from_str(&format!(r#"
+provider: local
+processor: gpt-4
+accelerator: none
+moderator: truncate
+toolkits: []
+endpoints:
+  local:
+    base_url: {}/v1
+"#, server.uri()))?;
+    let mut session = Session::with_profile(Some(name.clone()), "local".to_string(), profile.clone(), None, None, false).await?;
+
+    // The moderator truncated the history before the request failed, the
+    // history still goes back to how it was before the turn
+    assert!(session.process_message(Message::user("Next")).await.is_err());
+    assert_eq!(session.messages, history);
+    assert_eq!(read_from_file(&session.session_file_path)?, history);
+
+    session.process_message(Message::user("Next")).await?;
+    let requests = server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[1].body)?;
+    // The first turn was truncated away
+    assert_eq!(body["messages"].as_array().unwrap().len(), 3);
+    // The file keeps the whole conversation, not the truncated history
+    assert_eq!(read_from_file(&session.session_file_path)?.len(), 6);
+
+    // so resuming loads all of it and moderates it again
+    let mut resumed = Session::with_profile(Some(name), "local".to_string(), profile, None, None, false).await?;
+    assert_eq!(resumed.messages.len(), 6);
+    resumed.process_message(Message::user("And then?")).await?;
+    let requests = server.received_requests().await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&requests[2].body)?;
+    assert_eq!(body["messages"].as_array().unwrap().len(), 5);
+    assert_eq!(read_from_file(&resumed.session_file_path)?.len(), 8);
+
+    std::fs::remove_file(&resumed.session_file_path).ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_tool_calls_are_counted_after_moderation() -> Result<()> {
+    use rust_goose::cli::config::session_path;
+    use rust_goose::models::Profile;
+    use rust_goose::utils::session_file::log_messages;
+    use serde_json::json;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    // Truncating this to fit gpt-4's 8k context window leaves the history
+    // shorter after the turn than it was before
+    let name = format!("test_session_moderated_tools_{}", uuid::Uuid::new_v4());
+    let long_text = "details ".repeat(2500);
+    let mut history = Vec::new();
+    for _ in 0..3 {
+        history.push(Message::user(&long_text));
+        history.push(Message::assistant(&long_text));
+    }
+    history.push(Message::user(&long_text));
+    history.push(Message::assistant("Noted"));
+    log_messages(&session_path(&name), &history)?;
+
+    let sse = |chunk: serde_json::Value| {
+        ResponseTemplate::new(200).set_body_raw(format!("data: {}\n\ndata: [DONE]\n\n", chunk), "text/event-stream")
+    };
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(sse(json!({"choices": [{"index": 0, "delta": {"tool_calls": [
+            {"index": 0, "id": "call_1", "type": "function", "function": {"name": "bash", "arguments": "{\"command\": \"ls\"}"}}
+        ]}}]})))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/v1/chat/completions"))
+        .respond_with(sse(json!({"choices": [{"index": 0, "delta": {"content": "Fine"}}]})))
+        .mount(&server)
+        .await;
+
+    let profile: Profile = serde_yaml::from_str(&format!(r#"
+provider: local
+processor: gpt-4
+accelerator: none
+moderator: truncate
+toolkits: []
+endpoints:
+  local:
+    base_url: {}/v1
+"#, server.uri()))?;
+    let mut session = Session::with_profile(Some(name), "local".to_string(), profile, None, None, false).await?;
+
+    session.process_message(Message::user("Next")).await?;
+    assert!(session.messages.len() < history.len());
+    assert_eq!(session.stats.tool_calls, 1);
+
+    std::fs::remove_file(&session.session_file_path).ok();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_session_rejects_invalid_profile() -> Result<()> {
+    let profile: rust_goose::models::Profile = serde_yaml::from_str(r#"
+provider: openai
+processor: gpt-4o
+accelerator: gpt-4o-mini
+moderator: passive
+toolkits:
+  - name: github
+    requires:
+      shell: default
+"#)?;
+
+    let result = Session::with_profile(None, "broken".to_string(), profile, None, None, false).await;
+    let error = format!("{:#}", result.err().unwrap());
+    assert!(error.contains("Profile 'broken' is invalid"));
+    assert!(error.contains("requires default"));
+
+    Ok(())
+}
@@ -1,8 +1,18 @@
+mod common;
+
 use rust_goose::cli::session::Session;
 use anyhow::Result;
 
 #[tokio::test]
 async fn test_session_stats_integration() -> Result<()> {
+    dotenv::dotenv().ok();
+
+    // Skip test if no valid API key
+    if !common::has_valid_api_key() {
+        println!("Skipping test_session_stats_integration - no valid API key found");
+        return Ok(());
+    }
+
     // Create a new session
     let mut session = Session::new(
         Some("test_session".to_string()),
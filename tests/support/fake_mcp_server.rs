@@ -0,0 +1,92 @@
+//! A minimal MCP server over stdio for the MCP client tests
+//!
+//! It lists its tools over two pages and asks the client for a ping before
+//! answering each tool call, to exercise requests from the server.
+
+use std::io::{BufRead, Write};
+use serde_json::{json, Value};
+
+fn send(message: Value) {
+    let mut stdout = std::io::stdout().lock();
+    writeln!(stdout, "{}", message).unwrap();
+    stdout.flush().unwrap();
+}
+
+fn result(id: &Value, result: Value) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "result": result})
+}
+
+fn error(id: &Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn text(text: &str, is_error: bool) -> Value {
+    json!({"content": [{"type": "text", "text": text}], "isError": is_error})
+}
+
+fn tool(name: &str, description: &str, property: Option<&str>) -> Value {
+    let mut schema = json!({"type": "object", "properties": {}});
+    if let Some(property) = property {
+        schema["properties"][property] = json!({"type": "string"});
+        schema["required"] = json!([property]);
+    }
+    json!({"name": name, "description": description, "inputSchema": schema})
+}
+
+fn call_tool(params: &Value) -> Value {
+    let arguments = &params["arguments"];
+    match params["name"].as_str().unwrap_or_default() {
+        "echo" => text(arguments["text"].as_str().unwrap_or_default(), false),
+        "fail" => text("The tool failed on purpose", true),
+        "env" => {
+            let name = arguments["name"].as_str().unwrap_or_default();
+            text(&std::env::var(name).unwrap_or_default(), false)
+        }
+        name => text(&format!("Unknown tool: {}", name), true),
+    }
+}
+
+fn main() {
+    let mut lines = std::io::stdin().lock().lines();
+    let mut initialized = false;
+    let mut pings = 0;
+
+    while let Some(Ok(line)) = lines.next() {
+        let message: Value = serde_json::from_str(&line).expect("the client sends JSON-RPC");
+        let id = message["id"].clone();
+        let params = &message["params"];
+        eprintln!("received {}", message["method"]);
+
+        match message["method"].as_str() {
+            Some("initialize") => send(result(&id, json!({
+                "protocolVersion": params["protocolVersion"],
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": "fake", "version": "1.0.0"},
+                "instructions": "Use echo to repeat text back."
+            }))),
+            Some("notifications/initialized") => initialized = true,
+            Some(_) if !initialized => send(error(&id, -32002, "The server is not initialized")),
+            Some("tools/list") => match params["cursor"].as_str() {
+                None => send(result(&id, json!({
+                    "tools": [tool("echo", "Repeat the text", Some("text")), tool("fail", "Always fail", None)],
+                    "nextCursor": "2"
+                }))),
+                Some(_) => send(result(&id, json!({"tools": [tool("env", "Read an environment variable", Some("name"))]}))),
+            },
+            Some("tools/call") => {
+                pings += 1;
+                let ping = format!("ping-{}", pings);
+                send(json!({"jsonrpc": "2.0", "id": ping, "method": "ping"}));
+                for line in lines.by_ref() {
+                    let reply: Value = serde_json::from_str(&line.unwrap()).unwrap();
+                    if reply["id"] == ping {
+                        break;
+                    }
+                }
+                send(result(&id, call_tool(params)));
+            }
+            Some(method) => send(error(&id, -32601, &format!("Method not found: {}", method))),
+            None => {}
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use rust_goose::toolkit::{Tool, Toolkit, ToolkitResult};
+use rust_goose::toolkit::{Tool, Toolkit};
 use serde_json::json;
 
 #[derive(Debug)]
@@ -81,4 +81,119 @@ async fn test_toolkit_bash_execution() -> Result<()> {
     assert_eq!(result.text().trim(), "Hello, World!");
 
     Ok(())
-}
\ No newline at end of file
+}
+#[test]
+fn test_registry_builds_registered_toolkits() -> Result<()> {
+    use rust_goose::toolkit::ToolkitRegistry;
+
+    let mut registry = ToolkitRegistry::default();
+    registry.register("echo", "Echoes its parameters", &[], |_| {
+        Ok(Box::new(TestToolkit {
+            tools: vec![Tool::new("echo", "Echo the parameters", json!({"type": "object"}), vec![])],
+        }))
+    });
+    assert_eq!(registry.names(), vec!["default", "echo"]);
+
+    let profile: rust_goose::models::Profile = serde_yaml::from_str(r#"
+provider: openai
+processor: gpt-4o
+accelerator: gpt-4o-mini
+moderator: passive
+toolkits:
+  - name: echo
+  - name: default
+"#)?;
+    let toolkits = registry.create_for_profile(&profile)?;
+    assert_eq!(toolkits[0].tools()[0].name, "echo");
+    assert!(toolkits[1].tools().iter().any(|tool| tool.name == "bash"));
+
+    let echo = registry.list().into_iter().find(|toolkit| toolkit.name == "echo").unwrap();
+    assert_eq!(echo.description, "Echoes its parameters");
+    assert_eq!(echo.tools.len(), 1);
+    Ok(())
+}
+
+/// Counts the calls it gets, to tell whether toolkits share an instance
+#[derive(Debug, Default)]
+struct CountingToolkit {
+    calls: std::sync::atomic::AtomicUsize,
+}
+
+#[async_trait::async_trait]
+impl Toolkit for CountingToolkit {
+    fn tools(&self) -> Vec<Tool> {
+        vec![Tool::new("count", "Count this call", json!({"type": "object"}), vec![])]
+    }
+
+    async fn process_tool(&self, _tool_call: &Tool) -> Result<rust_goose::models::Message> {
+        let calls = self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+        Ok(rust_goose::models::Message::assistant(&calls.to_string()))
+    }
+}
+
+/// Calls into the toolkit it requires as `counter`
+#[derive(Debug)]
+struct DelegatingToolkit {
+    counter: std::sync::Arc<dyn Toolkit>,
+}
+
+#[async_trait::async_trait]
+impl Toolkit for DelegatingToolkit {
+    fn tools(&self) -> Vec<Tool> {
+        vec![Tool::new("count_twice", "Count two calls", json!({"type": "object"}), vec![])]
+    }
+
+    async fn process_tool(&self, _tool_call: &Tool) -> Result<rust_goose::models::Message> {
+        let count = Tool::new("count", "", json!({}), vec![]);
+        self.counter.process_tool(&count).await?;
+        self.counter.process_tool(&count).await
+    }
+}
+
+fn registry_with_requirements() -> rust_goose::toolkit::ToolkitRegistry {
+    let mut registry = rust_goose::toolkit::ToolkitRegistry::new();
+    registry.register("counter", "Counts calls", &[], |_| Ok(Box::new(CountingToolkit::default())));
+    registry.register("delegate", "Counts through its requirement", &["counter"], |requirements| {
+        Ok(Box::new(DelegatingToolkit { counter: requirements.require("counter")? }))
+    });
+    registry
+}
+
+#[tokio::test]
+async fn test_required_toolkits_are_shared() -> Result<()> {
+    let profile: rust_goose::models::Profile = serde_yaml::from_str(r#"
+provider: openai
+processor: gpt-4o
+accelerator: gpt-4o-mini
+moderator: passive
+toolkits:
+  - name: delegate
+    requires:
+      counter: counter
+  - name: counter
+"#)?;
+    let toolkits = registry_with_requirements().create_for_profile(&profile)?;
+
+    let reply = toolkits[0].process_tool(&Tool::new("count_twice", "", json!({}), vec![])).await?;
+    assert_eq!(reply.text(), "2");
+    // The session's counter is the one the delegate was given
+    let reply = toolkits[1].process_tool(&Tool::new("count", "", json!({}), vec![])).await?;
+    assert_eq!(reply.text(), "3");
+    Ok(())
+}
+
+#[test]
+fn test_unmapped_requirement_is_an_error() -> Result<()> {
+    let profile: rust_goose::models::Profile = serde_yaml::from_str(r#"
+provider: openai
+processor: gpt-4o
+accelerator: gpt-4o-mini
+moderator: passive
+toolkits:
+  - name: delegate
+  - name: counter
+"#)?;
+    let error = registry_with_requirements().create_for_profile(&profile).unwrap_err().to_string();
+    assert_eq!(error, "Toolkit delegate requires counter, map it to one of the profile's toolkits under requires");
+    Ok(())
+}